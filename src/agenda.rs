@@ -0,0 +1,341 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate, TimeZone};
+use colored::*;
+use serde_json::{json, Value};
+
+use crate::output::task_json;
+use todo_core::{
+    format::{format_duration, strip_colors, urgency_colour},
+    task::Task,
+    Error,
+};
+
+/// How far ahead `agenda` looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Range {
+    Today,
+    /// Today and the six days after it.
+    Week,
+    /// Today and the 30 days after it.
+    Month,
+}
+impl Range {
+    pub(crate) const NAMES: [&'static str; 3] = ["today", "week", "month"];
+
+    fn days(self) -> u64 {
+        match self {
+            Range::Today => 1,
+            Range::Week => 7,
+            Range::Month => 31,
+        }
+    }
+}
+impl FromStr for Range {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "today" => Ok(Range::Today),
+            "week" => Ok(Range::Week),
+            "month" => Ok(Range::Month),
+            other => Err(Error::Input(format!(
+                "Unknown range \"{other}\" (expected {})",
+                Range::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// The day `task` is due, `None` if its deadline is out of range.
+fn due_date(task: &Task) -> Option<NaiveDate> {
+    Local
+        .timestamp_opt(task.deadline, 0)
+        .single()
+        .map(|t| t.date_naive())
+}
+
+/// Open tasks due within `range`, grouped by due day in date order. Overdue
+/// tasks come first in a group without a date.
+pub(crate) fn agenda(tasks: &[Task], range: Range) -> Vec<(Option<NaiveDate>, Vec<&Task>)> {
+    let today = Local::now().date_naive();
+    let end = today + Days::new(range.days());
+
+    let mut open: Vec<&Task> = tasks.iter().filter(|t| t.is_open()).collect();
+    open.sort_by_key(|t| (t.deadline, t.id()));
+
+    let mut groups: Vec<(Option<NaiveDate>, Vec<&Task>)> = Vec::new();
+    for task in open {
+        let Some(due) = due_date(task) else {
+            continue;
+        };
+        let day = if task.get_time_left() < 0 {
+            None
+        } else if due < end {
+            Some(due)
+        } else {
+            continue;
+        };
+        match groups.last_mut() {
+            Some((d, group)) if *d == day => group.push(task),
+            _ => groups.push((day, vec![task])),
+        }
+    }
+    groups
+}
+
+pub(crate) fn render_agenda(groups: &[(Option<NaiveDate>, Vec<&Task>)]) -> String {
+    if groups.is_empty() {
+        return "Nothing due".green().to_string();
+    }
+    let today = Local::now().date_naive();
+
+    groups
+        .iter()
+        .map(|(day, tasks)| {
+            let header = match day {
+                None => "Overdue".bright_red().bold(),
+                Some(day) if *day == today => {
+                    format!("Today, {}", day.format("%a %Y-%m-%d")).bold()
+                }
+                Some(day) if *day == today + Days::new(1) => {
+                    format!("Tomorrow, {}", day.format("%a %Y-%m-%d")).bold()
+                }
+                Some(day) => day.format("%a %Y-%m-%d").to_string().bold(),
+            };
+            let lines = tasks.iter().map(|t| {
+                let tl = t.get_time_left();
+                let when = match day {
+                    None => due_date(t)
+                        .map(|d| d.format("%m-%d").to_string())
+                        .unwrap_or_default(),
+                    Some(_) => Local
+                        .timestamp_opt(t.deadline, 0)
+                        .single()
+                        .map(|t| t.format("%H:%M").to_string())
+                        .unwrap_or_default(),
+                };
+                format!(
+                    "  {}  {} {}  {}  {}",
+                    when.truecolor(255, 140, 0),
+                    format!("{:>3}", t.id()).cyan(),
+                    t.name.bold(),
+                    urgency_colour(&format_duration(tl), tl),
+                    format!(
+                        "({} left to do)",
+                        strip_colors(&format_duration(t.get_remaining())).trim_end()
+                    )
+                    .truecolor(128, 128, 128)
+                )
+            });
+            std::iter::once(header.to_string())
+                .chain(lines)
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub(crate) fn agenda_json(groups: &[(Option<NaiveDate>, Vec<&Task>)]) -> Vec<Value> {
+    groups
+        .iter()
+        .map(|(day, tasks)| {
+            json!({
+                "date": day.map(|d| d.to_string()),
+                "overdue": day.is_none(),
+                "tasks": tasks.iter().map(|t| task_json(t)).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// Open tasks due on each day of the month starting on `first`.
+pub(crate) fn month(tasks: &[Task], first: NaiveDate) -> Vec<(NaiveDate, Vec<&Task>)> {
+    let next = first + Months::new(1);
+    first
+        .iter_days()
+        .take_while(|d| *d < next)
+        .map(|day| {
+            let due = tasks
+                .iter()
+                .filter(|t| t.is_open() && due_date(t) == Some(day))
+                .collect();
+            (day, due)
+        })
+        .collect()
+}
+
+/// Width of a day cell in the calendar grid, including the separator.
+const CELL: usize = 7;
+
+/// A Monday-first month grid. Days with tasks due show how many, coloured
+/// by the time left on the most urgent of them; today is underlined.
+pub(crate) fn render_calendar(days: &[(NaiveDate, Vec<&Task>)]) -> String {
+    let Some((first, _)) = days.first() else {
+        return String::new();
+    };
+    let today = Local::now().date_naive();
+
+    let title = first.format("%B %Y").to_string();
+    let width = CELL * 7;
+    let mut lines = vec![format!("{:^width$}", title).trim_end().bold().to_string()];
+    lines.push(
+        ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+            .iter()
+            .map(|d| format!("{:<CELL$}", d))
+            .collect::<String>()
+            .trim_end()
+            .truecolor(128, 128, 128)
+            .to_string(),
+    );
+
+    let mut line = " ".repeat(CELL * first.weekday().num_days_from_monday() as usize);
+    for (day, tasks) in days {
+        let text = match tasks.len() {
+            0 => format!("{:>2}", day.day()),
+            n => format!("{:>2} ·{}", day.day(), n),
+        };
+        let padding = " ".repeat(CELL.saturating_sub(text.chars().count()));
+        let mut cell = match tasks.iter().map(|t| t.get_time_left()).min() {
+            Some(tl) => urgency_colour(&text, tl).bold(),
+            None => text.normal(),
+        };
+        if *day == today {
+            cell = cell.underline();
+        }
+        line += &format!("{cell}{padding}");
+
+        if day.weekday() == chrono::Weekday::Sun {
+            lines.push(line.trim_end().to_owned());
+            line = String::new();
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line.trim_end().to_owned());
+    }
+    lines.join("\n")
+}
+
+pub(crate) fn calendar_json(days: &[(NaiveDate, Vec<&Task>)]) -> Value {
+    json!({
+        "month": days.first().map(|(d, _)| d.format("%Y-%m").to_string()),
+        "days": days
+            .iter()
+            .map(|(day, tasks)| json!({
+                "date": day.to_string(),
+                "due": tasks.len(),
+                "ids": tasks.iter().map(|t| t.id()).collect::<Vec<_>>(),
+                "time_left": tasks.iter().map(|t| t.get_time_left()).min(),
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn task(id: i64, deadline: i64) -> Task {
+        let mut task = Task::with_id(id);
+        task.deadline = deadline;
+        task.estimated_time = HOUR;
+        task
+    }
+
+    /// A timestamp on `day` at `hour` o'clock.
+    fn at(day: NaiveDate, hour: u32) -> i64 {
+        day.and_hms_opt(hour, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap()
+            .timestamp()
+    }
+
+    fn ids(tasks: &[&Task]) -> Vec<i64> {
+        tasks.iter().map(|t| t.id()).collect()
+    }
+
+    #[test]
+    fn groups_open_tasks_by_due_day_after_the_overdue_ones() {
+        let today = Local::now().date_naive();
+        let mut completed = task(4, at(today + Days::new(2), 9));
+        completed.completed = 1;
+        let tasks = [
+            task(0, at(today + Days::new(2), 15)),
+            task(1, at(today + Days::new(10), 12)),
+            task(2, Local::now().timestamp() - HOUR),
+            task(3, at(today + Days::new(2), 9)),
+            completed,
+        ];
+
+        let week = agenda(&tasks, Range::Week);
+        let groups: Vec<_> = week.iter().map(|(day, t)| (*day, ids(t))).collect();
+        assert_eq!(
+            groups,
+            [(None, vec![2]), (Some(today + Days::new(2)), vec![3, 0])]
+        );
+
+        let month = agenda(&tasks, Range::Month);
+        assert_eq!(month.len(), 3);
+        assert_eq!(month[2].0, Some(today + Days::new(10)));
+
+        let today_only = agenda(&tasks, Range::Today);
+        assert_eq!(today_only.len(), 1);
+        assert_eq!(today_only[0].0, None);
+    }
+
+    #[test]
+    fn deadlines_out_of_range_are_skipped() {
+        let today = Local::now().date_naive();
+        let tasks = [
+            task(0, i64::MAX / 2),
+            task(1, -i64::MAX / 2),
+            task(2, at(today, 23)),
+        ];
+        let groups = agenda(&tasks, Range::Week);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(ids(&groups[0].1), [2]);
+        assert!(!render_agenda(&groups).is_empty());
+    }
+
+    #[test]
+    fn month_holds_every_day_with_the_tasks_due_on_it() {
+        let first = NaiveDate::from_ymd_opt(2100, 2, 1).unwrap();
+        let tenth = NaiveDate::from_ymd_opt(2100, 2, 10).unwrap();
+        let tasks = [
+            task(0, at(tenth, 9)),
+            task(1, at(tenth, 17)),
+            task(2, at(NaiveDate::from_ymd_opt(2100, 3, 1).unwrap(), 9)),
+        ];
+
+        let days = month(&tasks, first);
+        assert_eq!(days.len(), 28);
+        assert_eq!(days[0].0, first);
+        assert_eq!(days[9].0, tenth);
+        assert_eq!(ids(&days[9].1), [0, 1]);
+        assert_eq!(days.iter().map(|(_, t)| t.len()).sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn calendar_starts_weeks_on_monday() {
+        // February 2100 starts on a Monday and has four full weeks.
+        let first = NaiveDate::from_ymd_opt(2100, 2, 1).unwrap();
+        let tasks = [task(
+            0,
+            at(NaiveDate::from_ymd_opt(2100, 2, 10).unwrap(), 9),
+        )];
+        let calendar = strip_colors(&render_calendar(&month(&tasks, first)));
+        let lines: Vec<_> = calendar.lines().collect();
+
+        assert_eq!(lines[0].trim(), "February 2100");
+        assert!(lines[1].starts_with("Mon"));
+        assert_eq!(lines.len(), 2 + 4);
+        assert!(lines[2].starts_with(" 1"));
+        assert!(lines[3].contains("10 ·1"));
+        assert!(lines[5].ends_with("28"));
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+
+use todo_core::{task::Task, Backend, Result, TaskStore};
+
+use crate::passphrase;
+
+/// How long a task stays in the task file after being completed, unless
+/// `--older-than` or the `archive_after` setting say otherwise.
+pub(crate) const DEFAULT_AGE: i64 = 30 * 86400;
+
+/// The archive of `file_path`, `<file>.archive` next to it.
+pub(crate) fn path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.file_name().unwrap_or_default().to_owned();
+    name.push(".archive");
+    file_path.with_file_name(name)
+}
+
+/// Reads the archive of `file_path`, or starts an empty one.
+///
+/// Archives use the binary format whatever the task file's backend, but are
+/// encrypted when the task file is, with the same passphrase if it was given
+/// already, so archiving never writes tasks out in the clear.
+pub(crate) fn open(file_path: &Path) -> Result<TaskStore> {
+    let archive = path(file_path);
+    let default = match Backend::for_path(file_path, Backend::Binary)? {
+        Backend::Encrypted => Backend::Encrypted,
+        _ => Backend::Binary,
+    };
+    passphrase::alias(&archive, file_path);
+    TaskStore::load(&archive, default)
+}
+
+/// Whether `task` was completed or cancelled at least `age` seconds before
+/// `now`.
+pub(crate) fn is_stale(task: &Task, age: i64, now: i64) -> bool {
+    let finished = task.completed.max(task.cancelled);
+    !task.is_open() && finished <= now - age
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_sit_next_to_their_task_file() {
+        assert_eq!(
+            path(Path::new("/home/me/task_list")),
+            Path::new("/home/me/task_list.archive")
+        );
+        assert_eq!(path(Path::new("tasks.db")), Path::new("tasks.db.archive"));
+    }
+
+    #[test]
+    fn only_tasks_completed_long_enough_ago_are_stale() {
+        let now = 1_800_000_000;
+        let mut task = Task::with_id(0);
+        task.estimated_time = 3600;
+        assert!(!is_stale(&task, 0, now));
+
+        task.progress = 3600;
+        task.completed = now - 86400;
+        assert!(is_stale(&task, 86400, now));
+        assert!(is_stale(&task, 3600, now));
+        assert!(!is_stale(&task, 2 * 86400, now));
+    }
+
+    #[test]
+    fn cancelled_tasks_go_stale_too() {
+        let now = 1_800_000_000;
+        let mut task = Task::with_id(0);
+        task.cancelled = now - 86400;
+        assert!(is_stale(&task, 3600, now));
+        assert!(!is_stale(&task, 2 * 86400, now));
+    }
+}
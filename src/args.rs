@@ -0,0 +1,504 @@
+use todo_core::{filter::Filter, search, task::Task, Error, Result, Uuid};
+
+pub(crate) const DEFAULT_FILE: &str = "./task_list";
+
+/// Command line arguments split into positionals and `--name [value]` options.
+///
+/// Positionals keep their original indices relative to each other, so
+/// `positional[0]` is still the program and `positional[1]` the command.
+pub(crate) struct Args {
+    pub(crate) positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+impl Args {
+    /// Removes every occurrence of `--name <value>` (or `--name=value`) from
+    /// `raw`, returning the last value. Used for options that apply to every
+    /// command.
+    pub(crate) fn take_global(raw: &mut Vec<String>, name: &str) -> Result<Option<String>> {
+        let flag = format!("--{name}");
+        let prefix = format!("--{name}=");
+        let mut value = None;
+        let mut i = 0;
+
+        while i < raw.len() {
+            if raw[i] == "--" {
+                break;
+            } else if raw[i] == flag {
+                if i + 1 >= raw.len() {
+                    return Err(Error::Input(format!("Option {flag} requires a value")));
+                }
+                value = Some(raw.remove(i + 1));
+                raw.remove(i);
+            } else if let Some(v) = raw[i].strip_prefix(&prefix) {
+                value = Some(v.to_owned());
+                raw.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Splits `raw` into positionals and options. Options listed in `valued`
+    /// consume the following argument (or the part after `=`) as their value,
+    /// every other `--name` is treated as a switch.
+    pub(crate) fn parse(raw: &[String], valued: &[&str]) -> Result<Self> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut iter = raw.iter();
+
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                positional.extend(iter.by_ref().cloned());
+                break;
+            }
+
+            let Some(name) = arg.strip_prefix("--").filter(|n| !n.is_empty()) else {
+                positional.push(arg.clone());
+                continue;
+            };
+
+            match name.split_once('=') {
+                Some((name, value)) => options.push((name.to_owned(), Some(value.to_owned()))),
+                None if valued.contains(&name) => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| Error::Input(format!("Option --{name} requires a value")))?;
+                    options.push((name.to_owned(), Some(value.clone())));
+                }
+                None => options.push((name.to_owned(), None)),
+            }
+        }
+
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    /// Last value given for option `name`.
+    pub(crate) fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    pub(crate) fn switch(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+}
+
+/// How a command addresses the task it acts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Target {
+    Id(i64),
+    /// A whole UUID, or the start of one after `@`, which has to be unique.
+    Uuid(String),
+    /// `--match <query>`, resolved by fuzzy search when unambiguous.
+    Match(String),
+}
+impl Args {
+    /// The task addressed by the command together with the positionals that
+    /// follow it. The target is `--match <query>` if given, otherwise the id
+    /// or UUID prefix right after the command.
+    pub(crate) fn target(&self) -> Result<(Target, &[String])> {
+        if let Some(query) = self.option("match") {
+            return Ok((Target::Match(query.to_owned()), &self.positional[2..]));
+        }
+
+        let arg = self.positional.get(2).ok_or(Error::InvalidArguments)?;
+        Ok((Target::parse(arg)?, &self.positional[3..]))
+    }
+}
+
+/// The UUID `arg` addresses a task by: the start of one after `@`, as in
+/// `@1f3a` or `@1234`, or a whole hyphenated UUID. Anything else is an id, a
+/// range or no task at all, so words that happen to be hex such as `cafe`
+/// are never taken for a UUID.
+fn uuid_prefix(arg: &str) -> Option<&str> {
+    match arg.strip_prefix('@') {
+        Some(prefix) => (prefix.chars().any(|c| c.is_ascii_hexdigit())
+            && prefix.chars().all(|c| c.is_ascii_hexdigit() || c == '-'))
+        .then_some(prefix),
+        None => (arg.len() == 36 && arg.parse::<Uuid>().is_ok()).then_some(arg),
+    }
+}
+impl Target {
+    /// An id, `@` and the start of a UUID, or a whole UUID.
+    pub(crate) fn parse(arg: &str) -> Result<Self> {
+        if let Ok(id) = arg.parse() {
+            return Ok(Target::Id(id));
+        }
+        match uuid_prefix(arg) {
+            Some(prefix) => Ok(Target::Uuid(prefix.to_owned())),
+            None => Err(Error::Parse("Invalid task ID".into())),
+        }
+    }
+
+    /// Index of the addressed task in `tasks`.
+    pub(crate) fn find(&self, tasks: &[Task]) -> Result<usize> {
+        let id = match self {
+            Target::Id(id) => *id,
+            Target::Uuid(prefix) => {
+                let found: Vec<&Task> = tasks
+                    .iter()
+                    .filter(|t| t.uuid().starts_with(prefix))
+                    .collect();
+                match found.as_slice() {
+                    [] => return Err(Error::TaskNotFound),
+                    [task] => task.id(),
+                    candidates => {
+                        return Err(Error::AmbiguousMatch(
+                            candidates
+                                .iter()
+                                .map(|t| format!("{} ({})", t.id(), t.uuid().short()))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ))
+                    }
+                }
+            }
+            Target::Match(query) => match search::unique_match(query, tasks) {
+                Ok(Some(task)) => task.id(),
+                Ok(None) => return Err(Error::TaskNotFound),
+                Err(candidates) => {
+                    return Err(Error::AmbiguousMatch(
+                        candidates
+                            .iter()
+                            .map(|t| format!("{} ({})", t.id(), t.name))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ))
+                }
+            },
+        };
+
+        tasks
+            .iter()
+            .position(|t| t.id() == id)
+            .ok_or(Error::TaskNotFound)
+    }
+}
+
+/// One entry of a list of ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Id {
+    /// An inclusive range such as `4-9`; a single id is a range of one.
+    Range(i64, i64),
+    /// A whole UUID, or the start of one given after `@`.
+    Uuid(String),
+}
+
+/// The tasks a bulk command acts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Selection {
+    /// Ids, inclusive id ranges and UUID prefixes such as `3 5 7`, `4-9` or
+    /// `@1f3a`.
+    Ids(Vec<Id>),
+    /// `--match <query>`.
+    Match(String),
+    /// `--where <filter>`.
+    Where(Filter),
+}
+impl Args {
+    /// The tasks addressed by a bulk command together with the positionals
+    /// that follow them: `--where <filter>` or `--match <query>` if given,
+    /// otherwise the ids and ranges right after the command.
+    pub(crate) fn selection(&self) -> Result<(Selection, &[String])> {
+        if let Some(filter) = self.option("where") {
+            return Ok((Selection::Where(filter.parse()?), &self.positional[2..]));
+        }
+        if let Some(query) = self.option("match") {
+            return Ok((Selection::Match(query.to_owned()), &self.positional[2..]));
+        }
+
+        let mut ids = Vec::new();
+        let mut end = 2;
+        while let Some(arg) = self.positional.get(end) {
+            match parse_ids(arg) {
+                Some(parsed) => ids.extend(parsed),
+                None => break,
+            }
+            end += 1;
+        }
+        if ids.is_empty() {
+            return match self.positional.get(2) {
+                Some(_) => Err(Error::Parse("Invalid task ID".into())),
+                None => Err(Error::InvalidArguments),
+            };
+        }
+        Ok((Selection::Ids(ids), &self.positional[end..]))
+    }
+}
+
+/// Parses `3`, `4-9`, `@` and a UUID prefix, a whole UUID or a comma
+/// separated list of them.
+fn parse_ids(arg: &str) -> Option<Vec<Id>> {
+    arg.split(',')
+        .map(|part| {
+            if let Ok(id) = part.parse() {
+                return Some(Id::Range(id, id));
+            }
+            match part.split_once('-') {
+                Some((from, to)) if from.parse::<i64>().is_ok() && to.parse::<i64>().is_ok() => {
+                    Some(Id::Range(from.parse().ok()?, to.parse().ok()?))
+                }
+                _ => uuid_prefix(part).map(|prefix| Id::Uuid(prefix.to_owned())),
+            }
+        })
+        .collect()
+}
+
+impl Selection {
+    /// Whether the selection was written in a form that can address several
+    /// tasks, as opposed to a single id or `--match`.
+    pub(crate) fn is_bulk(&self) -> bool {
+        match self {
+            Selection::Ids(ids) => {
+                !matches!(ids.as_slice(), [Id::Range(from, to)] if from == to)
+                    && !matches!(ids.as_slice(), [Id::Uuid(_)])
+            }
+            Selection::Match(_) => false,
+            Selection::Where(_) => true,
+        }
+    }
+
+    /// Whether changing the `affected` tasks has to be confirmed: several
+    /// tasks addressed at once, or a `--match` that found a task by more
+    /// than its exact name, which may not be the task that was meant.
+    pub(crate) fn needs_confirmation(&self, affected: &[Task]) -> bool {
+        match self {
+            Selection::Match(query) => affected
+                .iter()
+                .any(|t| t.name.trim().to_lowercase() != query.trim().to_lowercase()),
+            _ => self.is_bulk() && affected.len() > 1,
+        }
+    }
+
+    /// Indices of the selected tasks in `tasks`, in task order. Single ids
+    /// and UUID prefixes have to match, ranges only cover the ids in use.
+    pub(crate) fn find(&self, tasks: &[Task]) -> Result<Vec<usize>> {
+        match self {
+            Selection::Match(query) => Ok(vec![Target::Match(query.clone()).find(tasks)?]),
+            Selection::Where(filter) => Ok((0..tasks.len())
+                .filter(|&i| filter.matches(&tasks[i]))
+                .collect()),
+            Selection::Ids(ids) => {
+                let mut uuids = Vec::new();
+                for id in ids {
+                    match id {
+                        Id::Range(from, to) => {
+                            if from == to && !tasks.iter().any(|t| t.id() == *from) {
+                                return Err(Error::TaskNotFound);
+                            }
+                        }
+                        Id::Uuid(prefix) => {
+                            let i = Target::Uuid(prefix.clone()).find(tasks)?;
+                            uuids.push(tasks[i].uuid());
+                        }
+                    }
+                }
+                let indices: Vec<usize> = (0..tasks.len())
+                    .filter(|&i| {
+                        let id = tasks[i].id();
+                        uuids.contains(&tasks[i].uuid())
+                            || ids.iter().any(|entry| {
+                                matches!(entry, Id::Range(from, to) if *from <= id && id <= *to)
+                            })
+                    })
+                    .collect();
+                if indices.is_empty() {
+                    return Err(Error::TaskNotFound);
+                }
+                Ok(indices)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(args: &[&str]) -> Vec<String> {
+        ["todo-cli", "remove"]
+            .iter()
+            .chain(args)
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn select(args: &[&str]) -> Result<(Selection, Vec<String>)> {
+        let args = Args::parse(&raw(args), &["where", "match"])?;
+        let (selection, rest) = args.selection()?;
+        Ok((selection, rest.to_vec()))
+    }
+
+    #[test]
+    fn splits_positionals_and_options() {
+        let args = Args::parse(
+            &raw(&[
+                "3",
+                "--sort",
+                "name",
+                "--reverse",
+                "--view=table",
+                "--",
+                "--x",
+            ]),
+            &["sort", "view"],
+        )
+        .unwrap();
+
+        assert_eq!(args.positional, ["todo-cli", "remove", "3", "--x"]);
+        assert_eq!(args.option("sort"), Some("name"));
+        assert_eq!(args.option("view"), Some("table"));
+        assert!(args.switch("reverse"));
+        assert!(!args.switch("yes"));
+        assert!(Args::parse(&raw(&["--sort"]), &["sort"]).is_err());
+    }
+
+    #[test]
+    fn global_options_are_taken_from_anywhere() {
+        let mut args = raw(&["--output", "json", "3", "--output=ndjson", "--", "--output"]);
+        let value = Args::take_global(&mut args, "output").unwrap();
+
+        assert_eq!(value.as_deref(), Some("ndjson"));
+        assert_eq!(args, raw(&["3", "--", "--output"]));
+    }
+
+    #[test]
+    fn ids_and_ranges_stop_at_the_first_other_word() {
+        let (selection, rest) = select(&["3", "5-7,9", "high"]).unwrap();
+        assert_eq!(
+            selection,
+            Selection::Ids(vec![Id::Range(3, 3), Id::Range(5, 7), Id::Range(9, 9)])
+        );
+        assert_eq!(rest, ["high"]);
+
+        assert!(matches!(select(&["x"]), Err(Error::Parse(_))));
+        assert!(matches!(select(&[]), Err(Error::InvalidArguments)));
+    }
+
+    #[test]
+    fn where_and_match_replace_the_ids() {
+        let (selection, rest) = select(&["--where", "overdue", "priority=high"]).unwrap();
+        assert_eq!(selection, Selection::Where("overdue".parse().unwrap()));
+        assert_eq!(rest, ["priority=high"]);
+
+        let (selection, _) = select(&["--match", "report"]).unwrap();
+        assert_eq!(selection, Selection::Match("report".into()));
+    }
+
+    #[test]
+    fn ranges_cover_only_the_ids_in_use() {
+        let tasks: Vec<Task> = [0, 2, 3, 7].into_iter().map(Task::with_id).collect();
+        let find = |args: &[&str]| select(args).unwrap().0.find(&tasks);
+
+        assert_eq!(find(&["1-5"]).unwrap(), [1, 2]);
+        // Task order, each task once, whatever order the ids came in.
+        assert_eq!(find(&["7,0-3,3"]).unwrap(), [0, 1, 2, 3]);
+        assert_eq!(find(&["7", "2"]).unwrap(), [1, 3]);
+        assert!(matches!(find(&["4"]), Err(Error::TaskNotFound)));
+        assert!(matches!(find(&["3,4"]), Err(Error::TaskNotFound)));
+        assert!(matches!(find(&["10-20"]), Err(Error::TaskNotFound)));
+    }
+
+    #[test]
+    fn only_single_ids_are_not_bulk() {
+        let bulk = |args: &[&str]| select(args).unwrap().0.is_bulk();
+        assert!(!bulk(&["3"]));
+        assert!(bulk(&["3-4"]));
+        assert!(bulk(&["3,4"]));
+        assert!(bulk(&["3", "4"]));
+        assert!(bulk(&["--where", "open"]));
+    }
+
+    #[test]
+    fn matches_other_than_the_exact_name_need_confirming() {
+        let mut task = Task::with_id(0);
+        task.name = "Pay rent".into();
+        let affected = [task];
+        let confirm =
+            |args: &[&str], affected: &[Task]| select(args).unwrap().0.needs_confirmation(affected);
+        assert!(confirm(&["--match", "rent"], &affected));
+        assert!(!confirm(&["--match", "pay RENT"], &affected));
+        assert!(!confirm(&["0"], &affected));
+        assert!(!confirm(&["0-3"], &affected));
+        assert!(confirm(&["0-3"], &[affected[0].clone(), Task::with_id(1)]));
+    }
+
+    #[test]
+    fn numbers_are_ids_and_ranges() {
+        assert_eq!(Target::parse("12").unwrap(), Target::Id(12));
+        assert_eq!(parse_ids("1234-5678"), Some(vec![Id::Range(1234, 5678)]));
+        assert_eq!(
+            parse_ids("3,5-7"),
+            Some(vec![Id::Range(3, 3), Id::Range(5, 7)])
+        );
+    }
+
+    #[test]
+    fn hex_words_are_not_uuids() {
+        for word in ["cafe", "deadbeef", "1f3a", "abc-def"] {
+            assert!(Target::parse(word).is_err(), "{word}");
+            assert_eq!(parse_ids(word), None, "{word}");
+        }
+    }
+
+    #[test]
+    fn uuid_prefixes_need_an_at_sign() {
+        assert_eq!(Target::parse("@1f3a").unwrap(), Target::Uuid("1f3a".into()));
+        // Digits only, which without `@` would be an id or a range.
+        assert_eq!(Target::parse("@1234").unwrap(), Target::Uuid("1234".into()));
+        assert_eq!(
+            parse_ids("@1234-5678"),
+            Some(vec![Id::Uuid("1234-5678".into())])
+        );
+        assert!(Target::parse("@").is_err());
+        assert!(Target::parse("@xyz").is_err());
+    }
+
+    #[test]
+    fn whole_uuids_need_no_at_sign() {
+        let uuid = "1f3a5c7e-0000-4000-8000-000000000000";
+        assert_eq!(Target::parse(uuid).unwrap(), Target::Uuid(uuid.into()));
+        assert_eq!(parse_ids(uuid), Some(vec![Id::Uuid(uuid.into())]));
+    }
+
+    #[test]
+    fn ids_stop_at_the_first_word_that_isnt_one() {
+        let (selection, rest) = select(&["3", "cafe"]).unwrap();
+        assert_eq!(selection, Selection::Ids(vec![Id::Range(3, 3)]));
+        assert_eq!(rest, ["cafe"]);
+
+        let (selection, rest) = select(&["3", "@cafe", "list"]).unwrap();
+        assert_eq!(
+            selection,
+            Selection::Ids(vec![Id::Range(3, 3), Id::Uuid("cafe".into())])
+        );
+        assert_eq!(rest, ["list"]);
+    }
+
+    #[test]
+    fn uuid_prefixes_find_unique_tasks() {
+        let mut tasks = vec![Task::with_id(0), Task::with_id(1)];
+        let uuid = tasks[1].uuid().to_string();
+        assert_eq!(Target::parse(&uuid).unwrap().find(&tasks).unwrap(), 1);
+        assert_eq!(
+            Target::parse(&format!("@{}", &uuid[..8]))
+                .unwrap()
+                .find(&tasks)
+                .unwrap(),
+            1
+        );
+
+        tasks.truncate(1);
+        assert!(matches!(
+            Target::parse(&uuid).unwrap().find(&tasks),
+            Err(Error::TaskNotFound)
+        ));
+    }
+}
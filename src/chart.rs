@@ -0,0 +1,293 @@
+use chrono::{Days, Local, NaiveDate, TimeZone};
+use colored::*;
+use serde_json::{json, Value};
+
+use todo_core::{
+    format::{format_duration, strip_colors, terminal_width},
+    task::Task,
+};
+
+/// Room taken by the date label and the value printed around each bar.
+const LABEL_WIDTH: usize = 12;
+const VALUE_WIDTH: usize = 22;
+
+/// Remaining estimated work at the end of each of the last `days` days,
+/// today included, rebuilt from the creation times and progress logs.
+pub(crate) fn burndown(tasks: &[Task], days: usize) -> Vec<(NaiveDate, i64)> {
+    let now = Local::now();
+    let today = now.date_naive();
+
+    (0..days as u64)
+        .rev()
+        .map(|i| {
+            let day = today - Days::new(i);
+            let end = if i == 0 {
+                now.timestamp()
+            } else {
+                end_of_day(day)
+            };
+            let remaining = tasks
+                .iter()
+                .filter(|t| t.created == 0 || t.created <= end)
+                .map(|t| remaining_at(t, end))
+                .sum();
+            (day, remaining)
+        })
+        .collect()
+}
+
+/// Work still to be done on `task` at `timestamp`. Progress that predates
+/// the log is counted from the start.
+fn remaining_at(task: &Task, timestamp: i64) -> i64 {
+    let logged: i64 = task.log.iter().map(|e| e.amount).sum();
+    let progress = task.progress - logged
+        + task
+            .log
+            .iter()
+            .filter(|e| e.timestamp <= timestamp)
+            .map(|e| e.amount)
+            .sum::<i64>();
+    task.estimated_time - progress.clamp(0, task.estimated_time)
+}
+
+fn end_of_day(day: NaiveDate) -> i64 {
+    day.and_hms_opt(23, 59, 59)
+        .unwrap()
+        .and_local_timezone(Local)
+        .earliest()
+        .map_or(0, |t| t.timestamp())
+}
+
+/// A day of the workload histogram.
+pub(crate) struct Day {
+    /// `None` for the row collecting every overdue task.
+    pub(crate) date: Option<NaiveDate>,
+    pub(crate) tasks: usize,
+    pub(crate) work: i64,
+}
+
+/// Remaining work of the open tasks due on each day of the next `weeks`
+/// weeks, preceded by the work already overdue.
+pub(crate) fn workload(tasks: &[Task], weeks: usize) -> Vec<Day> {
+    let today = Local::now().date_naive();
+    let mut days = vec![Day {
+        date: None,
+        tasks: 0,
+        work: 0,
+    }];
+    days.extend((0..weeks as u64 * 7).map(|i| Day {
+        date: Some(today + Days::new(i)),
+        tasks: 0,
+        work: 0,
+    }));
+
+    for task in tasks.iter().filter(|t| t.is_open()) {
+        let day = if task.get_time_left() < 0 {
+            days.first_mut()
+        } else {
+            // Deadlines out of range fall on no day.
+            let due = Local.timestamp_opt(task.deadline, 0).single();
+            due.and_then(|due| days.iter_mut().find(|d| d.date == Some(due.date_naive())))
+        };
+        if let Some(day) = day {
+            day.tasks += 1;
+            day.work += task.get_remaining();
+        }
+    }
+    days
+}
+
+/// A bar of `width` characters filled to `fraction`, drawn like the ones of
+/// `progress_bar`.
+fn bar(fraction: f32, width: usize, colour: Color) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * width as f32).round() as usize;
+    format!(
+        "{}{}",
+        "━".repeat(filled).color(colour),
+        "━".repeat(width - filled).truecolor(64, 64, 64)
+    )
+}
+
+fn bar_width() -> usize {
+    terminal_width()
+        .unwrap_or(80)
+        .saturating_sub(LABEL_WIDTH + VALUE_WIDTH)
+        .clamp(10, 60)
+}
+
+fn duration(d: i64) -> String {
+    strip_colors(&format_duration(d)).trim_end().to_owned()
+}
+
+pub(crate) fn render_burndown(points: &[(NaiveDate, i64)]) -> String {
+    let width = bar_width();
+    let most = points.iter().map(|(_, r)| *r).max().unwrap_or(0).max(1);
+
+    let mut lines = vec!["Burndown of remaining work".bold().to_string()];
+    lines.extend(points.iter().map(|(day, remaining)| {
+        format!(
+            "{:<LABEL_WIDTH$}{} {}",
+            day.format("%a %m-%d").to_string().truecolor(128, 128, 128),
+            bar(*remaining as f32 / most as f32, width, Color::Cyan),
+            duration(*remaining).cyan()
+        )
+    }));
+    lines.join("\n")
+}
+
+/// The histogram, with days whose work exceeds `capacity` in red.
+pub(crate) fn render_workload(days: &[Day], capacity: i64) -> String {
+    let width = bar_width();
+    let most = days.iter().map(|d| d.work).max().unwrap_or(0).max(capacity);
+
+    let mut lines = vec![format!(
+        "{} {}",
+        "Work due per day".bold(),
+        format!("(capacity {}/day)", duration(capacity)).truecolor(128, 128, 128)
+    )];
+    for day in days {
+        let label = match day.date {
+            Some(date) => date.format("%a %m-%d").to_string().truecolor(128, 128, 128),
+            None if day.tasks == 0 => continue,
+            None => "overdue".bright_red(),
+        };
+        let colour = if day.date.is_none() || day.work > capacity {
+            Color::BrightRed
+        } else if day.work * 2 > capacity {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        let value = match day.tasks {
+            0 => String::new(),
+            1 => format!("{} (1 task)", duration(day.work)),
+            n => format!("{} ({n} tasks)", duration(day.work)),
+        };
+        lines.push(format!(
+            "{:<LABEL_WIDTH$}{} {}",
+            label,
+            bar(day.work as f32 / most as f32, width, colour),
+            value.color(colour)
+        ));
+    }
+    lines.join("\n")
+}
+
+pub(crate) fn chart_json(points: &[(NaiveDate, i64)], days: &[Day], capacity: i64) -> Value {
+    json!({
+        "burndown": points
+            .iter()
+            .map(|(day, remaining)| json!({ "date": day.to_string(), "remaining": remaining }))
+            .collect::<Vec<_>>(),
+        "capacity": capacity,
+        "workload": days
+            .iter()
+            .map(|d| json!({
+                "date": d.date.map(|d| d.to_string()),
+                "overdue": d.date.is_none(),
+                "tasks": d.tasks,
+                "work": d.work,
+                "overloaded": (d.date.is_none() && d.tasks > 0) || d.work > capacity,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use todo_core::task::LogEntry;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn task(estimate: i64, progress: i64, log: &[(i64, i64)]) -> Task {
+        let mut task = Task::with_id(0);
+        task.estimated_time = estimate;
+        task.progress = progress;
+        task.log = log
+            .iter()
+            .map(|&(timestamp, amount)| LogEntry { timestamp, amount })
+            .collect();
+        task
+    }
+
+    /// A timestamp at noon `days` days from today.
+    fn noon(days: i64) -> i64 {
+        (Local::now().date_naive() + chrono::Duration::days(days))
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn remaining_work_follows_the_log() {
+        // An hour of progress predates the log.
+        let logged = task(4 * HOUR, 3 * HOUR, &[(1000, HOUR), (2000, HOUR)]);
+        assert_eq!(remaining_at(&logged, 500), 3 * HOUR);
+        assert_eq!(remaining_at(&logged, 1000), 2 * HOUR);
+        assert_eq!(remaining_at(&logged, 3000), HOUR);
+
+        let overdone = task(HOUR, 2 * HOUR, &[(1000, 2 * HOUR)]);
+        assert_eq!(remaining_at(&overdone, 500), HOUR);
+        assert_eq!(remaining_at(&overdone, 1000), 0);
+    }
+
+    #[test]
+    fn burndown_counts_tasks_from_their_creation() {
+        let today = Local::now().date_naive();
+        let yesterday = end_of_day(today - Days::new(1)) - 60;
+        let old = task(2 * HOUR, HOUR, &[(yesterday, HOUR)]);
+        let mut new = task(3 * HOUR, 0, &[]);
+        new.created = Local::now().timestamp();
+
+        let points = burndown(&[old, new], 3);
+        assert_eq!(
+            points,
+            [
+                (today - Days::new(2), 2 * HOUR),
+                (today - Days::new(1), HOUR),
+                (today, 4 * HOUR),
+            ]
+        );
+    }
+
+    #[test]
+    fn workload_sums_open_tasks_per_due_day() {
+        let mut tomorrow = task(3 * HOUR, HOUR, &[]);
+        tomorrow.deadline = noon(1);
+        let mut also_tomorrow = task(HOUR, 0, &[]);
+        also_tomorrow.deadline = noon(1) + HOUR;
+        let mut overdue = task(HOUR, 0, &[]);
+        overdue.deadline = Local::now().timestamp() - HOUR;
+        let mut completed = task(HOUR, HOUR, &[]);
+        completed.deadline = noon(1);
+        completed.completed = Local::now().timestamp();
+        let mut later = task(HOUR, 0, &[]);
+        later.deadline = noon(30);
+        let mut out_of_range = task(HOUR, 0, &[]);
+        out_of_range.deadline = i64::MAX / 2;
+
+        let days = workload(
+            &[
+                tomorrow,
+                also_tomorrow,
+                overdue,
+                completed,
+                later,
+                out_of_range,
+            ],
+            2,
+        );
+        assert_eq!(days.len(), 1 + 14);
+
+        assert_eq!(days[0].date, None);
+        assert_eq!((days[0].tasks, days[0].work), (1, HOUR));
+        let today = Local::now().date_naive();
+        assert_eq!(days[2].date, Some(today + Days::new(1)));
+        assert_eq!((days[2].tasks, days[2].work), (2, 3 * HOUR));
+        assert_eq!(days.iter().map(|d| d.tasks).sum::<usize>(), 3);
+    }
+}
@@ -0,0 +1,868 @@
+use std::str::FromStr;
+
+use crate::view::Column;
+use todo_core::{filter::Filter, sort::SortKey, Backend, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            other => Err(Error::Input(format!(
+                "Unknown shell \"{other}\" (expected bash, zsh or fish)"
+            ))),
+        }
+    }
+}
+
+struct OptSpec {
+    name: &'static str,
+    help: &'static str,
+    /// Whether the option consumes a value.
+    value: bool,
+    /// Known values, empty for free-form ones.
+    values: &'static [&'static str],
+}
+
+struct CommandSpec {
+    name: &'static str,
+    help: &'static str,
+    /// The first argument is a task id, completed from the task file.
+    takes_id: bool,
+    /// Trailing arguments are files.
+    takes_file: bool,
+    options: &'static [OptSpec],
+}
+
+const OUTPUT: OptSpec = OptSpec {
+    name: "output",
+    help: "Output format",
+    value: true,
+    values: &["text", "json", "ndjson"],
+};
+
+const PROJECT: OptSpec = OptSpec {
+    name: "project",
+    help: "Work on a project's tasks",
+    value: true,
+    values: &[],
+};
+
+/// Options every command takes.
+const GLOBAL: [OptSpec; 2] = [OUTPUT, PROJECT];
+
+const MATCH: OptSpec = OptSpec {
+    name: "match",
+    help: "Address the task by search instead of id",
+    value: true,
+    values: &[],
+};
+
+const WHERE: OptSpec = OptSpec {
+    name: "where",
+    help: "Select the tasks matching a filter",
+    value: true,
+    values: &Filter::NAMES,
+};
+
+const DRY_RUN: OptSpec = OptSpec {
+    name: "dry-run",
+    help: "List the tasks that would change and stop",
+    value: false,
+    values: &[],
+};
+
+const YES: OptSpec = OptSpec {
+    name: "yes",
+    help: "Don't ask before changing several tasks",
+    value: false,
+    values: &[],
+};
+
+const COMMAND_SPECS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "add",
+        help: "Add a new task",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "list",
+        help: "List all tasks",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "sort",
+                help: "Comma separated sort keys",
+                value: true,
+                values: &SortKey::NAMES,
+            },
+            OptSpec {
+                name: "reverse",
+                help: "Reverse the sort order",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "all",
+                help: "Include completed tasks",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "view",
+                help: "Render tasks as cards or as a table",
+                value: true,
+                values: &["card", "table"],
+            },
+            OptSpec {
+                name: "columns",
+                help: "Comma separated table columns",
+                value: true,
+                values: &Column::NAMES,
+            },
+            WHERE,
+            OptSpec {
+                name: "all-projects",
+                help: "List the tasks of every project",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "archived",
+                help: "List the archived tasks",
+                value: false,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "remove",
+        help: "Remove tasks by ID, range or filter",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH, WHERE, DRY_RUN, YES],
+    },
+    CommandSpec {
+        name: "cancel",
+        help: "Cancel tasks without completing them",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH, WHERE, DRY_RUN, YES],
+    },
+    CommandSpec {
+        name: "progress",
+        help: "Update task progress",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH, WHERE, DRY_RUN, YES],
+    },
+    CommandSpec {
+        name: "edit",
+        help: "Edit an existing task",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH],
+    },
+    CommandSpec {
+        name: "set",
+        help: "Set fields of tasks",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH, WHERE, DRY_RUN, YES],
+    },
+    CommandSpec {
+        name: "renumber",
+        help: "Number the tasks densely again",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "sort",
+                help: "Comma separated keys giving the new order",
+                value: true,
+                values: &SortKey::NAMES,
+            },
+            DRY_RUN,
+        ],
+    },
+    CommandSpec {
+        name: "move",
+        help: "Move tasks to another project",
+        takes_id: true,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "to",
+                help: "Project to move the tasks to",
+                value: true,
+                values: &[],
+            },
+            MATCH,
+            WHERE,
+            DRY_RUN,
+            YES,
+        ],
+    },
+    CommandSpec {
+        name: "archive",
+        help: "Move old completed tasks to the archive",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "older-than",
+                help: "How long ago tasks were completed",
+                value: true,
+                values: &[],
+            },
+            WHERE,
+            DRY_RUN,
+            YES,
+        ],
+    },
+    CommandSpec {
+        name: "unarchive",
+        help: "Bring archived tasks back",
+        takes_id: false,
+        takes_file: true,
+        options: &[MATCH, WHERE, DRY_RUN, YES],
+    },
+    CommandSpec {
+        name: "reschedule",
+        help: "Move task deadlines",
+        takes_id: true,
+        takes_file: true,
+        options: &[
+            MATCH,
+            WHERE,
+            DRY_RUN,
+            YES,
+            OptSpec {
+                name: "overdue",
+                help: "Reschedule every overdue task",
+                value: false,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "pomodoro",
+        help: "Work on a task in timed intervals",
+        takes_id: true,
+        takes_file: true,
+        options: &[
+            MATCH,
+            OptSpec {
+                name: "work",
+                help: "Length of a work interval",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "break",
+                help: "Length of a short break",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "long-break",
+                help: "Length of a long break",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "sessions",
+                help: "Stop after this many work intervals",
+                value: true,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "show",
+        help: "Show a task in detail",
+        takes_id: true,
+        takes_file: true,
+        options: &[MATCH],
+    },
+    CommandSpec {
+        name: "search",
+        help: "Fuzzy search tasks",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "agenda",
+        help: "List open tasks grouped by due day",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "calendar",
+        help: "Show a month with the tasks due each day",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "stats",
+        help: "Show workload, estimate accuracy and throughput",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "chart",
+        help: "Draw a burndown and the work due per day",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "days",
+                help: "Days of burndown history",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "weeks",
+                help: "Weeks of upcoming deadlines",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "capacity",
+                help: "Work per day before a day counts as overloaded",
+                value: true,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "watch",
+        help: "Keep running and remind of approaching deadlines",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "interval",
+                help: "How often to re-read the task file",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "before",
+                help: "Comma separated times before a deadline to remind at",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "bell",
+                help: "Ring the terminal bell with each reminder",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "command",
+                help: "Run a command for each reminder",
+                value: true,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "migrate-storage",
+        help: "Copy a task file into another storage backend",
+        takes_id: false,
+        takes_file: true,
+        options: &[OptSpec {
+            name: "to",
+            help: "Backend of the new file",
+            value: true,
+            values: &Backend::NAMES,
+        }],
+    },
+    CommandSpec {
+        name: "rekey",
+        help: "Change the passphrase of an encrypted task file",
+        takes_id: false,
+        takes_file: true,
+        options: &[OptSpec {
+            name: "new-key-file",
+            help: "Read the new passphrase from a file",
+            value: true,
+            values: &[],
+        }],
+    },
+    CommandSpec {
+        name: "sync",
+        help: "Sync the task file through its git repository",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "remote",
+                help: "Remote name or repository path",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "branch",
+                help: "Branch to sync",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "ours",
+                help: "Keep our side of every conflict",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "theirs",
+                help: "Keep the remote's side of every conflict",
+                value: false,
+                values: &[],
+            },
+            OptSpec {
+                name: "no-push",
+                help: "Don't push the result",
+                value: false,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "project",
+        help: "Create, list or switch projects",
+        takes_id: false,
+        takes_file: false,
+        options: &[OptSpec {
+            name: "none",
+            help: "Switch back to ./task_list",
+            value: false,
+            values: &[],
+        }],
+    },
+    CommandSpec {
+        name: "serve",
+        help: "Serve the tasks as a JSON API",
+        takes_id: false,
+        takes_file: true,
+        options: &[
+            OptSpec {
+                name: "port",
+                help: "Port to listen on",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "host",
+                help: "Address to listen on",
+                value: true,
+                values: &[],
+            },
+            OptSpec {
+                name: "allow-remote",
+                help: "Allow a host other machines can reach",
+                value: false,
+                values: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "tui",
+        help: "Open the interactive full-screen task view",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "shell",
+        help: "Run commands from an interactive prompt",
+        takes_id: false,
+        takes_file: true,
+        options: &[],
+    },
+    CommandSpec {
+        name: "completions",
+        help: "Print a shell completion script",
+        takes_id: false,
+        takes_file: false,
+        options: &[],
+    },
+    CommandSpec {
+        name: "help",
+        help: "Show usage",
+        takes_id: false,
+        takes_file: false,
+        options: &[],
+    },
+];
+
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Completion script for `shell`. Task ids are looked up when completing by
+/// running `todo-cli __complete-ids`, which prints `id<TAB>name` lines.
+pub(crate) fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+fn command_names() -> String {
+    COMMAND_SPECS
+        .iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn option_names(command: &CommandSpec) -> String {
+    command
+        .options
+        .iter()
+        .chain(&GLOBAL)
+        .map(|o| format!("--{}", o.name))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bash() -> String {
+    // Options of the same name can take different values in different
+    // commands (`convert --to` a backend, `move --to` a project), so each
+    // case matches the command as well.
+    let mut groups: Vec<(&OptSpec, Vec<&str>)> = Vec::new();
+    for command in COMMAND_SPECS {
+        for option in command.options.iter().filter(|o| o.value) {
+            match groups
+                .iter_mut()
+                .find(|(o, _)| o.name == option.name && o.values == option.values)
+            {
+                Some((_, commands)) => commands.push(command.name),
+                None => groups.push((option, vec![command.name])),
+            }
+        }
+    }
+    groups.extend(GLOBAL.iter().filter(|o| o.value).map(|o| (o, vec!["*"])));
+
+    let mut values = String::new();
+    for (option, commands) in groups {
+        let reply = if option.values.is_empty() {
+            "COMPREPLY=()".to_owned()
+        } else {
+            format!(
+                "COMPREPLY=($(compgen -P \"${{cur%\"${{cur##*,}}\"}}\" -W \"{}\" -- \"${{cur##*,}}\"))",
+                option.values.join(" ")
+            )
+        };
+        let pattern = commands
+            .iter()
+            .map(|c| format!("{c}:--{}", option.name))
+            .collect::<Vec<_>>()
+            .join("|");
+        values += &format!("        {pattern})\n            {reply}\n            return ;;\n");
+    }
+
+    let id_commands = COMMAND_SPECS
+        .iter()
+        .filter(|c| c.takes_id)
+        .map(|c| c.name)
+        .collect::<Vec<_>>()
+        .join("|");
+
+    let options = COMMAND_SPECS
+        .iter()
+        .map(|c| format!("        {}) opts=\"{}\" ;;\n", c.name, option_names(c)))
+        .collect::<String>();
+
+    format!(
+        r#"# bash completion for todo-cli
+_todo_cli() {{
+    local cur prev cmd opts
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    cmd="${{COMP_WORDS[1]}}"
+
+    case "$cmd:$prev" in
+{values}    esac
+
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "{commands}" -- "$cur"))
+        return
+    fi
+
+    if [[ "$cmd" == completions ]]; then
+        COMPREPLY=($(compgen -W "{shells}" -- "$cur"))
+        return
+    fi
+
+    case "$cmd" in
+        {id_commands})
+            if [[ $COMP_CWORD -eq 2 && "$cur" != -* ]]; then
+                local IFS=$'\n' line
+                local -a matches=()
+                while IFS= read -r line; do
+                    [[ "${{line%%$'\t'*}}" == "$cur"* ]] && matches+=("$line")
+                done < <(todo-cli __complete-ids 2>/dev/null)
+                if [[ ${{#matches[@]}} -eq 1 ]]; then
+                    COMPREPLY=("${{matches[0]%%$'\t'*}}")
+                else
+                    # Several candidates are only listed, so show their names too.
+                    COMPREPLY=("${{matches[@]/$'\t'/  }}")
+                fi
+                return
+            fi
+            ;;
+    esac
+
+    case "$cmd" in
+{options}    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "$opts" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+complete -F _todo_cli todo-cli
+"#,
+        commands = command_names(),
+        shells = SHELLS.join(" "),
+    )
+}
+
+fn zsh_escape(s: &str) -> String {
+    s.replace('\'', "'\\''").replace(':', "\\:")
+}
+
+fn zsh() -> String {
+    let commands = COMMAND_SPECS
+        .iter()
+        .map(|c| format!("        '{}:{}'\n", c.name, zsh_escape(c.help)))
+        .collect::<String>();
+
+    let cases = COMMAND_SPECS
+        .iter()
+        .map(|c| {
+            let mut specs: Vec<String> = c
+                .options
+                .iter()
+                .chain(&GLOBAL)
+                .map(|o| {
+                    let value = match (o.value, o.values.is_empty()) {
+                        (false, _) => String::new(),
+                        (true, true) => format!(":{}:", o.name),
+                        (true, false) => format!(":{}:({})", o.name, o.values.join(" ")),
+                    };
+                    format!("'--{}[{}]{}'", o.name, zsh_escape(o.help), value)
+                })
+                .collect();
+            if c.name == "completions" {
+                specs.push(format!("'1:shell:({})'", SHELLS.join(" ")));
+            }
+            if c.takes_id {
+                specs.push("'1:task id:_todo_cli_ids'".into());
+            }
+            if c.takes_file {
+                specs.push("'*:file:_files'".into());
+            }
+            format!(
+                "        {})\n            _arguments \\\n                {}\n            ;;\n",
+                c.name,
+                specs.join(" \\\n                ")
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r#"#compdef todo-cli
+
+_todo_cli_ids() {{
+    local -a ids
+    ids=(${{(f)"$(todo-cli __complete-ids 2>/dev/null)"}})
+    ids=(${{ids//:/\\:}})
+    ids=(${{ids/$'\t'/:}})
+    _describe 'task id' ids
+}}
+
+_todo_cli() {{
+    local -a commands
+    commands=(
+{commands}    )
+
+    if (( CURRENT == 2 )); then
+        _describe 'command' commands
+        return
+    fi
+
+    shift words
+    (( CURRENT-- ))
+    case $words[1] in
+{cases}    esac
+}}
+
+_todo_cli "$@"
+"#
+    )
+}
+
+fn fish_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish() -> String {
+    let mut out = String::from(
+        r#"# fish completion for todo-cli
+function __todo_cli_needs_id
+    set -l tokens (commandline -opc)
+    test (count $tokens) -eq 2
+end
+
+complete -c todo-cli -f
+"#,
+    );
+
+    for c in COMMAND_SPECS {
+        out += &format!(
+            "complete -c todo-cli -n __fish_use_subcommand -a {} -d '{}'\n",
+            c.name,
+            fish_escape(c.help)
+        );
+    }
+
+    let id_commands = COMMAND_SPECS
+        .iter()
+        .filter(|c| c.takes_id)
+        .map(|c| c.name)
+        .collect::<Vec<_>>()
+        .join(" ");
+    out += &format!(
+        "complete -c todo-cli -n '__fish_seen_subcommand_from {id_commands}; and __todo_cli_needs_id' -a '(todo-cli __complete-ids 2>/dev/null)'\n"
+    );
+    out += &format!(
+        "complete -c todo-cli -n '__fish_seen_subcommand_from completions' -a '{}'\n",
+        SHELLS.join(" ")
+    );
+
+    for c in COMMAND_SPECS.iter().filter(|c| c.takes_file) {
+        out += &format!(
+            "complete -c todo-cli -n '__fish_seen_subcommand_from {}' -F\n",
+            c.name
+        );
+    }
+
+    for c in COMMAND_SPECS {
+        for o in c.options {
+            out += &fish_option(Some(c.name), o);
+        }
+    }
+    for o in &GLOBAL {
+        out += &fish_option(None, o);
+    }
+    out
+}
+
+fn fish_option(command: Option<&str>, o: &OptSpec) -> String {
+    let condition = match command {
+        Some(command) => format!(" -n '__fish_seen_subcommand_from {command}'"),
+        None => String::new(),
+    };
+    let value = match (o.value, o.values.is_empty()) {
+        (false, _) => String::new(),
+        (true, true) => " -r".into(),
+        (true, false) => format!(" -x -a '{}'", o.values.join(" ")),
+    };
+    format!(
+        "complete -c todo-cli{condition} -l {}{value} -d '{}'\n",
+        o.name,
+        fish_escape(o.help)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    use super::*;
+
+    #[test]
+    fn every_command_has_a_spec() {
+        let specs: Vec<_> = COMMAND_SPECS.iter().map(|c| c.name).collect();
+        for command in crate::COMMANDS {
+            assert!(specs.contains(&command), "{command}");
+        }
+        for spec in specs {
+            assert!(crate::COMMANDS.contains(&spec), "{spec}");
+        }
+    }
+
+    #[test]
+    fn scripts_offer_every_command_and_option() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            assert!(script.contains("todo-cli __complete-ids"), "{shell:?}");
+            for command in COMMAND_SPECS {
+                assert!(script.contains(command.name), "{shell:?} {}", command.name);
+                for option in command.options.iter().chain([&OUTPUT]) {
+                    assert!(script.contains(option.name), "{shell:?} {}", option.name);
+                    for value in option.values {
+                        assert!(script.contains(value), "{shell:?} {value}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bash_script_parses() {
+        let Ok(mut bash) = Command::new("bash")
+            .arg("-n")
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        else {
+            return;
+        };
+        bash.stdin
+            .take()
+            .unwrap()
+            .write_all(script(Shell::Bash).as_bytes())
+            .unwrap();
+
+        let output = bash.wait_with_output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+    }
+
+    #[test]
+    fn help_texts_are_escaped() {
+        assert_eq!(zsh_escape("it's a:b"), r"it'\''s a\:b");
+        assert_eq!(fish_escape(r"it's a\b"), r"it\'s a\\b");
+    }
+
+    #[test]
+    fn parses_shell_names() {
+        assert_eq!("Bash".parse::<Shell>().unwrap(), Shell::Bash);
+        assert_eq!(" zsh".parse::<Shell>().unwrap(), Shell::Zsh);
+        assert_eq!("fish".parse::<Shell>().unwrap(), Shell::Fish);
+        assert!("powershell".parse::<Shell>().is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    overdue::OverduePolicy,
+    pomodoro::PomodoroOptions,
+    serve::ServeOptions,
+    sync::SyncOptions,
+    watch::{self, WatchOptions},
+};
+use todo_core::{parse, Backend, Error, Result};
+
+/// Settings read from the config file.
+///
+/// The file holds `key = value` lines; blank lines and lines starting with
+/// `#` are ignored. It is looked up at `$TODO_CLI_CONFIG`, then
+/// `$XDG_CONFIG_HOME/todo-cli/config`, then `~/.config/todo-cli/config`, and
+/// is optional.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    /// What happens to tasks whose deadline has passed.
+    pub(crate) overdue_policy: OverduePolicy,
+    /// Backend for new task files whose extension doesn't pick one.
+    pub(crate) storage: Backend,
+    /// Directory holding the projects, see [`crate::project::Projects`].
+    pub(crate) data_dir: Option<PathBuf>,
+    /// Age of completed tasks `archive` moves out, if not the default.
+    pub(crate) archive_after: Option<i64>,
+    /// File holding the passphrase of encrypted task files.
+    pub(crate) key_file: Option<PathBuf>,
+    /// Defaults for `watch`.
+    pub(crate) watch: WatchOptions,
+    /// Defaults for `pomodoro`.
+    pub(crate) pomodoro: PomodoroOptions,
+    /// Defaults for `sync`.
+    pub(crate) sync: SyncOptions,
+    /// Defaults for `serve`.
+    pub(crate) serve: ServeOptions,
+}
+impl Config {
+    pub(crate) fn load() -> Result<Self> {
+        match Config::path() {
+            Some(path) => match fs::read_to_string(&path) {
+                Ok(contents) => Config::parse(&contents, &path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+                Err(e) => Err(Error::Io(e)),
+            },
+            None => Ok(Config::default()),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("TODO_CLI_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => Path::new(&env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("todo-cli").join("config"))
+    }
+
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let mut config = Config::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: String| {
+                Error::Input(format!("{}:{}: {message}", path.display(), number + 1))
+            };
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected key = value".into()))?;
+            let value = value.trim();
+
+            let invalid = |e: Error| match e {
+                Error::Input(message) => error(message),
+                e => e,
+            };
+            match key.trim() {
+                "overdue_policy" => config.overdue_policy = value.parse().map_err(invalid)?,
+                "storage" => config.storage = value.parse().map_err(invalid)?,
+                "data_dir" => {
+                    config.data_dir = Some(PathBuf::from(value)).filter(|_| !value.is_empty())
+                }
+                "archive_after" => {
+                    config.archive_after = Some(parse::duration(value).map_err(invalid)?)
+                }
+                "key_file" => {
+                    config.key_file = Some(PathBuf::from(value)).filter(|_| !value.is_empty())
+                }
+                "watch_interval" => {
+                    config.watch.interval = parse::duration(value).map_err(invalid)?
+                }
+                "reminder_offsets" => {
+                    config.watch.offsets = watch::parse_offsets(value).map_err(invalid)?
+                }
+                "reminder_bell" => {
+                    config.watch.bell = match value {
+                        "true" | "yes" | "on" => true,
+                        "false" | "no" | "off" => false,
+                        _ => return Err(error(format!("expected true or false, got \"{value}\""))),
+                    }
+                }
+                "reminder_command" => {
+                    config.watch.command = Some(value.to_owned()).filter(|c| !c.is_empty())
+                }
+                "pomodoro_work" => {
+                    config.pomodoro.work = parse::duration(value).map_err(invalid)?
+                }
+                "pomodoro_break" => {
+                    config.pomodoro.short_break = parse::duration(value).map_err(invalid)?
+                }
+                "pomodoro_long_break" => {
+                    config.pomodoro.long_break = parse::duration(value).map_err(invalid)?
+                }
+                "pomodoro_long_break_every" => {
+                    config.pomodoro.long_break_every = value
+                        .parse()
+                        .map_err(|_| error(format!("expected a number, got \"{value}\"")))?
+                }
+                "sync_remote" => config.sync.remote = value.to_owned(),
+                "sync_branch" => {
+                    config.sync.branch = Some(value.to_owned()).filter(|b| !b.is_empty())
+                }
+                "serve_host" => config.serve.host = value.to_owned(),
+                "serve_port" => {
+                    config.serve.port = value
+                        .parse()
+                        .map_err(|_| error(format!("expected a port number, got \"{value}\"")))?
+                }
+                other => return Err(error(format!("unknown setting \"{other}\""))),
+            }
+        }
+        Ok(config)
+    }
+}
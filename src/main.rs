@@ -1,46 +1,54 @@
-mod format;
-mod task;
+mod agenda;
+mod archive;
+mod args;
+mod chart;
+mod completions;
+mod config;
+mod output;
+mod overdue;
+mod passphrase;
+mod pomodoro;
+mod project;
+mod serve;
+mod session;
+mod shell;
+mod stats;
+mod sync;
+mod tui;
+mod view;
+mod watch;
 
 use std::{
-    cmp::Ordering,
+    collections::HashMap,
     env,
-    fs::{remove_file, File, OpenOptions},
-    io::{self, stdin, stdout, BufReader, Seek, Write},
+    io::{self, stdin, stdout, Write},
     path::Path,
 };
 
-use chrono::Local;
+use args::{Args, Selection, DEFAULT_FILE};
+use chrono::{Datelike, Local};
 use colored::*;
-use format::format_duration;
+use config::Config;
+use output::{report, report_list, task_details_json, task_json};
 use regex::Regex;
-use task::Task;
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-enum CliError {
-    #[error("I/O error: {0}")]
-    Io(#[from] io::Error),
-
-    #[error("Parse error: {0}")]
-    Parse(String),
-
-    #[error("Invalid input: {0}")]
-    Input(String),
-
-    #[error("Task not found")]
-    TaskNotFound,
-
-    #[error("Invalid command")]
-    InvalidCommand,
-
-    #[error("Invalid file format")]
-    InvalidFileFormat,
-
-    #[error("Invalid arguments")]
-    InvalidArguments,
-}
-
-type Result<T> = std::result::Result<T, CliError>;
+use serde_json::json;
+use session::Session;
+use todo_core::{
+    filter::Filter,
+    format::{self, format_duration, strip_colors},
+    merge::Side,
+    parse::{
+        self, parse_due, parse_duration, parse_input, parse_progress, parse_when, Field,
+        DUE_PATTERN, DURATION_PATTERN,
+    },
+    search,
+    sort::{self, compare_tasks, SortKey},
+    storage::{self, Backend, Changes, Encrypted, Storage},
+    store::{next_id, renumber},
+    task::Task,
+    Error, Result, TaskStore, Uuid,
+};
+use view::{project_table, task_table, Column, View};
 
 fn print_help() -> Result<()> {
     println!("{}", "Usage: todo-cli <command> [arguments]".bold());
@@ -51,17 +59,22 @@ fn print_help() -> Result<()> {
         "Add a new task".white()
     );
     println!(
-        "  {} [file]                     {}",
+        "  {} [file] [options]           {}",
         "list".green(),
         "List all tasks".white()
     );
     println!(
-        "  {} <id> [file]              {}",
+        "  {} <ids> [file]             {}",
         "remove".green(),
-        "Remove a task by ID".white()
+        "Remove tasks by ID, range or filter".white()
+    );
+    println!(
+        "  {} <ids> [file]             {}",
+        "cancel".green(),
+        "Cancel tasks without completing them".white()
     );
     println!(
-        "  {} <id> <amount> [file]   {}",
+        "  {} <ids> <amount> [file]  {}",
         "progress".green(),
         "Update task progress".white()
     );
@@ -70,6 +83,128 @@ fn print_help() -> Result<()> {
         "edit".green(),
         "Edit an existing task".white()
     );
+    println!(
+        "  {} <ids> <field=value...>      {}",
+        "set".green(),
+        "Set fields of tasks; the file may follow".white()
+    );
+    println!(
+        "  {} <ids> <when> [file]  {}",
+        "reschedule".green(),
+        "Move deadlines to a date or by an offset (e.g. +2d)".white()
+    );
+    println!(
+        "  {} --overdue <when>     {}",
+        "reschedule".green(),
+        "Reschedule every overdue task".white()
+    );
+    println!(
+        "  {} <id> [file] [options]  {}",
+        "pomodoro".green(),
+        "Work on a task in timed intervals, crediting each one to its progress".white()
+    );
+    println!(
+        "  {} <id> [file]                {}",
+        "show".green(),
+        "Show a task with its pace, time elapsed and progress log".white()
+    );
+    println!(
+        "  {} <query> [file]           {}",
+        "search".green(),
+        "Fuzzy search task names and descriptions".white()
+    );
+    println!(
+        "  {} [when] [file]            {}",
+        "agenda".green(),
+        "List open tasks by due day; when is today, week (default) or month".white()
+    );
+    println!(
+        "  {} [YYYY-MM] [file]       {}",
+        "calendar".green(),
+        "Show a month with the number of tasks due each day".white()
+    );
+    println!(
+        "  {} [file]                    {}",
+        "stats".green(),
+        "Show workload, estimate accuracy and throughput".white()
+    );
+    println!(
+        "  {} [file] [options]          {}",
+        "chart".green(),
+        "Draw a burndown of remaining work and the work due per day".white()
+    );
+    println!(
+        "  {} [file] [options]          {}",
+        "watch".green(),
+        "Keep running and remind of approaching deadlines".white()
+    );
+    println!(
+        "  {} <from> <to>     {}",
+        "migrate-storage".green(),
+        "Copy a task file into another storage backend (--to binary|sqlite|journal|encrypted)"
+            .white()
+    );
+    println!(
+        "  {} [file]                    {}",
+        "rekey".green(),
+        "Change the passphrase of an encrypted task file (--new-key-file <file>)".white()
+    );
+    println!(
+        "  {} [file]                 {}",
+        "renumber".green(),
+        "Number the tasks densely again, in id order or by --sort keys (--dry-run)".white()
+    );
+    println!(
+        "  {} <ids> --to <project> [file]  {}",
+        "move".green(),
+        "Move tasks to another project, numbering them after its tasks".white()
+    );
+    println!(
+        "  {} [file] [options]        {}",
+        "archive".green(),
+        "Move tasks completed over --older-than ago (default: 30d) and any matching --where to <file>.archive".white()
+    );
+    println!(
+        "  {} <ids> [file]           {}",
+        "unarchive".green(),
+        "Bring archived tasks back, ids as shown by list --archived".white()
+    );
+    println!(
+        "  {} [file] [options]           {}",
+        "sync".green(),
+        "Commit the task file to its git repository, merge the remote's tasks and push".white()
+    );
+    println!(
+        "  {} [file] [options]          {}",
+        "serve".green(),
+        "Serve the tasks as a JSON API on http://127.0.0.1:7878 (--port, --host, --allow-remote)"
+            .white()
+    );
+    println!(
+        "  {} create|switch <name>     {}",
+        "project".green(),
+        "Create a project, or switch to one (switch --none goes back to ./task_list)".white()
+    );
+    println!(
+        "  {} list                     {}",
+        "project".green(),
+        "List the projects with their open tasks, marking the current one".white()
+    );
+    println!(
+        "  {} [file]                      {}",
+        "tui".green(),
+        "Open the interactive full-screen task view".white()
+    );
+    println!(
+        "  {} [file]                    {}",
+        "shell".green(),
+        "Run commands against the task file from an interactive prompt".white()
+    );
+    println!(
+        "  {} <bash|zsh|fish>     {}",
+        "completions".green(),
+        "Print a shell completion script".white()
+    );
     println!("\n{}", "Arguments:".bold());
     println!(
         "  {}                            {}",
@@ -81,130 +216,508 @@ fn print_help() -> Result<()> {
         "id".yellow(),
         "Task ID".white()
     );
+    println!(
+        "  {}                             {}",
+        "ids".yellow(),
+        "Task IDs and ranges (3 5 7, 4-9, 1,3-5), or --where / --match".white()
+    );
     println!(
         "  {}                          {}",
         "amount".yellow(),
         "Progress amount (e.g. 2h 30m, 50%)".white()
     );
+    println!(
+        "  {}                 {}",
+        "--match <query>".yellow(),
+        "Address a task by search instead of <id> (any command taking an <id>)".white()
+    );
+    println!(
+        "  {}                            {}",
+        "when".yellow(),
+        "New deadline: YYYY-MM-DD [HH:MM:SS], HH:MM:SS or an offset like +2d, +1w or +3h".white()
+    );
+    println!(
+        "  {}                     {}",
+        "field=value".yellow(),
+        "For set: name, description, priority, estimate (2h 30m), due (like when), status (open, completed, cancelled)".white()
+    );
+    println!("\n{}", "Selection options:".bold());
+    println!(
+        "  {}",
+        "Ids can also be given as a task's UUID, or @ and a unique start of it (e.g. @1f3a)"
+            .white()
+    );
+    println!(
+        "  {}                {}",
+        "--where <filter>".yellow(),
+        "Select the tasks matching all comma separated conditions".white()
+    );
+    println!(
+        "                                  {}",
+        Filter::NAMES.join(", ").white()
+    );
+    println!(
+        "  {}                       {}",
+        "--dry-run".yellow(),
+        "List the tasks that would change and stop".white()
+    );
+    println!(
+        "  {}                           {}",
+        "--yes".yellow(),
+        "Don't ask before changing several tasks, or a task --match found by more than its name"
+            .white()
+    );
+    println!("\n{}", "Global options:".bold());
+    println!(
+        "  {}               {}",
+        "--output <format>".yellow(),
+        "Output format: text, json or ndjson (one JSON object per line for lists)".white()
+    );
+    println!(
+        "  {}              {}",
+        "--project <name>".yellow(),
+        "Use the project's task file instead of the current project's or ./task_list".white()
+    );
+    println!("\n{}", "List options:".bold());
+    println!(
+        "  {}                   {}",
+        "--sort <keys>".yellow(),
+        "Comma separated sort keys (default: deadline)".white()
+    );
+    println!(
+        "                                  {}",
+        SortKey::NAMES.join(", ").white()
+    );
+    println!(
+        "  {}                       {}",
+        "--reverse".yellow(),
+        "Reverse the sort order".white()
+    );
+    println!(
+        "  {}                           {}",
+        "--all".yellow(),
+        "Include completed and cancelled tasks".white()
+    );
+    println!(
+        "  {}                {}",
+        "--where <filter>".yellow(),
+        "Only list the tasks matching the filter, as for selections".white()
+    );
+    println!(
+        "  {}                  {}",
+        "--all-projects".yellow(),
+        "List the tasks of every project, with a project column".white()
+    );
+    println!(
+        "  {}                      {}",
+        "--archived".yellow(),
+        "List the archived tasks instead".white()
+    );
+    println!(
+        "  {}                   {}",
+        "--view <view>".yellow(),
+        "Render tasks as cards or as a compact table (card, table)".white()
+    );
+    println!(
+        "  {}             {}",
+        "--columns <columns>".yellow(),
+        "Comma separated table columns (default: all)".white()
+    );
+    println!(
+        "                                  {}",
+        Column::NAMES.join(", ").white()
+    );
+    println!("\n{}", "Chart options:".bold());
+    println!(
+        "  {}                      {}",
+        "--days <n>".yellow(),
+        "Days of burndown history (default: 14)".white()
+    );
+    println!(
+        "  {}                     {}",
+        "--weeks <n>".yellow(),
+        "Weeks of upcoming deadlines (default: 2)".white()
+    );
+    println!(
+        "  {}           {}",
+        "--capacity <duration>".yellow(),
+        "Work per day before a day counts as overloaded (default: 8h)".white()
+    );
+    println!("\n{}", "Pomodoro options:".bold());
+    println!(
+        "  {}               {}",
+        "--work <duration>".yellow(),
+        "Length of a work interval (default: 25m)".white()
+    );
+    println!(
+        "  {}              {}",
+        "--break <duration>".yellow(),
+        "Length of a short break (default: 5m)".white()
+    );
+    println!(
+        "  {}         {}",
+        "--long-break <duration>".yellow(),
+        "Length of the break after every 4th interval (default: 15m)".white()
+    );
+    println!(
+        "  {}                  {}",
+        "--sessions <n>".yellow(),
+        "Stop after n work intervals instead of when the task is done".white()
+    );
+    println!("\n{}", "Watch options:".bold());
+    println!(
+        "  {}           {}",
+        "--interval <duration>".yellow(),
+        "How often to re-read the task file (default: 1m)".white()
+    );
+    println!(
+        "  {}              {}",
+        "--before <offsets>".yellow(),
+        "Comma separated times before a deadline to remind at (default: 1d,1h,0)".white()
+    );
+    println!(
+        "  {}                          {}",
+        "--bell".yellow(),
+        "Ring the terminal bell with each reminder".white()
+    );
+    println!(
+        "  {}                 {}",
+        "--command <cmd>".yellow(),
+        "Run a command for each reminder, with $TODO_CLI_MESSAGE and $TODO_CLI_TASK_ID set".white()
+    );
+    println!("\n{}", "Serve endpoints:".bold());
+    println!(
+        "  {}             {}",
+        "GET /tasks".yellow(),
+        "Open tasks; ?all, ?where=<filter>, ?sort=<keys> and ?reverse as for list".white()
+    );
+    println!(
+        "  {}            {}",
+        "POST /tasks".yellow(),
+        "Add a task from {\"name\", \"due\", \"estimate\"} and optionally description and priority"
+            .white()
+    );
+    println!(
+        "  {}        {}",
+        "GET /tasks/<id>".yellow(),
+        "A task with its pace and progress log".white()
+    );
+    println!(
+        "  {}      {}",
+        "PATCH /tasks/<id>".yellow(),
+        "Set fields, named and written as for set (e.g. {\"priority\": \"high\"})".white()
+    );
+    println!(
+        "  {}  {}",
+        "POST /tasks/<id>/progress".yellow(),
+        "Log progress given as {\"amount\": \"30m\"} or a percentage".white()
+    );
+    println!(
+        "  {}     {}",
+        "DELETE /tasks/<id>".yellow(),
+        "Remove a task".white()
+    );
+    println!("\n{}", "Configuration:".bold());
+    println!(
+        "  {}",
+        "Settings are read from $TODO_CLI_CONFIG or $XDG_CONFIG_HOME/todo-cli/config as key = value lines."
+            .white()
+    );
+    println!(
+        "  {}                  {}",
+        "overdue_policy".yellow(),
+        "none, escalate (raise priority once overdue) or prompt (ask for a new deadline)".white()
+    );
+    println!(
+        "  {}                         {}",
+        "storage".yellow(),
+        "Backend of new task files: binary (default), sqlite, journal or encrypted; .db and .journal files pick their own"
+            .white()
+    );
+    println!(
+        "  {}                   {}",
+        "archive_after".yellow(),
+        "Default for archive --older-than".white()
+    );
+    println!(
+        "  {}                        {}",
+        "data_dir".yellow(),
+        "Where projects are kept (default: $TODO_CLI_DATA or $XDG_DATA_HOME/todo-cli)".white()
+    );
+    println!(
+        "  {}                        {}",
+        "key_file".yellow(),
+        "File holding the passphrase of encrypted task files".white()
+    );
+    println!(
+        "  {}                     {}",
+        "sync_remote".yellow(),
+        "Default for sync --remote (default: origin), likewise sync_branch".white()
+    );
+    println!(
+        "  {}                      {}",
+        "serve_port".yellow(),
+        "Default for serve --port (default: 7878), likewise serve_host (default: 127.0.0.1)"
+            .white()
+    );
+    println!(
+        "  {}                  {}",
+        "watch_interval".yellow(),
+        "Default for watch --interval".white()
+    );
+    println!(
+        "  {}                {}",
+        "reminder_offsets".yellow(),
+        "Default for watch --before".white()
+    );
+    println!(
+        "  {}                   {}",
+        "reminder_bell".yellow(),
+        "true to always ring the bell".white()
+    );
+    println!(
+        "  {}                {}",
+        "reminder_command".yellow(),
+        "Default for watch --command".white()
+    );
+    println!(
+        "  {}                   {}",
+        "pomodoro_work".yellow(),
+        "Default for pomodoro --work, likewise pomodoro_break and pomodoro_long_break".white()
+    );
+    println!(
+        "  {}      {}",
+        "pomodoro_long_break_every".yellow(),
+        "Work intervals between long breaks (default: 4)".white()
+    );
     println!("\n{}", "Examples:".bold());
     println!("  {}", "todo-cli add".cyan());
     println!("  {}", "todo-cli list".cyan());
+    println!("  {}", "todo-cli list --sort urgency,name --reverse".cyan());
+    println!(
+        "  {}",
+        "todo-cli list --view table --columns id,name,left".cyan()
+    );
+    println!("  {}", "todo-cli list --output ndjson".cyan());
     println!("  {}", "todo-cli remove 1".cyan());
     println!("  {}", "todo-cli progress 2 30m".cyan());
+    println!("  {}", "todo-cli remove 3 5 7".cyan());
+    println!("  {}", "todo-cli remove 4-9 --dry-run".cyan());
+    println!(
+        "  {}",
+        "todo-cli set --where overdue,priority=low priority=high due=+1d --yes".cyan()
+    );
     println!("  {}", "todo-cli edit 3".cyan());
+    println!("  {}", "todo-cli show 3".cyan());
+    println!("  {}", "todo-cli pomodoro 3 --work 50m --break 10m".cyan());
+    println!("  {}", "todo-cli reschedule --overdue +2d".cyan());
+    println!("  {}", "todo-cli agenda today".cyan());
+    println!("  {}", "todo-cli calendar 2025-12".cyan());
+    println!("  {}", "todo-cli migrate-storage task_list tasks.db".cyan());
+    println!(
+        "  {}",
+        "todo-cli migrate-storage task_list secret --to encrypted".cyan()
+    );
+    println!(
+        "  {}",
+        "todo-cli sync --remote ../shared.git --theirs".cyan()
+    );
+    println!("  {}", "todo-cli project create work".cyan());
+    println!("  {}", "todo-cli move 4 7 --to work".cyan());
+    println!("  {}", "todo-cli list --all-projects --view table".cyan());
+    println!("  {}", "todo-cli archive --older-than 7d --dry-run".cyan());
+    println!("  {}", "todo-cli serve --port 8080".cyan());
+    println!("  {}", "todo-cli stats".cyan());
+    println!("  {}", "todo-cli chart --weeks 4 --capacity 6h".cyan());
+    println!(
+        "  {}",
+        "todo-cli watch --before 1d,2h,15m --command 'notify-send todo \"$TODO_CLI_MESSAGE\"'"
+            .cyan()
+    );
+    println!(
+        "  {}",
+        "todo-cli completions bash > ~/.local/share/bash-completion/completions/todo-cli".cyan()
+    );
+    println!("  {}", "todo-cli search \"quarterly report\"".cyan());
+    println!("  {}", "todo-cli edit --match \"quarterly report\"".cyan());
     Ok(())
 }
 
-fn save_tasks(tasks: &[Task], file_path: &Path, overwrite: bool) -> Result<()> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(!overwrite)
-        .create(true)
-        .truncate(overwrite)
-        .open(file_path)
-        .map_err(|e| CliError::Io(e))?;
-
-    for task in tasks {
-        file.write_all(&task.serialize())
-            .map_err(|e| CliError::Io(e))?;
-    }
-    Ok(())
-}
-
-fn read_tasks(file_path: &Path) -> Result<Vec<Task>> {
-    let mut tasks = Vec::new();
-    let f = File::open(file_path).map_err(|e| CliError::Io(e))?;
-
-    let total_size = f.metadata()?.len();
-    let mut br = BufReader::new(f);
-
-    while br.stream_position()? < total_size {
-        tasks.push(Task::from(&mut br).map_err(|_| CliError::InvalidFileFormat)?);
-    }
-
-    Ok(tasks)
-}
-
 fn query<V, F>(msg: &str, regex: &str, f: F) -> Result<V>
 where
     F: Fn(Vec<Option<String>>) -> Result<V>,
 {
-    let regex = Regex::new(regex).map_err(|e| CliError::Input(e.to_string()))?;
+    let regex = Regex::new(regex).map_err(|e| Error::Input(e.to_string()))?;
     loop {
-        print!("{}", msg.bold());
-        stdout().flush().map_err(CliError::Io)?;
+        // Keep stdout clean for the machine-readable result.
+        if output::is_text() {
+            print!("{}", msg.bold());
+            stdout().flush().map_err(Error::Io)?;
+        } else {
+            eprint!("{}", msg.bold());
+        }
 
         let mut input = String::new();
-        stdin().read_line(&mut input).map_err(CliError::Io)?;
-
-        match regex.captures(input.trim()) {
-            Some(captures) => {
-                let groups = captures
-                    .iter()
-                    .skip(1)
-                    .map(|x| x.map(|x| x.as_str().to_owned()))
-                    .collect();
+        if stdin().read_line(&mut input).map_err(Error::Io)? == 0 {
+            return Err(Error::Input("Unexpected end of input".into()));
+        }
 
-                match f(groups) {
-                    Ok(v) => return Ok(v),
-                    Err(e) => eprintln!("{}", format!("Error: {e}").red()),
-                }
-            }
-            None => eprintln!("{}", "Invalid input format, please try again".red()),
+        match parse_input(&input, &regex, &f) {
+            Ok(v) => return Ok(v),
+            Err(e) => eprintln!("{}", format!("Error: {e}").red()),
         }
     }
 }
 
 fn main() {
     if let Err(e) = try_main() {
-        eprintln!("{}", format!("Error: {e}").red());
-        std::process::exit(1);
+        if output::is_text() {
+            eprintln!("{}", format!("Error: {e}").red());
+        } else {
+            println!("{}", output::error_json(&e));
+        }
+        std::process::exit(e.exit_code());
     }
 }
 
 fn try_main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    if let Some(format) = Args::take_global(&mut args, "output")? {
+        output::set_format(format.parse()?);
+    }
 
     if args.len() == 1 {
         print_help()?;
         return Ok(());
     }
 
+    let project = Args::take_global(&mut args, "project")?;
+
+    let mut session = Session::new(Path::new(DEFAULT_FILE));
+    // Completion runs in the background and must never stop to prompt.
     match args[1].as_str() {
-        "add" => handle_add(&args),
-        "list" => handle_list(&args),
-        "remove" => handle_remove(&args),
-        "progress" => handle_progress(&args),
-        "edit" => handle_edit(&args),
+        "help" | "completions" => {}
+        "__complete-ids" => {
+            let config = Config::load().unwrap_or_default();
+            if let Ok(Some(file)) =
+                project::default_file(config.data_dir.as_deref(), project.as_deref())
+            {
+                session.set_default_file(&file);
+            }
+        }
         _ => {
-            print_help()?;
-            Err(CliError::InvalidCommand)
+            let config = Config::load()?;
+            session.set_overdue_policy(config.overdue_policy);
+            session.set_storage(config.storage);
+            if let Some(file) =
+                project::default_file(config.data_dir.as_deref(), project.as_deref())?
+            {
+                session.set_default_file(&file);
+            }
+            session.set_data_dir(config.data_dir);
+            passphrase::install(config.key_file);
         }
     }
+    run_command(&args, &mut session)
 }
 
-fn handle_add(args: &[String]) -> Result<()> {
-    if args.len() < 2 {
-        return Err(CliError::InvalidArguments);
+/// Commands understood by `run_command`.
+const COMMANDS: [&str; 29] = [
+    "add",
+    "list",
+    "remove",
+    "cancel",
+    "progress",
+    "edit",
+    "set",
+    "renumber",
+    "move",
+    "archive",
+    "unarchive",
+    "reschedule",
+    "pomodoro",
+    "show",
+    "search",
+    "agenda",
+    "calendar",
+    "stats",
+    "chart",
+    "watch",
+    "migrate-storage",
+    "rekey",
+    "sync",
+    "project",
+    "serve",
+    "tui",
+    "shell",
+    "completions",
+    "help",
+];
+
+/// Commands whose first argument is a task id.
+const ID_COMMANDS: [&str; 9] = [
+    "remove",
+    "cancel",
+    "move",
+    "progress",
+    "edit",
+    "set",
+    "reschedule",
+    "pomodoro",
+    "show",
+];
+
+/// Runs the command in `args[1]`, with `args[0]` being the program name.
+fn run_command(args: &[String], session: &mut Session) -> Result<()> {
+    match args[1].as_str() {
+        "add" => handle_add(args, session),
+        "list" => handle_list(args, session),
+        "remove" => handle_remove(args, session),
+        "cancel" => handle_cancel(args, session),
+        "progress" => handle_progress(args, session),
+        "edit" => handle_edit(args, session),
+        "set" => handle_set(args, session),
+        "renumber" => handle_renumber(args, session),
+        "move" => handle_move(args, session),
+        "archive" => handle_archive(args, session),
+        "unarchive" => handle_unarchive(args, session),
+        "reschedule" => handle_reschedule(args, session),
+        "pomodoro" => handle_pomodoro(args, session),
+        "show" => handle_show(args, session),
+        "search" => handle_search(args, session),
+        "agenda" => handle_agenda(args, session),
+        "calendar" => handle_calendar(args, session),
+        "stats" => handle_stats(args, session),
+        "chart" => handle_chart(args, session),
+        "watch" => handle_watch(args, session),
+        "migrate-storage" => handle_migrate_storage(args, session),
+        "rekey" => handle_rekey(args, session),
+        "sync" => handle_sync(args, session),
+        "project" => handle_project(args, session),
+        "serve" => handle_serve(args, session),
+        "tui" => handle_tui(args, session),
+        "shell" => handle_shell(args, session),
+        "completions" => handle_completions(args),
+        "__complete-ids" => handle_complete_ids(args, session),
+        "help" => print_help(),
+        _ => {
+            if output::is_text() {
+                print_help()?;
+            }
+            Err(Error::InvalidCommand)
+        }
     }
+}
 
-    let file_path = Path::new(if args.len() == 2 {
-        "./task_list"
-    } else {
-        &args[2]
-    });
-
-    let max_id = if file_path.exists() {
-        read_tasks(file_path)?
-            .iter()
-            .map(|t| t.id())
-            .max()
-            .unwrap_or(-1)
-    } else {
-        -1
-    };
+fn handle_add(args: &[String], session: &mut Session) -> Result<()> {
+    if args.len() < 2 {
+        return Err(Error::InvalidArguments);
+    }
 
-    let mut task = Task::with_id(max_id + 1);
+    let file_path = session.file(args.get(2));
+    let mut task = Task::new();
+    task.created = Local::now().timestamp();
 
     task.deadline = query(
         &format!(
@@ -212,328 +725,384 @@ fn handle_add(args: &[String]) -> Result<()> {
             "YYYY-MM-DD HH:MM:SS".yellow(),
             "HH:MM:SS".yellow()
         ),
-        r"^(?:(\d{4}-\d{2}-\d{2})(?: (\d{2}:\d{2}:\d{2}))?|(\d{2}:\d{2}:\d{2}))$",
-        |v| {
-            let date = match &v[0] {
-                Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"),
-                None => Ok(Local::now().date_naive()),
-            }
-            .map_err(|_| CliError::Input("Invalid date format".into()))?;
-
-            let time = match &v[1] {
-                Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
-                None => match &v[2] {
-                    Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
-                    None => Ok(Local::now().time()),
-                },
-            }
-            .map_err(|_| CliError::Input("Invalid time format".into()))?;
-
-            Ok(chrono::NaiveDateTime::new(date, time)
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp())
-        },
+        &format!("^(?:{DUE_PATTERN})$"),
+        |v| parse_due(&v),
     )?;
 
-    task.estimated_time = query(
-        "Estimated time to complete: ",
-        r"^(?:(\d+)h\s*)?(?:(\d+)m\s*)?(?:(\d+)s)?$",
-        |v| {
-            Ok(v[0]
-                .clone()
-                .unwrap_or("0".into())
-                .parse::<i64>()
-                .map_err(|_| CliError::Parse("Invalid hours".into()))?
-                * 3600
-                + v[1]
-                    .clone()
-                    .unwrap_or("0".into())
-                    .parse::<i64>()
-                    .map_err(|_| CliError::Parse("Invalid minutes".into()))?
-                    * 60
-                + v[2]
-                    .clone()
-                    .unwrap_or("0".into())
-                    .parse::<i64>()
-                    .map_err(|_| CliError::Parse("Invalid seconds".into()))?)
-        },
-    )?;
+    task.estimated_time = query("Estimated time to complete: ", DURATION_PATTERN, |v| {
+        parse_duration(&v)
+    })?;
 
     task.name = query("Name: ", r"(.*)", |v| {
-        Ok(v[0]
-            .clone()
-            .ok_or(CliError::Input("Name cannot be empty".into()))?)
+        v[0].clone()
+            .ok_or(Error::Input("Name cannot be empty".into()))
     })?;
 
     task.description = query("Description: ", r"(.*)", |v| {
-        Ok(v[0]
-            .clone()
-            .ok_or(CliError::Input("Description cannot be empty".into()))?)
+        v[0].clone()
+            .ok_or(Error::Input("Description cannot be empty".into()))
     })?;
 
-    save_tasks(&[task], file_path, false)?;
-    println!("{}", "Task added successfully".green());
+    // The file is only read once everything is asked, so it isn't locked
+    // while waiting for the answers.
+    task.set_id(next_id(session.tasks_or_empty(&file_path)?));
+    session.append(&file_path, task.clone())?;
+    report(
+        || "Task added successfully".green().to_string(),
+        || json!({ "added": task_json(&task) }),
+    );
     Ok(())
 }
 
-fn handle_list(args: &[String]) -> Result<()> {
-    let file_path = Path::new(if args.len() == 2 {
-        "./task_list"
-    } else {
-        &args[2]
-    });
+fn handle_list(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["sort", "view", "columns", "where"])?;
+    let file_path = session.file(args.positional.get(2));
 
-    if !file_path.exists() {
-        return Err(CliError::Io(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Task file {} not found", file_path.display()),
-        )));
-    }
+    let keys = match args.option("sort") {
+        Some(keys) => sort::parse_keys(keys)?,
+        None => vec![SortKey::Deadline],
+    };
 
-    let mut tasks = read_tasks(file_path)?;
-    tasks.sort_by(|a, b| match a.get_time_left().cmp(&b.get_time_left()) {
-        Ordering::Less => Ordering::Less,
-        Ordering::Greater => Ordering::Greater,
-        Ordering::Equal => a.id().cmp(&b.id()),
-    });
+    let view = match args.option("view") {
+        Some(view) => view.parse()?,
+        None => View::Card,
+    };
+    let columns = match args.option("columns") {
+        Some(columns) => view::parse_columns(columns)?,
+        None => Column::DEFAULT.to_vec(),
+    };
 
-    for task in read_tasks(file_path)? {
-        println!("{}\n", task);
+    // Completed tasks are kept for `stats` but only listed on request.
+    // Archived tasks are listed instead with `--archived`, all of them.
+    let archived = args.switch("archived");
+    let all = archived || args.switch("all");
+    let all_projects = args.switch("all-projects");
+    let mut filter: Option<Filter> = args.option("where").map(str::parse).transpose()?;
+    if !all {
+        let open: Filter = "open".parse()?;
+        filter = Some(match filter {
+            Some(filter) => filter.and(open),
+            None => open,
+        });
+    }
+    let filter = filter.as_ref();
+    let mut tasks: Vec<(Option<String>, Task)> = Vec::new();
+    if all_projects {
+        for name in session.projects()?.list()? {
+            let file_path = session.projects()?.file(&name)?;
+            for task in list_source(session, &file_path, archived, false, filter)? {
+                tasks.push((Some(name.clone()), task));
+            }
+        }
+    } else {
+        for task in list_source(session, &file_path, archived, true, filter)? {
+            tasks.push((None, task));
+        }
     }
+    let reverse = args.switch("reverse");
+    tasks.sort_by(|(_, a), (_, b)| compare_tasks(a, b, &keys, reverse));
+    let (overdue, upcoming): (Vec<_>, Vec<_>) =
+        tasks.into_iter().partition(|(_, t)| t.is_overdue());
+
+    let render = |tasks: &[(Option<String>, Task)]| match view {
+        View::Card => tasks
+            .iter()
+            .map(|(project, t)| match project {
+                Some(project) => format!("{}\n{t}\n", format!("[{project}]").magenta()),
+                None => format!("{t}\n"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        View::Table if all_projects => {
+            let tasks: Vec<(String, Task)> = tasks
+                .iter()
+                .map(|(project, t)| (project.clone().unwrap_or_default(), t.clone()))
+                .collect();
+            project_table(&tasks, &columns)
+        }
+        View::Table => {
+            let tasks: Vec<Task> = tasks.iter().map(|(_, t)| t.clone()).collect();
+            task_table(&tasks, &columns)
+        }
+    };
+    report_list(
+        || {
+            if overdue.is_empty() {
+                return render(&upcoming);
+            }
+            let mut out = format!(
+                "{}\n{}",
+                format!("Overdue ({})", overdue.len()).bright_red().bold(),
+                render(&overdue)
+            );
+            if !upcoming.is_empty() {
+                out += &format!("\n\n{}\n{}", "Upcoming".bold(), render(&upcoming));
+            }
+            out
+        },
+        || {
+            overdue
+                .iter()
+                .chain(&upcoming)
+                .map(|(project, t)| {
+                    let mut task = task_json(t);
+                    if let Some(project) = project {
+                        task["project"] = json!(project);
+                    }
+                    task
+                })
+                .collect()
+        },
+    );
     Ok(())
 }
 
-fn handle_remove(args: &[String]) -> Result<()> {
-    if args.len() < 3 {
-        return Err(CliError::InvalidArguments);
+/// The tasks of `file_path` matching `filter`, or those of its archive if
+/// `archived`. Only a missing task file fails, and only if `required`.
+fn list_source(
+    session: &mut Session,
+    file_path: &Path,
+    archived: bool,
+    required: bool,
+    filter: Option<&Filter>,
+) -> Result<Vec<Task>> {
+    if !archived {
+        return match filter {
+            Some(filter) => session.query(file_path, filter, required),
+            None if required => session.tasks(file_path).cloned(),
+            None => session.tasks_or_empty(file_path).cloned(),
+        };
     }
+    session.tasks_or_empty(file_path)?;
+    Ok(archive::open(file_path)?
+        .tasks()
+        .iter()
+        .filter(|t| filter.is_none_or(|f| f.matches(t)))
+        .cloned()
+        .collect())
+}
 
-    let file_path = Path::new(if args.len() == 3 {
-        "./task_list"
-    } else {
-        &args[3]
-    });
-
-    if !file_path.exists() {
-        return Err(CliError::Io(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Task file {} not found", file_path.display()),
-        )));
+fn handle_remove(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    let (selection, rest) = args.selection()?;
+    let file_path = session.file(rest.first());
+
+    let tasks = session.tasks(&file_path)?;
+    let affected = picked(tasks, &selection.find(tasks)?);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "remove",
+    )? {
+        return Ok(());
     }
+    let tasks = session.tasks(&file_path)?;
+    let mut indices = reselect(tasks, &affected)?;
 
-    let mut tasks = read_tasks(file_path)?;
-    let target_id = args[2]
-        .parse()
-        .map_err(|_| CliError::Parse("Invalid task ID".into()))?;
+    // Remove from the back so the remaining indices stay valid.
+    indices.sort_unstable_by(|a, b| b.cmp(a));
+    let mut removed: Vec<Task> = indices.iter().map(|&i| tasks.remove(i)).collect();
+    removed.reverse();
 
-    let index = tasks
-        .iter()
-        .position(|t| t.id() == target_id)
-        .ok_or(CliError::TaskNotFound)?;
+    session.save(&file_path)?;
 
-    tasks.swap_remove(index);
-
-    if tasks.is_empty() {
-        remove_file(file_path)?;
+    if !selection.is_bulk() {
+        let removed = &removed[0];
+        report(
+            || {
+                format!(
+                    "{}{}",
+                    "Successfully removed task with id ".green(),
+                    removed.id().to_string().cyan()
+                )
+            },
+            || json!({ "removed": task_json(removed) }),
+        );
     } else {
-        save_tasks(&tasks, file_path, true)?;
+        report_bulk("Removed", "removed", &removed);
     }
-
-    println!(
-        "{}",
-        format!(
-            "{}{}",
-            "Successfully removed task with id ".green(),
-            target_id.to_string().cyan()
-        )
-    );
     Ok(())
 }
 
-fn handle_progress(args: &[String]) -> Result<()> {
-    if args.len() < 4 {
-        return Err(CliError::InvalidArguments);
-    }
+fn handle_cancel(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    let (selection, rest) = args.selection()?;
+    let file_path = session.file(rest.first());
 
-    let file_path = Path::new(if args.len() == 4 {
-        "./task_list"
+    let tasks = session.tasks(&file_path)?;
+    let mut indices = selection.find(tasks)?;
+    if !selection.is_bulk() {
+        tasks[indices[0]].ensure_open()?;
     } else {
-        &args[4]
-    });
-
-    if !file_path.exists() {
-        return Err(CliError::Io(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Task file {} not found", file_path.display()),
-        )));
+        // Finished tasks stay completed or cancelled as they were.
+        indices.retain(|&i| tasks[i].is_open());
     }
-
-    let mut tasks = read_tasks(file_path)?;
-    let target_id = args[2]
-        .parse()
-        .map_err(|_| CliError::Parse("Invalid task ID".into()))?;
-
-    let index = tasks
-        .iter()
-        .position(|t| t.id() == target_id)
-        .ok_or(CliError::TaskNotFound)?;
-
-    let progress_input = args[3..].join(" ");
-    let progress_made = parse_progress(&progress_input, &tasks[index])?;
-
-    tasks[index].progress += progress_made;
-    let completed = tasks[index].progress >= tasks[index].estimated_time;
-
-    if completed {
-        tasks.remove(index);
+    let affected = picked(tasks, &indices);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "cancel",
+    )? {
+        return Ok(());
     }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
 
-    if tasks.is_empty() {
-        remove_file(file_path)?;
-    } else {
-        save_tasks(&tasks, file_path, true)?;
+    let now = Local::now().timestamp();
+    for &index in &indices {
+        tasks[index].cancelled = now;
     }
+    let cancelled: Vec<Task> = indices.iter().map(|&i| tasks[i].clone()).collect();
+    session.save(&file_path)?;
 
-    println!(
-        "{}",
-        format!(
-            "Task {}",
-            if completed {
-                "completed".green().into()
-            } else {
+    if !selection.is_bulk() {
+        let cancelled = &cancelled[0];
+        report(
+            || {
                 format!(
-                    "progress updated to {:.1}%",
-                    (tasks[index].progress as f32 / tasks[index].estimated_time as f32) * 100.0
+                    "{}{}",
+                    "Successfully cancelled task with id ".green(),
+                    cancelled.id().to_string().cyan()
                 )
-                .cyan()
-            }
-        )
-        .bold()
-    );
+            },
+            || json!({ "cancelled": task_json(cancelled) }),
+        );
+    } else {
+        report_bulk("Cancelled", "cancelled", &cancelled);
+    }
     Ok(())
 }
 
-fn parse_progress(input: &str, task: &Task) -> Result<i64> {
-    let time_re = Regex::new(r"^(?:(\d+)h\s*)?(?:(\d+)m\s*)?(?:(\d+)s)?$")
-        .map_err(|e| CliError::Input(e.to_string()))?;
-
-    let percent_re = Regex::new(r"^(\d+)%$").map_err(|e| CliError::Input(e.to_string()))?;
-
-    if let Some(caps) = time_re.captures(input) {
-        Ok(caps
-            .get(1)
-            .map_or(0, |m| m.as_str().parse::<i64>().unwrap_or(0))
-            * 3600
-            + caps
-                .get(2)
-                .map_or(0, |m| m.as_str().parse::<i64>().unwrap_or(0))
-                * 60
-            + caps
-                .get(3)
-                .map_or(0, |m| m.as_str().parse::<i64>().unwrap_or(0)))
-    } else if let Some(caps) = percent_re.captures(input) {
-        let percent = caps[1]
-            .parse::<f32>()
-            .map_err(|_| CliError::Parse("Invalid percentage".into()))?;
-        Ok((task.estimated_time as f32 * (percent / 100.0)).round() as i64)
+fn handle_progress(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    let (selection, rest) = args.selection()?;
+    if rest.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    // The amount may span several arguments ("2h 30m"), so only treat the
+    // last argument as the file when it doesn't look like part of an amount.
+    let amount_re =
+        Regex::new(r"^(?:\d+[wdhms]|\d+%)$").map_err(|e| Error::Input(e.to_string()))?;
+    let amount_end = if rest.len() > 1 && !amount_re.is_match(&rest[rest.len() - 1]) {
+        rest.len() - 1
+    } else {
+        rest.len()
+    };
+    let progress_input = rest[..amount_end].join(" ");
+
+    let file_path = session.file(rest.get(amount_end));
+
+    let tasks = session.tasks(&file_path)?;
+    let mut indices = selection.find(tasks)?;
+    if !selection.is_bulk() {
+        tasks[indices[0]].ensure_open()?;
     } else {
-        Err(CliError::Input("Invalid progress format".into()))
+        // Ranges and filters may take in finished tasks, which are left alone.
+        indices.retain(|&i| tasks[i].is_open());
     }
-}
+    // Check the amount before asking for confirmation.
+    for &index in &indices {
+        parse_progress(&progress_input, &tasks[index])?;
+    }
+    let affected = picked(tasks, &indices);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "log progress on",
+    )? {
+        return Ok(());
+    }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
 
-fn handle_edit(args: &[String]) -> Result<()> {
-    if args.len() < 3 {
-        return Err(CliError::InvalidArguments);
+    let mut progress_made = 0;
+    for &index in &indices {
+        progress_made = parse_progress(&progress_input, &tasks[index])?;
+        tasks[index].log_progress(progress_made);
     }
+    let updated: Vec<Task> = indices.iter().map(|&i| tasks[i].clone()).collect();
 
-    let file_path = Path::new(if args.len() == 3 {
-        "./task_list"
-    } else {
-        &args[3]
-    });
+    session.save(&file_path)?;
 
-    if !file_path.exists() {
-        return Err(CliError::Io(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Task file {} not found", file_path.display()),
-        )));
+    if selection.is_bulk() {
+        report_bulk("Updated", "updated", &updated);
+        return Ok(());
     }
 
-    let mut tasks = read_tasks(file_path)?;
-    let target_id = args[2]
-        .parse()
-        .map_err(|_| CliError::Parse("Invalid task ID".into()))?;
+    let task = &updated[0];
+    let completed = task.is_completed();
+    report(
+        || {
+            format!(
+                "Task {}",
+                if completed {
+                    "completed".green()
+                } else {
+                    format!(
+                        "progress updated to {:.1}%",
+                        (task.progress as f32 / task.estimated_time as f32) * 100.0
+                    )
+                    .cyan()
+                }
+            )
+            .bold()
+            .to_string()
+        },
+        || {
+            json!({
+                "task": task_json(task),
+                "progress_made": progress_made,
+                "completed": completed,
+            })
+        },
+    );
+    Ok(())
+}
 
-    let index = tasks
-        .iter()
-        .position(|t| t.id() == target_id)
-        .ok_or(CliError::TaskNotFound)?;
+fn handle_edit(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match"])?;
+    let (target, rest) = args.target()?;
+    let file_path = session.file(rest.first());
 
-    let original_task = tasks[index].clone();
+    let index = target.find(session.tasks(&file_path)?)?;
+    let original_task = session.tasks(&file_path)?[index].clone();
+    let mut task = original_task.clone();
+    // Other commands can use the file while the answers are awaited.
+    session.reload();
 
-    tasks[index].deadline = query(
+    task.deadline = query(
         &format!("Due (press Enter to keep {}): ", original_task.format_due()),
-        r"^(?:(\d{4}-\d{2}-\d{2})(?: (\d{2}:\d{2}:\d{2}))?|(\d{2}:\d{2}:\d{2}))?$",
+        &format!("^(?:{DUE_PATTERN})?$"),
         |v| {
-            if v[0].as_ref().map(|s| s.trim().is_empty()).unwrap_or(true) {
+            if v.iter().all(Option::is_none) {
                 return Ok(original_task.deadline);
             }
-
-            let date = match &v[0] {
-                Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"),
-                None => Ok(Local::now().date_naive()),
-            }
-            .map_err(|_| CliError::Input("Invalid date format".into()))?;
-
-            let time = match &v[1] {
-                Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
-                None => match &v[2] {
-                    Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
-                    None => Ok(Local::now().time()),
-                },
-            }
-            .map_err(|_| CliError::Input("Invalid time format".into()))?;
-
-            Ok(chrono::NaiveDateTime::new(date, time)
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp())
+            parse_due(&v)
         },
     )?;
 
-    tasks[index].estimated_time = query(
+    task.estimated_time = query(
         &format!(
             "Estimated time (press Enter to keep {}): ",
             format_duration(original_task.estimated_time)
         ),
-        r"^(?:(\d+)h\s*)?(?:(\d+)m\s*)?(?:(\d+)s)?$",
+        DURATION_PATTERN,
         |v| {
-            if v[0].as_ref().map(|s| s.trim().is_empty()).unwrap_or(true) {
+            if v.iter().all(Option::is_none) {
                 return Ok(original_task.estimated_time);
             }
-
-            Ok(v[0]
-                .clone()
-                .unwrap_or("0".into())
-                .parse::<i64>()
-                .map_err(|_| CliError::Parse("Invalid hours".into()))?
-                * 3600
-                + v[1]
-                    .clone()
-                    .unwrap_or("0".into())
-                    .parse::<i64>()
-                    .map_err(|_| CliError::Parse("Invalid minutes".into()))?
-                    * 60
-                + v[2]
-                    .clone()
-                    .unwrap_or("0".into())
-                    .parse::<i64>()
-                    .map_err(|_| CliError::Parse("Invalid seconds".into()))?)
+            parse_duration(&v)
         },
     )?;
 
-    tasks[index].name = query(
+    task.name = query(
         &format!("Name (press Enter to keep \"{}\"): ", original_task.name),
         r"(.*)",
         |v| {
@@ -546,7 +1115,7 @@ fn handle_edit(args: &[String]) -> Result<()> {
         },
     )?;
 
-    tasks[index].description = query(
+    task.description = query(
         &format!(
             "Description (press Enter to keep \"{}\"): ",
             original_task.description
@@ -562,8 +1131,1074 @@ fn handle_edit(args: &[String]) -> Result<()> {
         },
     )?;
 
-    save_tasks(&tasks, file_path, true)?;
-    println!("{}", "Task updated successfully".green());
-    println!("{}", tasks[index]);
+    // Only the fields asked for change, in the task as it is by now.
+    let current = session
+        .tasks(&file_path)?
+        .iter_mut()
+        .find(|t| t.uuid() == task.uuid())
+        .ok_or(Error::TaskNotFound)?;
+    current.deadline = task.deadline;
+    current.estimated_time = task.estimated_time;
+    current.name = task.name;
+    current.description = task.description;
+    let task = current.clone();
+    session.save(&file_path)?;
+    report(
+        || format!("{}\n{}", "Task updated successfully".green(), task),
+        || json!({ "updated": task_json(&task) }),
+    );
+    Ok(())
+}
+
+fn handle_reschedule(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    // `--overdue` is short for `--where overdue`.
+    let (selection, rest) = if args.switch("overdue") {
+        (Selection::Where("overdue".parse()?), &args.positional[2..])
+    } else {
+        args.selection()?
+    };
+    if rest.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    // The new deadline may be a date and a time, so only treat the last
+    // argument as the file when it can't be part of one.
+    let when_re = Regex::new(r"^(?:\+\S+|\d{4}-\d{2}-\d{2}|\d{2}:\d{2}:\d{2})$")
+        .map_err(|e| Error::Input(e.to_string()))?;
+    let when_end = if rest.len() > 1 && !when_re.is_match(&rest[rest.len() - 1]) {
+        rest.len() - 1
+    } else {
+        rest.len()
+    };
+    let when = rest[..when_end].join(" ");
+    let file_path = session.file(rest.get(when_end));
+
+    let tasks = session.tasks(&file_path)?;
+    let affected = picked(tasks, &selection.find(tasks)?);
+    // Check the new deadline before asking for confirmation.
+    for task in &affected {
+        parse_when(&when, task.deadline)?;
+    }
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "reschedule",
+    )? {
+        return Ok(());
+    }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
+    let deadlines = indices
+        .iter()
+        .map(|&i| parse_when(&when, tasks[i].deadline))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (&index, deadline) in indices.iter().zip(deadlines) {
+        tasks[index].deadline = deadline;
+    }
+    let rescheduled: Vec<Task> = indices.iter().map(|&i| tasks[i].clone()).collect();
+    session.save(&file_path)?;
+
+    report_bulk("Rescheduled", "rescheduled", &rescheduled);
+    Ok(())
+}
+
+fn handle_set(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    let (selection, rest) = args.selection()?;
+
+    // Every field is `name=value`, so a trailing argument without `=` is the
+    // file.
+    let fields_end = match rest.last() {
+        Some(last) if !last.contains('=') => rest.len() - 1,
+        _ => rest.len(),
+    };
+    let fields = rest[..fields_end]
+        .iter()
+        .map(|f| Field::parse(f))
+        .collect::<Result<Vec<_>>>()?;
+    if fields.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+    let file_path = session.file(rest.get(fields_end));
+
+    let tasks = session.tasks(&file_path)?;
+    let affected = picked(tasks, &selection.find(tasks)?);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "update",
+    )? {
+        return Ok(());
+    }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
+
+    for &index in &indices {
+        for field in &fields {
+            field.apply(&mut tasks[index])?;
+        }
+    }
+    let updated: Vec<Task> = indices.iter().map(|&i| tasks[i].clone()).collect();
+    session.save(&file_path)?;
+
+    report_bulk("Updated", "updated", &updated);
+    Ok(())
+}
+
+fn handle_renumber(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["sort"])?;
+    let file_path = session.file(args.positional.get(2));
+    let keys = match args.option("sort") {
+        Some(keys) => sort::parse_keys(keys)?,
+        None => vec![SortKey::Id],
+    };
+    let dry_run = args.switch("dry-run");
+
+    let tasks = session.tasks(&file_path)?;
+    let mut renumbered = tasks.clone();
+    let changes = renumber(&mut renumbered, &keys);
+    if !dry_run && !changes.is_empty() {
+        *tasks = renumbered.clone();
+        session.save(&file_path)?;
+    }
+
+    let uuids: HashMap<i64, Uuid> = renumbered.iter().map(|t| (t.id(), t.uuid())).collect();
+    report(
+        || {
+            if changes.is_empty() {
+                return "Ids are already dense".green().to_string();
+            }
+            let heading = if dry_run {
+                format!("Would renumber {} task(s):", changes.len())
+            } else {
+                format!("Renumbered {} task(s):", changes.len())
+            };
+            std::iter::once(heading.green().to_string())
+                .chain(changes.iter().map(|(from, to)| {
+                    format!(
+                        "  {} {} {}",
+                        format!("{from:>3}").cyan(),
+                        "→".truecolor(128, 128, 128),
+                        to.to_string().cyan()
+                    )
+                }))
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        || {
+            json!({
+                "dry_run": dry_run,
+                "renumbered": changes
+                    .iter()
+                    .map(|(from, to)| json!({ "from": from, "to": to, "uuid": uuids[to].to_string() }))
+                    .collect::<Vec<_>>(),
+            })
+        },
+    );
+    Ok(())
+}
+
+fn handle_move(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where", "to"])?;
+    let (selection, rest) = args.selection()?;
+    let file_path = session.file(rest.first());
+    let project = args
+        .option("to")
+        .ok_or(Error::Input("Missing --to <project>".into()))?;
+    let destination = session.projects()?.file(project)?;
+    if destination == file_path {
+        return Err(Error::Input(format!(
+            "The tasks are in project \"{project}\" already"
+        )));
+    }
+
+    let tasks = session.tasks(&file_path)?;
+    let affected = picked(tasks, &selection.find(tasks)?);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "move",
+    )? {
+        return Ok(());
+    }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
+    let mut moved = picked(tasks, &indices);
+
+    // The tasks are only removed once they are safe in the other project.
+    let from = adopt(session.tasks_or_empty(&destination)?, &mut moved);
+    session.save(&destination)?;
+    remove_at(session.tasks(&file_path)?, indices);
+    session.save(&file_path)?;
+
+    report(
+        || {
+            moved_lines(
+                &format!("Moved {} task(s) to project {project}", moved.len()),
+                &moved,
+                &from,
+            )
+        },
+        || json!({ "project": project, "moved": moved_json(&moved, &from) }),
+    );
+    Ok(())
+}
+
+fn handle_archive(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["older-than", "where"])?;
+    let file_path = session.file(args.positional.get(2));
+    let age = match args.option("older-than") {
+        Some(age) => parse::duration(age)?,
+        None => Config::load()?
+            .archive_after
+            .unwrap_or(archive::DEFAULT_AGE),
+    };
+    let filter: Option<Filter> = args.option("where").map(str::parse).transpose()?;
+
+    let now = Local::now().timestamp();
+    let tasks = session.tasks(&file_path)?;
+    let indices: Vec<usize> = (0..tasks.len())
+        .filter(|&i| {
+            archive::is_stale(&tasks[i], age, now)
+                || filter.as_ref().is_some_and(|f| f.matches(&tasks[i]))
+        })
+        .collect();
+    let affected = picked(tasks, &indices);
+    if !confirm(&args, session, true, &affected, "archive")? {
+        return Ok(());
+    }
+    let tasks = session.tasks(&file_path)?;
+    let indices = reselect(tasks, &affected)?;
+    let mut archived = picked(tasks, &indices);
+
+    // Like `move`, the archive is written before the tasks leave the file.
+    let mut archive = archive::open(&file_path)?;
+    let from = adopt(archive.tasks_mut(), &mut archived);
+    archive.save()?;
+    remove_at(session.tasks(&file_path)?, indices);
+    session.save(&file_path)?;
+
+    report(
+        || {
+            if archived.is_empty() {
+                return "No tasks to archive".green().to_string();
+            }
+            moved_lines(
+                &format!(
+                    "Archived {} task(s) to {}",
+                    archived.len(),
+                    archive.path().display()
+                ),
+                &archived,
+                &from,
+            )
+        },
+        || json!({ "archived": moved_json(&archived, &from) }),
+    );
+    Ok(())
+}
+
+fn handle_unarchive(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "where"])?;
+    let (selection, rest) = args.selection()?;
+    let file_path = session.file(rest.first());
+
+    // The task file is read first so an encrypted archive shares its passphrase.
+    session.tasks_or_empty(&file_path)?;
+    let archive = archive::open(&file_path)?;
+    let affected = picked(archive.tasks(), &selection.find(archive.tasks())?);
+    if !confirm(
+        &args,
+        session,
+        selection.needs_confirmation(&affected),
+        &affected,
+        "unarchive",
+    )? {
+        return Ok(());
+    }
+    // The archive is read again in case it changed while asking; the lock on
+    // the task file covers it too.
+    session.tasks_or_empty(&file_path)?;
+    let mut archive = archive::open(&file_path)?;
+    let indices = reselect(archive.tasks(), &affected)?;
+    let mut restored = picked(archive.tasks(), &indices);
+
+    let from = adopt(session.tasks_or_empty(&file_path)?, &mut restored);
+    session.save(&file_path)?;
+    remove_at(archive.tasks_mut(), indices);
+    archive.save()?;
+
+    report(
+        || {
+            moved_lines(
+                &format!("Restored {} task(s) from the archive", restored.len()),
+                &restored,
+                &from,
+            )
+        },
+        || json!({ "unarchived": moved_json(&restored, &from) }),
+    );
+    Ok(())
+}
+
+/// Gives `moved` the ids following those of `target` and adds them to it,
+/// returning the id each had before.
+fn adopt(target: &mut Vec<Task>, moved: &mut [Task]) -> Vec<i64> {
+    moved
+        .iter_mut()
+        .map(|task| {
+            let from = task.id();
+            task.set_id(next_id(target));
+            target.push(task.clone());
+            from
+        })
+        .collect()
+}
+
+/// Removes the tasks at `indices`, given in task order.
+fn remove_at(tasks: &mut Vec<Task>, indices: Vec<usize>) {
+    for i in indices.into_iter().rev() {
+        tasks.remove(i);
+    }
+}
+
+/// `heading` over the tasks moved to another file, each with its old id.
+fn moved_lines(heading: &str, moved: &[Task], from: &[i64]) -> String {
+    std::iter::once(heading.green().to_string())
+        .chain(
+            moved
+                .iter()
+                .zip(from)
+                .map(|(task, from)| format!("{} (was {from})", task_line(task))),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The tasks moved to another file, each with its old id as `from`.
+fn moved_json(moved: &[Task], from: &[i64]) -> Vec<serde_json::Value> {
+    moved
+        .iter()
+        .zip(from)
+        .map(|(task, from)| {
+            let mut task = task_json(task);
+            task["from"] = json!(from);
+            task
+        })
+        .collect()
+}
+
+/// One line summarising `task` in lists of affected tasks.
+fn task_line(task: &Task) -> String {
+    format!(
+        "  {} {} due {}",
+        format!("{:>3}", task.id()).cyan(),
+        task.name.bold(),
+        task.format_due().truecolor(255, 140, 0)
+    )
+}
+
+/// Reports the tasks changed by a bulk command under `key`.
+fn report_bulk(verb: &str, key: &str, tasks: &[Task]) {
+    report(
+        || {
+            if tasks.is_empty() {
+                return "No matching tasks".green().to_string();
+            }
+            std::iter::once(
+                format!("{verb} {} task(s)", tasks.len())
+                    .green()
+                    .to_string(),
+            )
+            .chain(tasks.iter().map(task_line))
+            .collect::<Vec<_>>()
+            .join("\n")
+        },
+        || json!({ key: tasks.iter().map(task_json).collect::<Vec<_>>() }),
+    );
+}
+
+/// Decides whether a command goes ahead with the `affected` tasks.
+///
+/// With `--dry-run` the tasks are only listed. When `ask` is set, as
+/// [`Selection::needs_confirmation`] decides, the tasks are listed and
+/// confirmed first, unless `--yes` was given. Asking reloads `session`, so
+/// no file stays locked until the answer comes.
+fn confirm(
+    args: &Args,
+    session: &mut Session,
+    ask: bool,
+    affected: &[Task],
+    action: &str,
+) -> Result<bool> {
+    let listing = || {
+        std::iter::once(format!("Would {action} {} task(s):", affected.len()))
+            .chain(affected.iter().map(task_line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    if args.switch("dry-run") {
+        report(listing, || {
+            json!({
+                "dry_run": true,
+                "action": action,
+                "tasks": affected.iter().map(task_json).collect::<Vec<_>>(),
+            })
+        });
+        return Ok(false);
+    }
+    if !ask || args.switch("yes") {
+        return Ok(true);
+    }
+
+    // The listing goes to stderr in JSON mode, like the prompt itself.
+    if output::is_text() {
+        println!("{}", listing());
+    } else {
+        eprintln!("{}", listing());
+    }
+    // Other commands can use the files while the answer is awaited; the
+    // caller reads them again and finds the tasks with `reselect`.
+    session.reload();
+    let proceed = query("Proceed? [y/N] ", r"(?i)^(y|yes|n|no)?$", |v| {
+        Ok(v[0].as_deref().is_some_and(|a| a.starts_with(['y', 'Y'])))
+    })?;
+    if !proceed {
+        report(
+            || "Cancelled, no tasks changed".yellow().to_string(),
+            || json!({ "cancelled": true }),
+        );
+    }
+    Ok(proceed)
+}
+
+/// Copies of the tasks at `indices`.
+fn picked(tasks: &[Task], indices: &[usize]) -> Vec<Task> {
+    indices.iter().map(|&i| tasks[i].clone()).collect()
+}
+
+/// Indices in `tasks` of the `affected` tasks passed to [`confirm`], in task
+/// order, found by UUID as the file may have been read again meanwhile.
+fn reselect(tasks: &[Task], affected: &[Task]) -> Result<Vec<usize>> {
+    let mut indices = affected
+        .iter()
+        .map(|a| {
+            tasks
+                .iter()
+                .position(|t| t.uuid() == a.uuid())
+                .ok_or_else(|| {
+                    Error::Input(format!(
+                        "Task {} was removed while waiting for confirmation, nothing was changed",
+                        a.id()
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+fn handle_pomodoro(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match", "work", "break", "long-break", "sessions"])?;
+    let (target, rest) = args.target()?;
+    let file_path = session.file(rest.first());
+
+    let mut options = Config::load()?.pomodoro;
+    if let Some(work) = args.option("work") {
+        options.work = parse::duration(work)?;
+    }
+    if let Some(short_break) = args.option("break") {
+        options.short_break = parse::duration(short_break)?;
+    }
+    if let Some(long_break) = args.option("long-break") {
+        options.long_break = parse::duration(long_break)?;
+    }
+    if let Some(sessions) = args.option("sessions") {
+        options.sessions = Some(
+            sessions
+                .parse()
+                .map_err(|_| Error::Parse("Invalid number for --sessions".into()))?,
+        );
+    }
+    if options.work <= 0 {
+        return Err(Error::Input("Work intervals must be longer than 0s".into()));
+    }
+
+    let tasks = session.tasks(&file_path)?;
+    let task = &tasks[target.find(tasks)?];
+    task.ensure_open()?;
+    let (id, uuid) = (task.id(), task.uuid());
+    if output::is_text() {
+        println!(
+            "{} {}",
+            format!("Pomodoro on task {id} \"{}\"", task.name).bold(),
+            format!(
+                "({} work, {} breaks)",
+                strip_colors(&format_duration(options.work)).trim_end(),
+                strip_colors(&format_duration(options.short_break)).trim_end()
+            )
+            .truecolor(128, 128, 128)
+        );
+    }
+
+    let summary = pomodoro::run(session, &file_path, uuid, &options)?;
+    let tasks = session.tasks(&file_path)?;
+    let task = tasks
+        .iter()
+        .find(|t| t.uuid() == uuid)
+        .ok_or(Error::TaskNotFound)?
+        .clone();
+
+    report(
+        || {
+            let label = |s: &str| s.truecolor(128, 128, 128).bold().to_string();
+            let status = if summary.completed {
+                "completed".green()
+            } else if summary.stopped {
+                "stopped".yellow()
+            } else {
+                "sessions done".cyan()
+            };
+            format::card(vec![
+                (label("Task:"), task.name.bold().to_string()),
+                (
+                    label("Work intervals:"),
+                    summary.sessions.to_string().cyan().to_string(),
+                ),
+                (label("Time credited:"), format_duration(summary.credited)),
+                (
+                    label("Progress:"),
+                    format::progress_bar(task.get_completion()),
+                ),
+                (label("Status:"), status.to_string()),
+            ])
+        },
+        || {
+            json!({
+                "task": task_json(&task),
+                "sessions": summary.sessions,
+                "credited": summary.credited,
+                "completed": summary.completed,
+                "stopped": summary.stopped,
+            })
+        },
+    );
+    Ok(())
+}
+
+fn handle_show(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["match"])?;
+    let (target, rest) = args.target()?;
+    let file_path = session.file(rest.first());
+
+    let tasks = session.tasks(&file_path)?;
+    let task = &tasks[target.find(tasks)?];
+
+    report(
+        || format!("{}\n{}", task, task.render_details()),
+        || task_details_json(task),
+    );
+    Ok(())
+}
+
+fn handle_search(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let query = args.positional.get(2).ok_or(Error::InvalidArguments)?;
+    let file_path = session.file(args.positional.get(3));
+
+    let tasks = session.tasks(&file_path)?;
+    let results = search::search(query, tasks);
+
+    report_list(
+        || {
+            if results.is_empty() {
+                return "No matching tasks".yellow().to_string();
+            }
+            results
+                .iter()
+                .map(|(t, m)| format!("{}\n", t.render_highlighted(&m.name, &m.description)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        || {
+            results
+                .iter()
+                .map(|(t, m)| json!({ "task": task_json(t), "score": m.score }))
+                .collect()
+        },
+    );
+    Ok(())
+}
+
+fn handle_agenda(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+
+    // The range is optional, so a lone argument that isn't one is the file.
+    let (range, file) = match args.positional.get(2) {
+        Some(range) if agenda::Range::NAMES.contains(&range.as_str()) => {
+            (range.parse()?, args.positional.get(3))
+        }
+        file => (agenda::Range::Week, file),
+    };
+    let file_path = session.file(file);
+
+    let groups = agenda::agenda(session.tasks(&file_path)?, range);
+    report_list(
+        || agenda::render_agenda(&groups),
+        || agenda::agenda_json(&groups),
+    );
+    Ok(())
+}
+
+fn handle_calendar(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let month_re = Regex::new(r"^\d{4}-\d{2}$").map_err(|e| Error::Input(e.to_string()))?;
+
+    let (first, file) = match args.positional.get(2) {
+        Some(month) if month_re.is_match(month) => (
+            chrono::NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+                .map_err(|_| Error::Input("Invalid month".into()))?,
+            args.positional.get(3),
+        ),
+        file => (Local::now().date_naive().with_day(1).unwrap(), file),
+    };
+    let file_path = session.file(file);
+
+    let days = agenda::month(session.tasks(&file_path)?, first);
+    report(
+        || agenda::render_calendar(&days),
+        || agenda::calendar_json(&days),
+    );
+    Ok(())
+}
+
+fn handle_stats(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let file_path = session.file(args.positional.get(2));
+
+    let stats = stats::Stats::new(session.tasks(&file_path)?);
+    report(|| stats.render(), || stats.to_json());
+    Ok(())
+}
+
+fn handle_chart(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["days", "weeks", "capacity"])?;
+    let file_path = session.file(args.positional.get(2));
+
+    let count = |name: &str, default: usize| match args.option(name) {
+        Some(n) => n
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| Error::Parse(format!("Invalid number for --{name}"))),
+        None => Ok(default),
+    };
+    let days = count("days", 14)?;
+    let weeks = count("weeks", 2)?;
+    let capacity = match args.option("capacity") {
+        Some(capacity) => parse::duration(capacity)?,
+        None => 8 * 3600,
+    };
+
+    let tasks = session.tasks(&file_path)?;
+    let points = chart::burndown(tasks, days);
+    let workload = chart::workload(tasks, weeks);
+
+    report(
+        || {
+            format!(
+                "{}\n\n{}",
+                chart::render_burndown(&points),
+                chart::render_workload(&workload, capacity)
+            )
+        },
+        || chart::chart_json(&points, &workload, capacity),
+    );
+    Ok(())
+}
+
+fn handle_watch(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["interval", "before", "command"])?;
+    let file_path = session.file(args.positional.get(2));
+
+    let mut options = Config::load()?.watch;
+    if let Some(interval) = args.option("interval") {
+        options.interval = parse::duration(interval)?;
+    }
+    if let Some(offsets) = args.option("before") {
+        options.offsets = watch::parse_offsets(offsets)?;
+    }
+    if args.switch("bell") {
+        options.bell = true;
+    }
+    if let Some(command) = args.option("command") {
+        options.command = Some(command.to_owned());
+    }
+
+    watch::run(&file_path, &options, session.storage())
+}
+
+fn handle_migrate_storage(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["to"])?;
+    let (Some(source), Some(destination)) = (args.positional.get(2), args.positional.get(3)) else {
+        return Err(Error::InvalidArguments);
+    };
+    let (source, destination) = (Path::new(source), Path::new(destination));
+    if !source.exists() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Task file {} not found", source.display()),
+        )));
+    }
+    if destination.exists() {
+        return Err(Error::Input(format!(
+            "{} already exists",
+            destination.display()
+        )));
+    }
+
+    let from = Backend::for_path(source, session.storage())?;
+    // Without --to or a telling extension, migrate to the other backend.
+    let to = match args.option("to") {
+        Some(to) => to.parse()?,
+        None => Backend::for_path(
+            destination,
+            match from {
+                Backend::Binary => Backend::Sqlite,
+                _ => Backend::Binary,
+            },
+        )?,
+    };
+
+    // Loading through the store gives tasks from old files their UUIDs.
+    let tasks = TaskStore::with_storage(source, from.open(source))?
+        .tasks()
+        .to_vec();
+    let changes = Changes {
+        written: tasks.iter().collect(),
+        removed: Vec::new(),
+    };
+    to.open(destination).save(&tasks, &changes)?;
+
+    report(
+        || {
+            format!(
+                "{} {} ({}) {} {} ({})",
+                format!("Migrated {} task(s) from", tasks.len()).green(),
+                source.display().to_string().cyan(),
+                from.name(),
+                "to".green(),
+                destination.display().to_string().cyan(),
+                to.name()
+            )
+        },
+        || {
+            json!({
+                "migrated": tasks.len(),
+                "from": { "file": source.display().to_string(), "storage": from.name() },
+                "to": { "file": destination.display().to_string(), "storage": to.name() },
+            })
+        },
+    );
+    Ok(())
+}
+
+fn handle_rekey(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["new-key-file"])?;
+    let file_path = session.file(args.positional.get(2));
+    if !file_path.exists() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Task file {} not found", file_path.display()),
+        )));
+    }
+    if Backend::for_path(&file_path, session.storage())? != Backend::Encrypted {
+        return Err(Error::Input(format!(
+            "{} isn't encrypted; use migrate-storage --to encrypted to encrypt it",
+            file_path.display()
+        )));
+    }
+
+    let mut storage = Encrypted::new(&file_path);
+    // Ask for the current passphrase before the new one.
+    storage.load()?;
+    let new = passphrase::new_passphrase(&file_path, args.option("new-key-file"))?;
+    let count = storage.rekey(&new)?;
+    passphrase::remember(&file_path, &new);
+    // Loaded copies still hold the old key.
+    session.reload();
+
+    report(
+        || {
+            format!(
+                "{} {} ({} task(s))",
+                "Changed the passphrase of".green(),
+                file_path.display().to_string().cyan(),
+                count
+            )
+        },
+        || json!({ "rekeyed": file_path.display().to_string(), "tasks": count }),
+    );
+    Ok(())
+}
+
+fn handle_sync(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["remote", "branch"])?;
+    let file_path = session.file(args.positional.get(2));
+
+    let mut options = Config::load()?.sync;
+    if let Some(remote) = args.option("remote") {
+        options.remote = remote.to_owned();
+    }
+    if let Some(branch) = args.option("branch") {
+        options.branch = Some(branch.to_owned());
+    }
+    if args.switch("no-push") {
+        options.push = false;
+    }
+    let preferred = match (args.switch("ours"), args.switch("theirs")) {
+        (true, true) => return Err(Error::InvalidArguments),
+        (true, false) => Some(Side::Ours),
+        (false, true) => Some(Side::Theirs),
+        (false, false) => None,
+    };
+
+    let outcome = sync::run(&file_path, &options, session.storage(), |conflict| {
+        if let Some(side) = preferred {
+            return Ok(side);
+        }
+        let message = format!(
+            "Task {} ({}): {} is \"{}\" here and \"{}\" in {}. Keep [o]urs or [t]heirs? ",
+            conflict.id,
+            conflict.name,
+            conflict.field,
+            conflict.ours,
+            conflict.theirs,
+            options.remote
+        );
+        query(&message, r"(?i)^(o|ours|t|theirs)$", |v| {
+            Ok(match v[0].as_deref() {
+                Some(a) if a.starts_with(['t', 'T']) => Side::Theirs,
+                _ => Side::Ours,
+            })
+        })
+    })?;
+    // Loaded copies may predate what was pulled.
+    session.reload();
+
+    let side_name = |side: Side| match side {
+        Side::Ours => "ours",
+        Side::Theirs => "theirs",
+    };
+    report(
+        || {
+            let mut lines = Vec::new();
+            if outcome.committed {
+                lines.push(format!(
+                    "{} {}",
+                    "Committed".green(),
+                    file_path.display().to_string().cyan()
+                ));
+            }
+            lines.push(match outcome.pull {
+                sync::Pull::UpToDate => {
+                    format!("{} {}", "Already up to date with".green(), options.remote)
+                }
+                sync::Pull::FastForward => {
+                    format!("{} {}", "Fast-forwarded to".green(), options.remote)
+                }
+                sync::Pull::Merge => format!("{} {}", "Merged tasks from".green(), options.remote),
+            });
+            if let Some(merged) = &outcome.merged {
+                for (conflict, side) in &merged.conflicts {
+                    lines.push(format!(
+                        "  Task {} {}: kept {}",
+                        conflict.id,
+                        conflict.field,
+                        side_name(*side)
+                    ));
+                }
+                for (from, to) in &merged.renumbered {
+                    lines.push(format!(
+                        "  {}",
+                        format!("Two tasks had id {from}, one of them is now task {to}").yellow()
+                    ));
+                }
+            }
+            if outcome.pushed {
+                lines.push(format!(
+                    "{} {} {}",
+                    "Pushed".green(),
+                    options.remote,
+                    outcome.branch
+                ));
+            }
+            lines.join("\n")
+        },
+        || {
+            let merged = outcome.merged.as_ref();
+            json!({
+                "file": file_path.display().to_string(),
+                "remote": options.remote,
+                "branch": outcome.branch,
+                "committed": outcome.committed,
+                "pull": outcome.pull.name(),
+                "conflicts": merged.map_or_else(Vec::new, |m| m.conflicts.iter().map(|(c, side)| json!({
+                    "id": c.id,
+                    "field": c.field,
+                    "ours": c.ours,
+                    "theirs": c.theirs,
+                    "kept": side_name(*side),
+                })).collect()),
+                "renumbered": merged.map_or_else(Vec::new, |m| m.renumbered.iter().map(|(from, to)| json!({
+                    "from": from,
+                    "to": to,
+                })).collect()),
+                "pushed": outcome.pushed,
+            })
+        },
+    );
+    Ok(())
+}
+
+fn handle_project(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let projects = session.projects()?;
+    let name = args.positional.get(3).map(String::as_str);
+    match (args.positional.get(2).map(String::as_str), name) {
+        (Some("create"), Some(name)) => {
+            let file = projects.create(name)?;
+            report(
+                || format!("{}{}", "Created project ".green(), name.magenta()),
+                || json!({ "created": { "name": name, "file": file } }),
+            );
+        }
+        (Some("list"), None) => {
+            let current = projects.current()?;
+            let mut rows = Vec::new();
+            for name in projects.list()? {
+                let file = projects.file(&name)?;
+                let open = session
+                    .tasks_or_empty(&file)?
+                    .iter()
+                    .filter(|t| t.is_open())
+                    .count();
+                rows.push((current.as_ref() == Some(&name), name, file, open));
+            }
+            report_list(
+                || {
+                    if rows.is_empty() {
+                        return "No projects; create one with `todo-cli project create <name>`"
+                            .yellow()
+                            .to_string();
+                    }
+                    rows.iter()
+                        .map(|(current, name, _, open)| {
+                            let marker = if *current { "*" } else { " " };
+                            format!(
+                                "{} {} {}",
+                                marker.green(),
+                                name.magenta(),
+                                format!("({open} open)").truecolor(128, 128, 128)
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                },
+                || {
+                    rows.iter()
+                        .map(|(current, name, file, open)| {
+                            json!({ "name": name, "current": current, "file": file, "open": open })
+                        })
+                        .collect()
+                },
+            );
+        }
+        (Some("switch"), Some(name)) => {
+            projects.switch(Some(name))?;
+            report(
+                || format!("{}{}", "Switched to project ".green(), name.magenta()),
+                || json!({ "switched": name }),
+            );
+        }
+        (Some("switch"), None) if args.switch("none") => {
+            projects.switch(None)?;
+            report(
+                || {
+                    format!("Switched back to {DEFAULT_FILE}")
+                        .green()
+                        .to_string()
+                },
+                || json!({ "switched": null }),
+            );
+        }
+        _ => return Err(Error::InvalidArguments),
+    }
+    Ok(())
+}
+
+fn handle_serve(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &["host", "port"])?;
+    let file_path = session.file(args.positional.get(2));
+
+    let mut options = Config::load()?.serve;
+    if let Some(host) = args.option("host") {
+        options.host = host.to_owned();
+    }
+    if let Some(port) = args.option("port") {
+        options.port = port
+            .parse()
+            .map_err(|_| Error::Input(format!("Invalid port \"{port}\"")))?;
+    }
+    options.allow_remote = args.switch("allow-remote");
+
+    serve::run(&file_path, &options, session)
+}
+
+fn handle_tui(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    tui::run(&session.file(args.positional.get(2)), session.storage())
+}
+
+fn handle_shell(args: &[String], session: &mut Session) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let file_path = session.file(args.positional.get(2));
+    shell::run(
+        &file_path,
+        session.overdue_policy(),
+        session.storage(),
+        session.data_dir(),
+    )
+}
+
+fn handle_completions(args: &[String]) -> Result<()> {
+    if args.len() < 3 {
+        return Err(Error::InvalidArguments);
+    }
+
+    print!("{}", completions::script(args[2].parse()?));
+    Ok(())
+}
+
+/// Prints `id<TAB>name` for every open task, for the completion scripts.
+/// Prints nothing rather than failing so completion never shows an error.
+fn handle_complete_ids(args: &[String], session: &mut Session) -> Result<()> {
+    let file_path = session.file(args.get(2));
+    let open: Filter = "open".parse()?;
+    if let Ok(tasks) = storage::open(&file_path, session.storage()).and_then(|mut s| s.query(&open))
+    {
+        for task in &tasks {
+            println!("{}\t{}", task.id(), task.name.replace(['\t', '\n'], " "));
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,111 @@
+use std::{str::FromStr, sync::OnceLock};
+
+use serde_json::{json, Value};
+
+use todo_core::{task::Task, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Text,
+    /// One JSON document per invocation.
+    Json,
+    /// Like `Json`, but lists are written as one JSON object per line.
+    Ndjson,
+}
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            other => Err(Error::Input(format!(
+                "Unknown output format \"{other}\" (expected text, json or ndjson)"
+            ))),
+        }
+    }
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Sets the output format for the rest of the process. Only the first call
+/// has any effect.
+pub(crate) fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub(crate) fn format() -> OutputFormat {
+    *FORMAT.get().unwrap_or(&OutputFormat::Text)
+}
+
+pub(crate) fn is_text() -> bool {
+    format() == OutputFormat::Text
+}
+
+/// Prints `text` in text mode, `value` otherwise.
+pub(crate) fn report(text: impl FnOnce() -> String, value: impl FnOnce() -> Value) {
+    match format() {
+        OutputFormat::Text => println!("{}", text()),
+        OutputFormat::Json | OutputFormat::Ndjson => println!("{}", value()),
+    }
+}
+
+/// Like [`report`], but `values` is written as an array or one per line.
+pub(crate) fn report_list(text: impl FnOnce() -> String, values: impl FnOnce() -> Vec<Value>) {
+    match format() {
+        OutputFormat::Text => println!("{}", text()),
+        OutputFormat::Json => println!("{}", Value::Array(values())),
+        OutputFormat::Ndjson => {
+            for value in values() {
+                println!("{}", value);
+            }
+        }
+    }
+}
+
+pub(crate) fn error_json(e: &Error) -> Value {
+    json!({
+        "error": {
+            "kind": e.kind(),
+            "message": e.to_string(),
+            "code": e.exit_code(),
+        }
+    })
+}
+
+/// A task with its stored fields and the values derived from them.
+pub(crate) fn task_json(task: &Task) -> Value {
+    json!({
+        "id": task.id(),
+        "uuid": task.uuid().to_string(),
+        "name": task.name,
+        "description": task.description,
+        "deadline": task.deadline,
+        "due": task.format_due(),
+        "estimated_time": task.estimated_time,
+        "progress": task.progress,
+        "time_left": task.get_time_left(),
+        "remaining": task.get_remaining(),
+        "completion": task.get_completion(),
+        "created": (task.created != 0).then_some(task.created),
+        "completed": task.is_completed().then_some(task.completed),
+        "cancelled": task.is_cancelled().then_some(task.cancelled),
+        "overdue": task.is_overdue(),
+        "priority": task.priority.name(),
+    })
+}
+
+/// A task along with the details shown by `show`.
+pub(crate) fn task_details_json(task: &Task) -> Value {
+    json!({
+        "task": task_json(task),
+        "required_pace": task.get_required_pace(),
+        "time_elapsed": task.get_time_elapsed(),
+        "log": task
+            .log
+            .iter()
+            .map(|e| json!({ "timestamp": e.timestamp, "amount": e.amount }))
+            .collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,128 @@
+use std::{io::IsTerminal, str::FromStr};
+
+use colored::*;
+
+use crate::query;
+use todo_core::{parse::parse_when, task::Task, Error, Result, Uuid};
+
+/// What happens to a task the first time it is seen past its deadline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum OverduePolicy {
+    /// Only flag the task as overdue.
+    #[default]
+    None,
+    /// Raise the task's priority by one level.
+    Escalate,
+    /// Ask for a new deadline, when running interactively.
+    Prompt,
+}
+impl OverduePolicy {
+    pub(crate) const NAMES: [&'static str; 3] = ["none", "escalate", "prompt"];
+}
+impl FromStr for OverduePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "none" => Ok(OverduePolicy::None),
+            "escalate" => Ok(OverduePolicy::Escalate),
+            "prompt" => Ok(OverduePolicy::Prompt),
+            other => Err(Error::Input(format!(
+                "Unknown overdue policy \"{other}\" (expected one of: {})",
+                OverduePolicy::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Applies `policy` to every task that missed its deadline since the policy
+/// last looked at it. Returns whether any task changed.
+pub(crate) fn apply(tasks: &mut [Task], policy: OverduePolicy) -> Result<bool> {
+    match policy {
+        OverduePolicy::None => Ok(false),
+        OverduePolicy::Escalate => {
+            let mut changed = false;
+            for task in tasks.iter_mut().filter(|t| is_pending(t)) {
+                task.priority = task.priority.escalated();
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Task {} is overdue, priority raised to {}",
+                        task.id(),
+                        task.priority.name()
+                    )
+                    .yellow()
+                );
+                task.overdue_handled = task.deadline;
+                changed = true;
+            }
+            Ok(changed)
+        }
+        OverduePolicy::Prompt => {
+            let answers = ask(tasks)?;
+            Ok(answer(tasks, &answers))
+        }
+    }
+}
+
+/// Whether `task` missed a deadline the policy hasn't handled yet.
+pub(crate) fn is_pending(task: &Task) -> bool {
+    task.is_overdue() && task.overdue_handled != task.deadline
+}
+
+/// The answer to the prompt policy for one task: its UUID, the deadline it
+/// missed and the new deadline given, if any.
+pub(crate) type Answer = (Uuid, i64, Option<i64>);
+
+/// Asks for a new deadline for every task [`is_pending`], when running
+/// interactively. The answers only touch the tasks once given to
+/// [`answer`], so the file doesn't have to stay locked while asking.
+pub(crate) fn ask(tasks: &[Task]) -> Result<Vec<Answer>> {
+    // Prompting needs someone to answer.
+    if !std::io::stdin().is_terminal() {
+        return Ok(Vec::new());
+    }
+
+    let mut answers = Vec::new();
+    for task in tasks.iter().filter(|t| is_pending(t)) {
+        let deadline = task.deadline;
+        let new_deadline = query(
+            &format!(
+                "Task {} \"{}\" was due {}. New deadline ({}, {} or Enter to keep): ",
+                task.id(),
+                task.name,
+                task.format_due(),
+                "+2d".yellow(),
+                "YYYY-MM-DD HH:MM:SS".yellow()
+            ),
+            r"(.*)",
+            |v| match v[0].as_deref() {
+                None | Some("") => Ok(None),
+                Some(when) => parse_when(when, deadline).map(Some),
+            },
+        )?;
+        answers.push((task.uuid(), deadline, new_deadline));
+    }
+    Ok(answers)
+}
+
+/// Applies `answers` from [`ask`] to the tasks still due when they were
+/// asked about. Returns whether any task changed.
+pub(crate) fn answer(tasks: &mut [Task], answers: &[Answer]) -> bool {
+    let mut changed = false;
+    for &(uuid, deadline, new_deadline) in answers {
+        let Some(task) = tasks
+            .iter_mut()
+            .find(|t| t.uuid() == uuid && t.deadline == deadline && is_pending(t))
+        else {
+            continue;
+        };
+        match new_deadline {
+            // A fresh deadline is handled again once it passes too.
+            Some(new_deadline) => task.deadline = new_deadline,
+            None => task.overdue_handled = task.deadline,
+        }
+        changed = true;
+    }
+    changed
+}
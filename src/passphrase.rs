@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use todo_core::{
+    storage::{self, PassphraseProvider},
+    Error, Result,
+};
+
+/// Passphrases that worked this run, so the shell, `tui` and `watch` ask
+/// only once per file.
+static KNOWN: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
+
+/// Makes encrypted task files take their passphrase from
+/// `$TODO_CLI_PASSPHRASE`, else the key file (`$TODO_CLI_KEY_FILE` or the
+/// `key_file` setting), else a prompt.
+pub(crate) fn install(key_file: Option<PathBuf>) {
+    storage::set_passphrase_provider(Box::new(Provider { key_file }));
+}
+
+struct Provider {
+    key_file: Option<PathBuf>,
+}
+impl PassphraseProvider for Provider {
+    fn passphrase(&self, path: &Path, new: bool) -> Result<String> {
+        if let Some(passphrase) = known(path) {
+            return Ok(passphrase);
+        }
+        let passphrase = match env::var("TODO_CLI_PASSPHRASE") {
+            Ok(passphrase) if !passphrase.is_empty() => passphrase,
+            _ => {
+                let key_file = env::var_os("TODO_CLI_KEY_FILE")
+                    .map(PathBuf::from)
+                    .or_else(|| self.key_file.clone());
+                match key_file {
+                    Some(key_file) => read_key_file(&key_file)?,
+                    None => prompt(&format!("Passphrase for {}", path.display()), new)?,
+                }
+            }
+        };
+        Ok(passphrase)
+    }
+
+    fn accepted(&self, path: &Path, passphrase: &str) {
+        remember(path, passphrase);
+    }
+}
+
+/// The new passphrase for `rekey`: `$TODO_CLI_NEW_PASSPHRASE`, else
+/// `key_file`, else a prompt.
+pub(crate) fn new_passphrase(path: &Path, key_file: Option<&str>) -> Result<String> {
+    match env::var("TODO_CLI_NEW_PASSPHRASE") {
+        Ok(passphrase) if !passphrase.is_empty() => Ok(passphrase),
+        _ => match key_file {
+            Some(key_file) => read_key_file(Path::new(key_file)),
+            None => prompt(&format!("New passphrase for {}", path.display()), true),
+        },
+    }
+}
+
+/// Uses `passphrase` for `path` from now on.
+pub(crate) fn remember(path: &Path, passphrase: &str) {
+    KNOWN
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(path.to_owned(), passphrase.to_owned());
+}
+
+/// Uses the passphrase known for `original`, if any, for `copy` too.
+pub(crate) fn alias(copy: &Path, original: &Path) {
+    if let Some(passphrase) = known(original) {
+        remember(copy, &passphrase);
+    }
+}
+
+fn known(path: &Path) -> Option<String> {
+    KNOWN.lock().unwrap().as_ref()?.get(path).cloned()
+}
+
+/// The first line of `path`, without surrounding whitespace.
+fn read_key_file(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::Input(format!("Can't read key file {}: {e}", path.display())))?;
+    let passphrase = contents.lines().next().unwrap_or("").trim();
+    if passphrase.is_empty() {
+        return Err(Error::Input(format!(
+            "Key file {} is empty",
+            path.display()
+        )));
+    }
+    Ok(passphrase.to_owned())
+}
+
+/// Asks for a passphrase without echoing it, twice if it is a new one.
+fn prompt(message: &str, confirm: bool) -> Result<String> {
+    let passphrase = rpassword::prompt_password(format!("{message}: "))?;
+    if passphrase.is_empty() {
+        return Err(Error::Input("The passphrase can't be empty".into()));
+    }
+    if confirm && rpassword::prompt_password("Repeat it: ")? != passphrase {
+        return Err(Error::Input("The passphrases don't match".into()));
+    }
+    Ok(passphrase)
+}
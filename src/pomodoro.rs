@@ -0,0 +1,217 @@
+use std::{
+    io::{self, stdout, IsTerminal, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use colored::*;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+use crate::{output, session::Session};
+use todo_core::{
+    format::{format_duration, progress_bar, strip_colors},
+    Error, Result, Uuid,
+};
+
+/// Lengths of the intervals, in seconds.
+#[derive(Debug, Clone)]
+pub(crate) struct PomodoroOptions {
+    pub(crate) work: i64,
+    pub(crate) short_break: i64,
+    pub(crate) long_break: i64,
+    /// Every this many work intervals the break is a long one.
+    pub(crate) long_break_every: usize,
+    /// Stop after this many work intervals instead of when the task is done.
+    pub(crate) sessions: Option<usize>,
+}
+impl Default for PomodoroOptions {
+    fn default() -> Self {
+        PomodoroOptions {
+            work: 25 * 60,
+            short_break: 5 * 60,
+            long_break: 15 * 60,
+            long_break_every: 4,
+            sessions: None,
+        }
+    }
+}
+
+/// What a pomodoro run achieved.
+pub(crate) struct Summary {
+    pub(crate) sessions: usize,
+    pub(crate) credited: i64,
+    pub(crate) completed: bool,
+    /// Stopped by the user rather than by finishing.
+    pub(crate) stopped: bool,
+}
+
+/// Leaves the terminal in raw mode, so single key presses can be read, for
+/// as long as it is alive.
+struct RawMode;
+impl RawMode {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Alternates work intervals and breaks on the task with UUID `uuid`, crediting
+/// every finished work interval to its progress. Runs until the task is
+/// completed, `options.sessions` work intervals are done, or `q` is pressed.
+pub(crate) fn run(
+    session: &mut Session,
+    file_path: &Path,
+    uuid: Uuid,
+    options: &PomodoroOptions,
+) -> Result<Summary> {
+    let interactive = output::is_text() && stdout().is_terminal() && io::stdin().is_terminal();
+    let _raw = if interactive {
+        Some(RawMode::enter()?)
+    } else {
+        None
+    };
+
+    let mut summary = Summary {
+        sessions: 0,
+        credited: 0,
+        completed: false,
+        stopped: false,
+    };
+    // The task file is only read and locked while an interval is credited,
+    // so other commands can use it during the countdowns.
+    session.reload();
+    loop {
+        if !countdown("Work", Color::Red, options.work, interactive)? {
+            summary.stopped = true;
+            break;
+        }
+
+        // The file is read afresh in case it changed during the interval,
+        // and the task is found even if it was renumbered.
+        let tasks = session.tasks(file_path)?;
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.uuid() == uuid)
+            .ok_or(Error::TaskNotFound)?;
+        task.log_progress(options.work);
+        summary.completed = task.is_completed();
+        let completion = task.get_completion();
+        session.save(file_path)?;
+        session.reload();
+
+        summary.sessions += 1;
+        summary.credited += options.work;
+        notify(
+            &format!(
+                "Work interval {} done, task at {:.0}%",
+                summary.sessions,
+                completion * 100.0
+            ),
+            interactive,
+        );
+
+        if summary.completed || options.sessions.is_some_and(|n| summary.sessions >= n) {
+            break;
+        }
+
+        let long = summary
+            .sessions
+            .is_multiple_of(options.long_break_every.max(1));
+        let (label, length) = if long {
+            ("Long break", options.long_break)
+        } else {
+            ("Break", options.short_break)
+        };
+        if !countdown(label, Color::Green, length, interactive)? {
+            summary.stopped = true;
+            break;
+        }
+        notify("Break over", interactive);
+    }
+    Ok(summary)
+}
+
+/// Rings the bell and prints `message` on its own line.
+fn notify(message: &str, interactive: bool) {
+    if interactive {
+        print!("\r\x1B[2K\x07{}\r\n", message.bold());
+        let _ = stdout().flush();
+    }
+}
+
+/// Counts `length` seconds down with a live progress bar. `p` pauses and
+/// `q`, Esc or Ctrl-C stop, in which case `false` is returned.
+fn countdown(label: &str, colour: Color, length: i64, interactive: bool) -> Result<bool> {
+    let length = Duration::from_secs(length.max(0) as u64);
+    if !interactive {
+        thread::sleep(length);
+        return Ok(true);
+    }
+
+    let mut elapsed = Duration::ZERO;
+    let mut resumed = Instant::now();
+    let mut paused = false;
+    loop {
+        let current = if paused {
+            elapsed
+        } else {
+            elapsed + resumed.elapsed()
+        };
+        if current >= length {
+            return Ok(true);
+        }
+
+        let left = (length - current).as_secs() as i64;
+        print!(
+            "\r\x1B[2K{} {} {}{}",
+            format!("{label:<10}").color(colour).bold(),
+            progress_bar(current.as_secs_f32() / length.as_secs_f32().max(1.0)),
+            strip_colors(&format_duration(left)).trim_end(),
+            if paused {
+                " paused, p to resume".yellow().to_string()
+            } else {
+                "  (p pause, q stop)".truecolor(128, 128, 128).to_string()
+            }
+        );
+        stdout().flush()?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return stop(),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return stop()
+                }
+                KeyCode::Char('p') | KeyCode::Char(' ') => {
+                    if paused {
+                        resumed = Instant::now();
+                    } else {
+                        elapsed += resumed.elapsed();
+                    }
+                    paused = !paused;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn stop() -> Result<bool> {
+    print!("\r\x1B[2K");
+    stdout().flush()?;
+    Ok(false)
+}
@@ -0,0 +1,210 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use todo_core::{Error, Result};
+
+/// Name of the task file inside each project's directory.
+const TASK_FILE: &str = "tasks";
+
+/// Named task lists kept by todo-cli under its data directory.
+///
+/// Every project is a directory holding its task file, so a project exists
+/// before it has any tasks. The data directory is the `data_dir` setting,
+/// else `$TODO_CLI_DATA`, else `$XDG_DATA_HOME/todo-cli`
+/// (`~/.local/share/todo-cli`). The project switched to is recorded in its
+/// `current` file and used by commands not given a file.
+pub(crate) struct Projects {
+    dir: PathBuf,
+}
+impl Projects {
+    pub(crate) fn new(data_dir: Option<&Path>) -> Result<Self> {
+        let dir = match data_dir {
+            Some(dir) => dir.to_owned(),
+            None => Projects::default_dir().ok_or_else(|| {
+                Error::Input("No data directory; set $TODO_CLI_DATA or $HOME".into())
+            })?,
+        };
+        Ok(Projects { dir })
+    }
+
+    fn default_dir() -> Option<PathBuf> {
+        if let Some(dir) = env::var_os("TODO_CLI_DATA") {
+            return Some(PathBuf::from(dir));
+        }
+        let base = match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => Path::new(&env::var_os("HOME")?)
+                .join(".local")
+                .join("share"),
+        };
+        Some(base.join("todo-cli"))
+    }
+
+    fn project_dir(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(Error::Input(format!(
+                "Invalid project name \"{name}\" (use letters, digits, '-', '_' and '.')"
+            )));
+        }
+        Ok(self.dir.join("projects").join(name))
+    }
+
+    /// The task file of project `name`, which has to exist.
+    pub(crate) fn file(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.project_dir(name)?;
+        if !dir.is_dir() {
+            return Err(Error::Input(format!(
+                "No project \"{name}\"; create it with `todo-cli project create {name}`"
+            )));
+        }
+        Ok(dir.join(TASK_FILE))
+    }
+
+    /// Creates project `name`, returning its task file.
+    pub(crate) fn create(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.project_dir(name)?;
+        if dir.exists() {
+            return Err(Error::Input(format!("Project \"{name}\" already exists")));
+        }
+        fs::create_dir_all(&dir)?;
+        Ok(dir.join(TASK_FILE))
+    }
+
+    /// Names of all projects, sorted.
+    pub(crate) fn list(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(self.dir.join("projects")) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The project switched to, if any.
+    pub(crate) fn current(&self) -> Result<Option<String>> {
+        match fs::read_to_string(self.dir.join("current")) {
+            Ok(name) => Ok(Some(name.trim().to_owned()).filter(|n| !n.is_empty())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+
+    /// Makes `name` the current project, or goes back to `./task_list` for
+    /// `None`.
+    pub(crate) fn switch(&self, name: Option<&str>) -> Result<()> {
+        let current = self.dir.join("current");
+        match name {
+            Some(name) => {
+                self.file(name)?;
+                fs::write(current, format!("{name}\n"))?;
+            }
+            None => match fs::remove_file(current) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(Error::Io(e)),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    /// Task file used by commands not given one: that of `name` if given,
+    /// else that of the current project, if any.
+    pub(crate) fn default_file(&self, name: Option<&str>) -> Result<Option<PathBuf>> {
+        match name {
+            Some(name) => self.file(name).map(Some),
+            None => match self.current()? {
+                Some(current) => self.file(&current).map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// Task file of project `name` under `data_dir`, else of the current project.
+/// Without a data directory there is no current project to fall back to.
+pub(crate) fn default_file(data_dir: Option<&Path>, name: Option<&str>) -> Result<Option<PathBuf>> {
+    match Projects::new(data_dir) {
+        Ok(projects) => projects.default_file(name),
+        Err(_) if name.is_none() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempData(PathBuf);
+    impl TempData {
+        fn new(test: &str) -> Self {
+            let dir =
+                env::temp_dir().join(format!("todo-cli-projects-{}-{test}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TempData(dir)
+        }
+    }
+    impl Drop for TempData {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn creates_and_lists_projects() {
+        let data = TempData::new("create");
+        let projects = Projects::new(Some(&data.0)).unwrap();
+        assert!(projects.list().unwrap().is_empty());
+
+        let file = projects.create("work").unwrap();
+        projects.create("home").unwrap();
+        assert_eq!(file, data.0.join("projects").join("work").join(TASK_FILE));
+        assert_eq!(projects.list().unwrap(), ["home", "work"]);
+        assert_eq!(projects.file("work").unwrap(), file);
+        assert!(projects.create("work").is_err());
+        assert!(projects.file("garden").is_err());
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_plain_directory_names() {
+        let data = TempData::new("names");
+        let projects = Projects::new(Some(&data.0)).unwrap();
+        for name in ["", ".hidden", "../escape", "a/b", "two words"] {
+            assert!(projects.create(name).is_err(), "{name:?}");
+        }
+        assert!(projects.create("side-project_2.0").is_ok());
+    }
+
+    #[test]
+    fn switching_changes_the_default_file() {
+        let data = TempData::new("switch");
+        let projects = Projects::new(Some(&data.0)).unwrap();
+        let work = projects.create("work").unwrap();
+        let home = projects.create("home").unwrap();
+        assert_eq!(projects.default_file(None).unwrap(), None);
+
+        projects.switch(Some("work")).unwrap();
+        assert_eq!(projects.current().unwrap().as_deref(), Some("work"));
+        assert_eq!(projects.default_file(None).unwrap(), Some(work));
+        assert_eq!(projects.default_file(Some("home")).unwrap(), Some(home));
+        assert!(projects.switch(Some("garden")).is_err());
+        assert_eq!(projects.current().unwrap().as_deref(), Some("work"));
+
+        projects.switch(None).unwrap();
+        projects.switch(None).unwrap();
+        assert_eq!(projects.default_file(None).unwrap(), None);
+    }
+}
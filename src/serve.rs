@@ -0,0 +1,651 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    time::Duration,
+};
+
+use chrono::Local;
+use colored::*;
+use serde_json::{json, Value};
+use todo_core::{
+    parse::{parse_progress, Field},
+    sort::{self, sort_tasks, SortKey},
+    store::next_id,
+    task::Task,
+    Error, Filter, Result,
+};
+
+use crate::{
+    args::Target,
+    output::{self, error_json, task_details_json, task_json},
+    overdue::OverduePolicy,
+    session::Session,
+};
+
+/// Where `serve` listens.
+#[derive(Debug, Clone)]
+pub(crate) struct ServeOptions {
+    /// Address to bind to; only this machine can connect by default.
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    /// Whether `host` may be an address other machines can reach.
+    pub(crate) allow_remote: bool,
+}
+impl Default for ServeOptions {
+    fn default() -> Self {
+        ServeOptions {
+            host: "127.0.0.1".to_owned(),
+            port: 7878,
+            allow_remote: false,
+        }
+    }
+}
+
+/// Largest request body accepted, far more than any task needs.
+const MAX_BODY: usize = 1 << 20;
+/// Longest request line or header line accepted.
+const MAX_LINE: u64 = 8 << 10;
+/// Most bytes of headers accepted in all.
+const MAX_HEADERS: u64 = 64 << 10;
+
+/// Answers HTTP requests on the tasks of `file_path` with JSON, one request
+/// at a time. Runs until killed.
+///
+/// Every request reads the file afresh under its lock and saves its changes
+/// before the lock is released, so the server and the CLI can change the
+/// file side by side. Requests made while another command holds the lock
+/// are answered with 503 rather than kept waiting.
+pub(crate) fn run(file_path: &Path, options: &ServeOptions, session: &mut Session) -> Result<()> {
+    if !options.allow_remote && !is_loopback(&options.host, options.port)? {
+        return Err(Error::Input(format!(
+            "{} can be reached from other machines, which could then change the tasks; \
+             pass --allow-remote to serve on it anyway",
+            options.host
+        )));
+    }
+    let listener = TcpListener::bind((options.host.as_str(), options.port)).map_err(|e| {
+        Error::Input(format!(
+            "Can't listen on {}:{}: {e}",
+            options.host, options.port
+        ))
+    })?;
+    let address = listener.local_addr()?;
+    // The names this server goes by, unknown when it listens on every
+    // interface.
+    let hosts = if address.ip().is_unspecified() {
+        Vec::new()
+    } else {
+        let mut hosts = vec![
+            address.to_string(),
+            format!("{}:{}", options.host, address.port()),
+        ];
+        if address.ip().is_loopback() {
+            hosts.push(format!("localhost:{}", address.port()));
+        }
+        hosts
+    };
+    if output::is_text() {
+        println!(
+            "{}",
+            format!(
+                "Serving {} on http://{address}, press Ctrl-C to stop.",
+                file_path.display()
+            )
+            .truecolor(128, 128, 128)
+        );
+    } else {
+        println!("{}", json!({ "listening": address.to_string() }));
+    }
+
+    // A request that had to wait for the lock could hold up the next ones
+    // for as long as a prompt in another command, so it fails instead.
+    session.set_wait(false);
+    // Nobody is at the terminal to answer for overdue tasks.
+    if session.overdue_policy() == OverduePolicy::Prompt {
+        session.set_overdue_policy(OverduePolicy::None);
+    }
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+        let (line, (status, body)) = match Request::read(&mut stream) {
+            Ok(request) => (
+                format!("{} {}", request.method, request.path),
+                match check(&request, &hosts) {
+                    Ok(()) => respond(&request, file_path, session),
+                    Err(refusal) => refusal,
+                },
+            ),
+            Err((status, e)) => ("-".to_owned(), (status, error_json(&e))),
+        };
+        // Drops the tasks and their lock until the next request.
+        session.reload();
+        eprintln!("{line} {status}");
+        let _ = write_response(&mut stream, status, &body);
+    }
+    Ok(())
+}
+
+/// Whether every address `host` stands for is on this machine only.
+fn is_loopback(host: &str, port: u16) -> Result<bool> {
+    let addresses: Vec<_> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| Error::Input(format!("Can't resolve {host}: {e}")))?
+        .collect();
+    Ok(!addresses.is_empty() && addresses.iter().all(|a| a.ip().is_loopback()))
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// Header values by lowercase name.
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+impl Request {
+    /// Reads the next request from `stream`, or the status and error to
+    /// answer with if it is malformed or too large.
+    fn read(stream: &mut impl Read) -> std::result::Result<Self, (u16, Error)> {
+        let bad = |message: &str| (400, Error::Parse(message.to_owned()));
+        let mut reader = BufReader::new(stream);
+        let line = read_line(&mut reader, MAX_LINE, 400)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(bad("Invalid request line"));
+        };
+
+        let mut headers = HashMap::new();
+        let mut total = 0;
+        loop {
+            let header = read_line(&mut reader, MAX_LINE, 431)?;
+            total += header.len() as u64;
+            if total > MAX_HEADERS {
+                return Err((431, Error::Input("Request headers too large".into())));
+            }
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+            }
+        }
+        let length = match headers.get("content-length") {
+            Some(length) => length.parse().map_err(|_| bad("Invalid Content-Length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err((413, Error::Input("Request body too large".into())));
+        }
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| (400, Error::Io(e)))?;
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Ok(Request {
+            method: method.to_uppercase(),
+            path: path.to_owned(),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (decode(key), decode(value))
+                })
+                .collect(),
+            headers,
+            body,
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// The body as a JSON object.
+    fn object(&self) -> Result<serde_json::Map<String, Value>> {
+        match serde_json::from_slice(&self.body) {
+            Ok(Value::Object(object)) => Ok(object),
+            _ => Err(Error::Input("Expected a JSON object".into())),
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.query
+            .get(name)
+            .is_some_and(|v| matches!(v.as_str(), "" | "1" | "true" | "yes"))
+    }
+}
+
+/// Reads a line of at most `limit` bytes, failing with `status` if it is
+/// longer. An empty string means the connection ended.
+fn read_line(
+    reader: &mut impl BufRead,
+    limit: u64,
+    status: u16,
+) -> std::result::Result<String, (u16, Error)> {
+    let mut line = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_until(b'\n', &mut line)
+        .map_err(|e| (400, Error::Io(e)))?;
+    if line.len() as u64 > limit {
+        return Err((status, Error::Input("Request line too long".into())));
+    }
+    String::from_utf8(line).map_err(|_| (400, Error::Parse("Request isn't UTF-8".into())))
+}
+
+/// Decodes `%XX` escapes and `+` in a query string part.
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match u8::from_str_radix(s.get(i + 1..i + 3).unwrap_or(""), 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Refuses what a web page open in a browser on this machine could send:
+/// requests for another host name, as DNS rebinding makes them, requests
+/// carrying an `Origin`, and bodies that aren't JSON, which a page can post
+/// without asking first. Any host goes when `hosts` is empty.
+fn check(request: &Request, hosts: &[String]) -> std::result::Result<(), (u16, Value)> {
+    let refuse = |status, kind, message: &str| {
+        Err((
+            status,
+            json!({ "error": { "kind": kind, "message": message } }),
+        ))
+    };
+    let host = request.header("host").unwrap_or("");
+    if !hosts.is_empty() && !hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+        return refuse(403, "forbidden", "Unknown Host");
+    }
+    if request.header("origin").is_some() {
+        return refuse(403, "forbidden", "Requests from web pages aren't allowed");
+    }
+    let json = request
+        .header("content-type")
+        .and_then(|t| t.split(';').next())
+        .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/json"));
+    if matches!(request.method.as_str(), "POST" | "PATCH") && !json {
+        return refuse(
+            415,
+            "unsupported_media_type",
+            "Content-Type has to be application/json",
+        );
+    }
+    Ok(())
+}
+
+/// The status and body answering `request`.
+fn respond(request: &Request, file_path: &Path, session: &mut Session) -> (u16, Value) {
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["tasks"]) => list(request, file_path, session),
+        ("POST", ["tasks"]) => create(request, file_path, session),
+        ("GET", ["tasks", id]) => show(id, file_path, session),
+        ("PATCH", ["tasks", id]) => update(request, id, file_path, session),
+        ("DELETE", ["tasks", id]) => remove(id, file_path, session),
+        ("POST", ["tasks", id, "progress"]) => progress(request, id, file_path, session),
+        (_, ["tasks"] | ["tasks", _] | ["tasks", _, "progress"]) => {
+            return (405, json!({ "error": { "kind": "method_not_allowed" } }))
+        }
+        _ => return (404, json!({ "error": { "kind": "not_found" } })),
+    };
+    result.unwrap_or_else(|e| {
+        let status = match e {
+            Error::TaskNotFound => 404,
+            Error::AmbiguousMatch(_) => 409,
+            Error::Locked(_) => 503,
+            Error::Parse(_) | Error::Input(_) | Error::InvalidArguments => 400,
+            _ => 500,
+        };
+        (status, error_json(&e))
+    })
+}
+
+/// `GET /tasks`: open tasks sorted by deadline, like `list`. `?all` takes in
+/// completed ones, `?where=<filter>` and `?sort=<keys>` work as for `list`.
+fn list(request: &Request, file_path: &Path, session: &mut Session) -> Result<(u16, Value)> {
+    let filter: Option<Filter> = request.query.get("where").map(|f| f.parse()).transpose()?;
+    let keys = match request.query.get("sort") {
+        Some(keys) => sort::parse_keys(keys)?,
+        None => vec![SortKey::Deadline],
+    };
+    let filter = match filter {
+        Some(filter) if request.flag("all") => Some(filter),
+        Some(filter) => Some(filter.and("open".parse()?)),
+        None if request.flag("all") => None,
+        None => Some("open".parse()?),
+    };
+    let mut tasks = match filter {
+        Some(filter) => session.query(file_path, &filter, false)?,
+        None => session.tasks_or_empty(file_path)?.clone(),
+    };
+    sort_tasks(&mut tasks, &keys, request.flag("reverse"));
+    Ok((200, Value::Array(tasks.iter().map(task_json).collect())))
+}
+
+/// `GET /tasks/<id>`: the task with its pace and progress log, like `show`.
+fn show(id: &str, file_path: &Path, session: &mut Session) -> Result<(u16, Value)> {
+    let tasks = session.tasks_or_empty(file_path)?;
+    let index = Target::parse(id)?.find(tasks)?;
+    Ok((200, task_details_json(&tasks[index])))
+}
+
+/// `POST /tasks`: adds a task from `name`, `due` and `estimate`, and
+/// optionally `description` and `priority`, given as for `set`.
+fn create(request: &Request, file_path: &Path, session: &mut Session) -> Result<(u16, Value)> {
+    let object = request.object()?;
+    for required in ["name", "due", "estimate"] {
+        if !object.contains_key(required) {
+            return Err(Error::Input(format!("Missing \"{required}\"")));
+        }
+    }
+    let fields = fields(&object)?;
+
+    let now = Local::now().timestamp();
+    let mut task = Task::with_id(next_id(session.tasks_or_empty(file_path)?));
+    task.created = now;
+    // Offsets such as `+2d` count from now.
+    task.deadline = now;
+    for field in &fields {
+        field.apply(&mut task)?;
+    }
+    session.append(file_path, task.clone())?;
+    Ok((201, json!({ "added": task_json(&task) })))
+}
+
+/// `PATCH /tasks/<id>`: sets the fields given, as for `set`.
+fn update(
+    request: &Request,
+    id: &str,
+    file_path: &Path,
+    session: &mut Session,
+) -> Result<(u16, Value)> {
+    let fields = fields(&request.object()?)?;
+    change_task(id, file_path, session, |task| {
+        for field in &fields {
+            field.apply(task)?;
+        }
+        Ok(json!({ "updated": task_json(task) }))
+    })
+}
+
+/// `POST /tasks/<id>/progress`: logs `amount`, as for `progress`.
+fn progress(
+    request: &Request,
+    id: &str,
+    file_path: &Path,
+    session: &mut Session,
+) -> Result<(u16, Value)> {
+    let object = request.object()?;
+    let amount = object
+        .get("amount")
+        .and_then(Value::as_str)
+        .ok_or(Error::Input("Missing \"amount\"".into()))?;
+    change_task(id, file_path, session, |task| {
+        task.ensure_open()?;
+        let progress_made = parse_progress(amount, task)?;
+        task.log_progress(progress_made);
+        Ok(json!({
+            "task": task_json(task),
+            "progress_made": progress_made,
+            "completed": task.is_completed(),
+        }))
+    })
+}
+
+/// `DELETE /tasks/<id>`.
+fn remove(id: &str, file_path: &Path, session: &mut Session) -> Result<(u16, Value)> {
+    let index = find(id, file_path, session)?;
+    let removed = session.tasks_or_empty(file_path)?.remove(index);
+    session.save(file_path)?;
+    Ok((200, json!({ "removed": task_json(&removed) })))
+}
+
+/// The fields of a JSON object, as `set` takes them.
+fn fields(object: &serde_json::Map<String, Value>) -> Result<Vec<Field>> {
+    object
+        .iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => Field::parse(&format!("{key}={value}")),
+            _ => Err(Error::Input(format!("\"{key}\" has to be a string"))),
+        })
+        .collect()
+}
+
+/// Index of the task addressed by `id`, an id, `@` and the start of a UUID,
+/// or a whole UUID.
+fn find(id: &str, file_path: &Path, session: &mut Session) -> Result<usize> {
+    Target::parse(id)?.find(session.tasks_or_empty(file_path)?)
+}
+
+/// Applies `change` to the task addressed by `id` and saves it, answering
+/// with what `change` returns.
+fn change_task(
+    id: &str,
+    file_path: &Path,
+    session: &mut Session,
+    change: impl FnOnce(&mut Task) -> Result<Value>,
+) -> Result<(u16, Value)> {
+    let index = find(id, file_path, session)?;
+    let body = change(&mut session.tasks_or_empty(file_path)?[index])?;
+    session.save(file_path)?;
+    Ok((200, body))
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(raw: &[u8]) -> std::result::Result<Request, (u16, Error)> {
+        Request::read(&mut &raw[..])
+    }
+
+    /// The status `read` refuses `raw` with.
+    fn refusal(raw: &[u8]) -> u16 {
+        match read(raw) {
+            Ok(_) => panic!("accepted {}", String::from_utf8_lossy(raw)),
+            Err((status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn reads_the_request_line_query_and_body() {
+        let request = read(
+            b"post /tasks?sort=name,deadline&all&name=pay%20rent+now HTTP/1.1\r\n\
+              Host: localhost\r\n\
+              content-length: 12\r\n\
+              \r\n\
+              {\"name\":\"a\"}",
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/tasks");
+        assert_eq!(request.query["sort"], "name,deadline");
+        assert_eq!(request.query["name"], "pay rent now");
+        assert!(request.flag("all"));
+        assert!(!request.flag("sort"));
+        assert_eq!(request.object().unwrap()["name"], "a");
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        assert_eq!(refusal(b"\r\n\r\n"), 400);
+        assert_eq!(
+            refusal(b"GET /tasks HTTP/1.1\r\nContent-Length: lots\r\n\r\n"),
+            400
+        );
+        // A body shorter than announced.
+        assert_eq!(
+            refusal(b"POST /tasks HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}"),
+            400
+        );
+        assert_eq!(refusal(b"GET /t\xffsks HTTP/1.1\r\n\r\n"), 400);
+    }
+
+    #[test]
+    fn rejects_requests_over_the_size_limits() {
+        let path = "a".repeat(MAX_LINE as usize);
+        assert_eq!(
+            refusal(format!("GET /{path} HTTP/1.1\r\n\r\n").as_bytes()),
+            400
+        );
+
+        let header = format!("X-Long: {}\r\n", "a".repeat(MAX_LINE as usize));
+        assert_eq!(
+            refusal(format!("GET /tasks HTTP/1.1\r\n{header}\r\n").as_bytes()),
+            431
+        );
+
+        // Each header fits, all of them together don't.
+        let header = format!("X-Some: {}\r\n", "a".repeat(1000));
+        let headers = header.repeat((MAX_HEADERS / 1000) as usize + 1);
+        assert_eq!(
+            refusal(format!("GET /tasks HTTP/1.1\r\n{headers}\r\n").as_bytes()),
+            431
+        );
+        let headers = header.repeat(10);
+        assert!(read(format!("GET /tasks HTTP/1.1\r\n{headers}\r\n").as_bytes()).is_ok());
+
+        let too_large = format!(
+            "POST /tasks HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(refusal(too_large.as_bytes()), 413);
+    }
+
+    #[test]
+    fn bodies_must_be_json_objects() {
+        for body in ["[1, 2]", "not json", ""] {
+            let raw = format!(
+                "POST /tasks HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            assert!(read(raw.as_bytes()).unwrap().object().is_err(), "{body}");
+        }
+    }
+
+    #[test]
+    fn decodes_query_escapes() {
+        assert_eq!(decode("a+b%2Cc"), "a b,c");
+        assert_eq!(decode("%C3%A9t%C3%A9"), "été");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+    }
+
+    fn status(raw: &str, hosts: &[&str]) -> u16 {
+        let hosts: Vec<String> = hosts.iter().map(|h| h.to_string()).collect();
+        match check(&read(raw.as_bytes()).unwrap(), &hosts) {
+            Ok(()) => 200,
+            Err((status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn refuses_requests_for_other_hosts() {
+        let hosts = ["127.0.0.1:7878", "localhost:7878"];
+        assert_eq!(
+            status(
+                "GET /tasks HTTP/1.1\r\nHost: localhost:7878\r\n\r\n",
+                &hosts
+            ),
+            200
+        );
+        assert_eq!(
+            status(
+                "GET /tasks HTTP/1.1\r\nHOST: LocalHost:7878\r\n\r\n",
+                &hosts
+            ),
+            200
+        );
+        assert_eq!(
+            status(
+                "GET /tasks HTTP/1.1\r\nHost: evil.example:7878\r\n\r\n",
+                &hosts
+            ),
+            403
+        );
+        assert_eq!(status("GET /tasks HTTP/1.1\r\n\r\n", &hosts), 403);
+        // Listening on every interface, the name isn't known.
+        assert_eq!(
+            status("GET /tasks HTTP/1.1\r\nHost: anything\r\n\r\n", &[]),
+            200
+        );
+    }
+
+    #[test]
+    fn refuses_requests_from_web_pages() {
+        let raw =
+            "GET /tasks HTTP/1.1\r\nHost: localhost:7878\r\nOrigin: https://evil.example\r\n\r\n";
+        assert_eq!(status(raw, &["localhost:7878"]), 403);
+    }
+
+    #[test]
+    fn bodies_have_to_be_declared_json() {
+        let post = |content_type: &str| {
+            let raw = format!(
+                "POST /tasks HTTP/1.1\r\nHost: h\r\n{content_type}Content-Length: 2\r\n\r\n{{}}"
+            );
+            status(&raw, &["h"])
+        };
+        assert_eq!(post("Content-Type: application/json\r\n"), 200);
+        assert_eq!(
+            post("Content-Type: Application/JSON; charset=utf-8\r\n"),
+            200
+        );
+        assert_eq!(post("Content-Type: text/plain\r\n"), 415);
+        assert_eq!(
+            post("Content-Type: application/x-www-form-urlencoded\r\n"),
+            415
+        );
+        assert_eq!(post(""), 415);
+        assert_eq!(
+            status("DELETE /tasks/1 HTTP/1.1\r\nHost: h\r\n\r\n", &["h"]),
+            200
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use todo_core::{storage, store::FileLock, task::Task, Backend, Filter, Result, TaskStore};
+
+use crate::{
+    overdue::{self, OverduePolicy},
+    project::Projects,
+};
+
+/// The task files a run of commands works on.
+///
+/// Files are read the first time a command asks for them and kept in memory
+/// afterwards, so the shell only parses its file once. Every change is
+/// written straight back with [`Session::save`].
+///
+/// The overdue policy is applied to each file as it is read, and the
+/// storage backend decides the format of new files.
+///
+/// Each file is locked from when it is read until [`Session::reload`] or the
+/// end of the session, so other commands wait instead of overwriting
+/// changes. Commands call [`Session::reload`] before prompting, and
+/// long-running ones after each change, so the lock is never held while
+/// waiting on someone.
+pub(crate) struct Session {
+    default_file: PathBuf,
+    loaded: HashMap<PathBuf, TaskStore>,
+    /// Whether to wait for a file another command has locked, rather than
+    /// fail.
+    wait: bool,
+    locks: HashMap<PathBuf, FileLock>,
+    overdue_policy: OverduePolicy,
+    storage: Backend,
+    data_dir: Option<PathBuf>,
+}
+impl Session {
+    pub(crate) fn new(default_file: &Path) -> Self {
+        Session {
+            default_file: default_file.to_owned(),
+            loaded: HashMap::new(),
+            wait: true,
+            locks: HashMap::new(),
+            overdue_policy: OverduePolicy::None,
+            storage: Backend::default(),
+            data_dir: None,
+        }
+    }
+
+    /// Sets the file used by commands not given one.
+    pub(crate) fn set_default_file(&mut self, default_file: &Path) {
+        self.default_file = default_file.to_owned();
+    }
+
+    /// Makes reading a file another command has locked fail with
+    /// [`todo_core::Error::Locked`] instead of waiting, when `wait` is unset.
+    pub(crate) fn set_wait(&mut self, wait: bool) {
+        self.wait = wait;
+    }
+
+    pub(crate) fn set_overdue_policy(&mut self, policy: OverduePolicy) {
+        self.overdue_policy = policy;
+    }
+
+    pub(crate) fn overdue_policy(&self) -> OverduePolicy {
+        self.overdue_policy
+    }
+
+    /// Sets the backend of new task files without a known extension.
+    pub(crate) fn set_storage(&mut self, storage: Backend) {
+        self.storage = storage;
+    }
+
+    pub(crate) fn storage(&self) -> Backend {
+        self.storage
+    }
+
+    /// Sets the directory projects are kept in, instead of the default one.
+    pub(crate) fn set_data_dir(&mut self, data_dir: Option<PathBuf>) {
+        self.data_dir = data_dir;
+    }
+
+    pub(crate) fn data_dir(&self) -> Option<&Path> {
+        self.data_dir.as_deref()
+    }
+
+    pub(crate) fn projects(&self) -> Result<Projects> {
+        Projects::new(self.data_dir.as_deref())
+    }
+
+    /// The file given as `arg`, or the session's default file.
+    pub(crate) fn file(&self, arg: Option<&String>) -> PathBuf {
+        arg.map_or_else(|| self.default_file.clone(), PathBuf::from)
+    }
+
+    /// Tasks of `file_path`, failing if the file doesn't exist.
+    pub(crate) fn tasks(&mut self, file_path: &Path) -> Result<&mut Vec<Task>> {
+        if !self.loaded.contains_key(file_path) {
+            self.read(file_path, TaskStore::open)?;
+        }
+        self.tasks_or_empty(file_path)
+    }
+
+    /// Tasks of `file_path`, or an empty list if the file doesn't exist yet.
+    pub(crate) fn tasks_or_empty(&mut self, file_path: &Path) -> Result<&mut Vec<Task>> {
+        Ok(self.store(file_path)?.tasks_mut())
+    }
+
+    /// Tasks of `file_path` matching `filter`, failing if the file doesn't
+    /// exist when `required`.
+    ///
+    /// Unless the file is already in memory or has to be read whole, for the
+    /// overdue policy or to give old tasks their UUIDs, only the matching
+    /// tasks are read, through the storage backend's indexes if it has any.
+    pub(crate) fn query(
+        &mut self,
+        file_path: &Path,
+        filter: &Filter,
+        required: bool,
+    ) -> Result<Vec<Task>> {
+        if !self.loaded.contains_key(file_path)
+            && self.overdue_policy == OverduePolicy::None
+            && (file_path.exists() || !required)
+        {
+            let tasks = {
+                let _lock = lock_file(file_path, self.storage, self.wait)?;
+                storage::open(file_path, self.storage)?.query(filter)?
+            };
+            if tasks.iter().all(|t| !t.uuid().is_nil()) {
+                return Ok(tasks);
+            }
+        }
+
+        let tasks = if required {
+            self.tasks(file_path)?
+        } else {
+            self.tasks_or_empty(file_path)?
+        };
+        Ok(tasks
+            .iter()
+            .filter(|t| filter.matches(t))
+            .cloned()
+            .collect())
+    }
+
+    /// The store of `file_path`, read on first use.
+    fn store(&mut self, file_path: &Path) -> Result<&mut TaskStore> {
+        if !self.loaded.contains_key(file_path) {
+            self.read(file_path, TaskStore::load)?;
+        }
+        Ok(self.loaded.get_mut(file_path).unwrap())
+    }
+
+    /// Reads `file_path` with `open` under its lock and keeps it in memory,
+    /// after applying the overdue policy to it and saving what that or
+    /// giving old tasks their UUIDs changed.
+    fn read(
+        &mut self,
+        file_path: &Path,
+        open: fn(&Path, Backend) -> Result<TaskStore>,
+    ) -> Result<()> {
+        self.lock(file_path)?;
+        let mut store = open(file_path, self.storage)?;
+        if store.is_changed() {
+            store.save()?;
+        }
+
+        let changed = if self.overdue_policy == OverduePolicy::Prompt
+            && store.tasks().iter().any(overdue::is_pending)
+        {
+            // Ask with the file unlocked, then answer for the tasks as they
+            // are by then.
+            self.locks.remove(file_path);
+            let answers = overdue::ask(store.tasks())?;
+            self.lock(file_path)?;
+            store.reload()?;
+            overdue::answer(store.tasks_mut(), &answers)
+        } else {
+            overdue::apply(store.tasks_mut(), self.overdue_policy)?
+        };
+        if changed {
+            store.save()?;
+        }
+        self.loaded.insert(store.path().to_owned(), store);
+        Ok(())
+    }
+
+    /// Takes the lock on `file_path` before it is read.
+    fn lock(&mut self, file_path: &Path) -> Result<()> {
+        if !self.locks.contains_key(file_path) {
+            if let Some(lock) = lock_file(file_path, self.storage, self.wait)? {
+                self.locks.insert(file_path.to_owned(), lock);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the loaded tasks of `file_path` back to disk.
+    pub(crate) fn save(&mut self, file_path: &Path) -> Result<()> {
+        match self.loaded.get_mut(file_path) {
+            Some(store) => store.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds `task` to `file_path`, appending it to the file rather than
+    /// rewriting it.
+    pub(crate) fn append(&mut self, file_path: &Path, task: Task) -> Result<()> {
+        self.store(file_path)?.add(task)
+    }
+
+    /// Drops the in-memory copy of every file so they are read again, and
+    /// releases their locks.
+    pub(crate) fn reload(&mut self) {
+        self.loaded.clear();
+        self.locks.clear();
+    }
+}
+
+/// Locks the task file at `path`, first asking for anything reading it will
+/// need so that no prompt waits with the lock held. Fails with
+/// [`todo_core::Error::Locked`] if another command holds the lock, unless
+/// `wait` is set.
+pub(crate) fn lock_file(path: &Path, default: Backend, wait: bool) -> Result<Option<FileLock>> {
+    if path.exists() && Backend::for_path(path, default)? == Backend::Encrypted {
+        storage::request_passphrase(path)?;
+    }
+    if wait {
+        FileLock::acquire(path)
+    } else {
+        FileLock::try_acquire(path)
+    }
+}
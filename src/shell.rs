@@ -0,0 +1,234 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use colored::*;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+
+use crate::{output, overdue::OverduePolicy, run_command, session::Session, COMMANDS, ID_COMMANDS};
+use todo_core::{Backend, Error, Result};
+
+/// Commands only available inside the shell.
+const SHELL_COMMANDS: [&str; 3] = ["reload", "exit", "quit"];
+
+/// Completes command names and, for commands taking an id, the ids of the
+/// tasks currently loaded.
+struct ShellHelper {
+    tasks: Vec<(i64, String)>,
+}
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let candidates = match previous.as_slice() {
+            [] => COMMANDS
+                .iter()
+                .chain(SHELL_COMMANDS.iter())
+                .filter(|c| c.starts_with(word) && **c != "shell")
+                .map(|c| Pair {
+                    display: c.to_string(),
+                    replacement: format!("{c} "),
+                })
+                .collect(),
+            [command] if ID_COMMANDS.contains(command) => self
+                .tasks
+                .iter()
+                .filter(|(id, _)| id.to_string().starts_with(word))
+                .map(|(id, name)| Pair {
+                    display: format!("{id}  {name}"),
+                    replacement: format!("{id} "),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok((start, candidates))
+    }
+}
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+impl Highlighter for ShellHelper {}
+impl Validator for ShellHelper {}
+impl Helper for ShellHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".todo_cli_history"))
+}
+
+/// Reads commands from an interactive prompt and runs them against
+/// `file_path`. The file is read again for every command and saved after
+/// every change, and isn't locked while waiting at the prompt.
+/// Projects are kept in `data_dir`, if given, as set in the configuration.
+pub(crate) fn run(
+    file_path: &Path,
+    overdue_policy: OverduePolicy,
+    storage: Backend,
+    data_dir: Option<&Path>,
+) -> Result<()> {
+    let mut session = Session::new(file_path);
+    session.set_overdue_policy(overdue_policy);
+    session.set_storage(storage);
+    session.set_data_dir(data_dir.map(Path::to_owned));
+    let mut editor: Editor<ShellHelper, DefaultHistory> =
+        Editor::new().map_err(|e| Error::Input(e.to_string()))?;
+    editor.set_helper(Some(ShellHelper { tasks: Vec::new() }));
+
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    println!(
+        "{} {}",
+        format!("todo-cli shell on {}.", file_path.display()).bold(),
+        "Type help for commands, exit to leave.".truecolor(128, 128, 128)
+    );
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.tasks = session
+                .tasks_or_empty(file_path)
+                .map(|tasks| {
+                    tasks
+                        .iter()
+                        .filter(|t| t.is_open())
+                        .map(|t| (t.id(), t.name.clone()))
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        session.reload();
+
+        let line = match editor.readline(&format!("{} ", "todo>".cyan().bold())) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(Error::Input(e.to_string())),
+        };
+
+        let words = match split_words(&line) {
+            Ok(words) if words.is_empty() => continue,
+            Ok(words) => words,
+            Err(e) => {
+                print_error(&e);
+                continue;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        match words[0].as_str() {
+            "exit" | "quit" => break,
+            "reload" => {}
+            "shell" => print_error(&Error::InvalidCommand),
+            _ => {
+                let args: Vec<String> = ["todo-cli".to_owned()].into_iter().chain(words).collect();
+                if let Err(e) = run_command(&args, &mut session) {
+                    print_error(&e);
+                }
+            }
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+fn print_error(e: &Error) {
+    if output::is_text() {
+        eprintln!("{}", format!("Error: {e}").red());
+    } else {
+        println!("{}", output::error_json(e));
+    }
+}
+
+/// Splits a command line into words, honouring single and double quotes and
+/// backslash escapes.
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.get_or_insert_with(String::new).push(next);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::Input("Unterminated quote".into()));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> Vec<String> {
+        split_words(line).unwrap()
+    }
+
+    #[test]
+    fn splits_on_runs_of_whitespace() {
+        assert_eq!(split("  progress 2\t30m "), ["progress", "2", "30m"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_words_together() {
+        assert_eq!(
+            split(r#"search "write report" 'pay rent'"#),
+            ["search", "write report", "pay rent"]
+        );
+        assert_eq!(split(r#"a"b c"d"#), ["ab cd"]);
+        assert_eq!(split(r#"set name="" ''"#), ["set", "name=", ""]);
+    }
+
+    #[test]
+    fn backslashes_escape_outside_single_quotes() {
+        assert_eq!(split(r"a\ b \'c"), ["a b", "'c"]);
+        assert_eq!(split(r#""say \"hi\"""#), [r#"say "hi""#]);
+        assert_eq!(split(r"'C:\dir'"), [r"C:\dir"]);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_an_error() {
+        assert!(split_words("search \"write report").is_err());
+        assert!(split_words("search 'write").is_err());
+    }
+}
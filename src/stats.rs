@@ -0,0 +1,397 @@
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone};
+use colored::*;
+use serde_json::{json, Value};
+
+use todo_core::{
+    format::{card, format_duration, progress_bar, strip_colors, urgency_colour},
+    task::Task,
+};
+
+/// Weeks of completions shown in the throughput breakdown, this one included.
+const WEEKS: usize = 8;
+
+/// Bands of time left open tasks are grouped by, with the lower bound of
+/// each. They follow the colours used for the time left.
+const BANDS: [(&str, i64); 6] = [
+    ("over a week", 7 * 24 * 60 * 60),
+    ("2 to 7 days", 2 * 24 * 60 * 60),
+    ("1 to 2 days", 24 * 60 * 60),
+    ("5 to 24 hours", 5 * 60 * 60),
+    ("under 5 hours", 0),
+    ("overdue", i64::MIN),
+];
+
+pub(crate) struct Band {
+    label: &'static str,
+    lower: i64,
+    tasks: usize,
+    remaining: i64,
+}
+
+pub(crate) struct Week {
+    /// Monday the week starts on.
+    start: NaiveDate,
+    tasks: usize,
+    /// Estimated time of the tasks completed that week.
+    estimate: i64,
+}
+impl Week {
+    fn label(&self) -> String {
+        let week = self.start.iso_week();
+        format!("{}-W{:02}", week.year(), week.week())
+    }
+}
+
+/// Figures about the open tasks of a file and the ones completed so far.
+pub(crate) struct Stats {
+    open: usize,
+    estimate: i64,
+    remaining: i64,
+    /// Time until the latest deadline of an open task.
+    available: i64,
+    overdue: usize,
+    average_completion: Option<f32>,
+    completed: usize,
+    /// Logged and estimated time of the completed tasks.
+    actual: i64,
+    estimated: i64,
+    /// Mean time from creation to completion, for tasks that recorded both.
+    average_duration: Option<i64>,
+    weeks: Vec<Week>,
+    bands: Vec<Band>,
+}
+impl Stats {
+    pub(crate) fn new(tasks: &[Task]) -> Self {
+        let now = Local::now();
+        let completed: Vec<&Task> = tasks.iter().filter(|t| t.is_completed()).collect();
+        let open: Vec<&Task> = tasks.iter().filter(|t| t.is_open()).collect();
+
+        let average_completion = (!open.is_empty()).then(|| {
+            open.iter()
+                .map(|t| t.get_completion().min(1.0))
+                .sum::<f32>()
+                / open.len() as f32
+        });
+
+        let durations: Vec<i64> = completed
+            .iter()
+            .filter(|t| t.created != 0)
+            .map(|t| t.completed - t.created)
+            .collect();
+        let average_duration =
+            (!durations.is_empty()).then(|| durations.iter().sum::<i64>() / durations.len() as i64);
+
+        let this_week = now.date_naive().week(chrono::Weekday::Mon).first_day();
+        let mut weeks: Vec<Week> = (0..WEEKS)
+            .rev()
+            .map(|i| Week {
+                start: this_week - Days::new(7 * i as u64),
+                tasks: 0,
+                estimate: 0,
+            })
+            .collect();
+        for task in &completed {
+            let Some(day) = Local.timestamp_opt(task.completed, 0).single() else {
+                continue;
+            };
+            let start = day.date_naive().week(chrono::Weekday::Mon).first_day();
+            if let Some(week) = weeks.iter_mut().find(|w| w.start == start) {
+                week.tasks += 1;
+                week.estimate += task.estimated_time;
+            }
+        }
+
+        let mut bands: Vec<Band> = BANDS
+            .iter()
+            .map(|&(label, lower)| Band {
+                label,
+                lower,
+                tasks: 0,
+                remaining: 0,
+            })
+            .collect();
+        for task in &open {
+            let tl = task.get_time_left();
+            if let Some(band) = bands.iter_mut().find(|b| tl >= b.lower) {
+                band.tasks += 1;
+                band.remaining += task.get_remaining();
+            }
+        }
+
+        Stats {
+            open: open.len(),
+            estimate: open.iter().map(|t| t.estimated_time).sum(),
+            remaining: open.iter().map(|t| t.get_remaining()).sum(),
+            available: open
+                .iter()
+                .map(|t| t.get_time_left())
+                .max()
+                .unwrap_or(0)
+                .max(0),
+            overdue: open.iter().filter(|t| t.get_time_left() < 0).count(),
+            average_completion,
+            completed: completed.len(),
+            actual: completed.iter().map(|t| t.progress).sum(),
+            estimated: completed.iter().map(|t| t.estimated_time).sum(),
+            average_duration,
+            weeks,
+            bands,
+        }
+    }
+
+    /// Logged time of the completed tasks relative to their estimates.
+    fn accuracy(&self) -> Option<f32> {
+        (self.estimated != 0).then(|| self.actual as f32 / self.estimated as f32)
+    }
+
+    /// Remaining work relative to the time until the last deadline.
+    fn load(&self) -> Option<f32> {
+        (self.available != 0).then(|| self.remaining as f32 / self.available as f32)
+    }
+
+    fn per_week(&self) -> f32 {
+        self.weeks.iter().map(|w| w.tasks).sum::<usize>() as f32 / WEEKS as f32
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let label = |s: &str| s.truecolor(128, 128, 128).bold().to_string();
+        let none = || "n/a".italic().to_string();
+        let duration = |d: i64| strip_colors(&format_duration(d)).trim_end().to_owned();
+
+        let load = match self.load() {
+            Some(load) if load > 1.0 => format!("{:.0}%", load * 100.0).bright_red().to_string(),
+            Some(load) => format!("{:.0}%", load * 100.0).cyan().to_string(),
+            None => none(),
+        };
+        let overdue = match self.overdue {
+            0 => "0".green().to_string(),
+            n => n.to_string().bright_red().to_string(),
+        };
+        let open = card(vec![
+            (
+                label("Open tasks:"),
+                self.open.to_string().cyan().to_string(),
+            ),
+            (label("Estimated:"), format_duration(self.estimate)),
+            (label("Remaining work:"), format_duration(self.remaining)),
+            (
+                label("Until last deadline:"),
+                format_duration(self.available),
+            ),
+            (label("Load:"), load),
+            (label("Overdue:"), overdue),
+            (
+                label("Average completion:"),
+                self.average_completion.map_or_else(none, progress_bar),
+            ),
+        ]);
+
+        let accuracy = match self.accuracy() {
+            Some(accuracy) => format!(
+                "{} / {} ({:.0}%)",
+                duration(self.actual),
+                duration(self.estimated),
+                accuracy * 100.0
+            )
+            .cyan()
+            .to_string(),
+            None => none(),
+        };
+        let completed = card(vec![
+            (
+                label("Completed tasks:"),
+                self.completed.to_string().green().to_string(),
+            ),
+            (label("Actual vs estimate:"), accuracy),
+            (
+                label("Average duration:"),
+                self.average_duration.map_or_else(none, format_duration),
+            ),
+            (
+                label("Per week:"),
+                format!("{:.1}", self.per_week()).cyan().to_string(),
+            ),
+        ]);
+
+        let most = self.weeks.iter().map(|w| w.tasks).max().unwrap_or(0).max(1);
+        let weeks = card(
+            self.weeks
+                .iter()
+                .map(|w| {
+                    (
+                        label(&w.label()),
+                        format!(
+                            "{:<width$} {} {}",
+                            "■".repeat(w.tasks * 10 / most),
+                            w.tasks.to_string().cyan(),
+                            format!("({})", duration(w.estimate)).truecolor(128, 128, 128),
+                            width = 10,
+                        )
+                        .to_string(),
+                    )
+                })
+                .collect(),
+        );
+
+        let bands = card(
+            self.bands
+                .iter()
+                .map(|b| {
+                    (
+                        urgency_colour(b.label, b.lower),
+                        format!(
+                            "{} {}",
+                            b.tasks.to_string().cyan(),
+                            format!("({} remaining)", duration(b.remaining))
+                                .truecolor(128, 128, 128)
+                        ),
+                    )
+                })
+                .collect(),
+        );
+
+        [
+            ("Open", open),
+            ("Completed", completed),
+            ("Completed per week", weeks),
+            ("Open by time left", bands),
+        ]
+        .iter()
+        .map(|(title, card)| format!("{}\n{}", title.bold(), card))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "open": {
+                "tasks": self.open,
+                "estimated": self.estimate,
+                "remaining": self.remaining,
+                "until_last_deadline": self.available,
+                "load": self.load(),
+                "overdue": self.overdue,
+                "average_completion": self.average_completion,
+            },
+            "completed": {
+                "tasks": self.completed,
+                "actual": self.actual,
+                "estimated": self.estimated,
+                "accuracy": self.accuracy(),
+                "average_duration": self.average_duration,
+                "per_week": self.per_week(),
+            },
+            "weeks": self
+                .weeks
+                .iter()
+                .map(|w| json!({
+                    "week": w.label(),
+                    "start": w.start.to_string(),
+                    "tasks": w.tasks,
+                    "estimated": w.estimate,
+                }))
+                .collect::<Vec<_>>(),
+            "time_left": self
+                .bands
+                .iter()
+                .map(|b| json!({
+                    "band": b.label,
+                    "tasks": b.tasks,
+                    "remaining": b.remaining,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    fn open(id: i64, time_left: i64, estimate: i64) -> Task {
+        let mut task = Task::with_id(id);
+        task.deadline = Local::now().timestamp() + time_left;
+        task.estimated_time = estimate;
+        task
+    }
+
+    fn completed(id: i64, ago: i64) -> Task {
+        let mut task = open(id, -ago, HOUR);
+        task.progress = HOUR;
+        task.completed = Local::now().timestamp() - ago;
+        task
+    }
+
+    #[test]
+    fn groups_open_tasks_by_time_left() {
+        let tasks = [
+            open(0, 10 * DAY, 2 * HOUR),
+            open(1, 3 * DAY, HOUR),
+            open(2, 36 * HOUR, HOUR),
+            open(3, 10 * HOUR, HOUR),
+            open(4, HOUR, HOUR),
+            open(5, -HOUR, 3 * HOUR),
+            open(6, 20 * DAY, HOUR),
+            completed(7, 0),
+        ];
+        let stats = Stats::new(&tasks);
+
+        let counts: Vec<_> = stats.bands.iter().map(|b| (b.label, b.tasks)).collect();
+        assert_eq!(
+            counts,
+            [
+                ("over a week", 2),
+                ("2 to 7 days", 1),
+                ("1 to 2 days", 1),
+                ("5 to 24 hours", 1),
+                ("under 5 hours", 1),
+                ("overdue", 1),
+            ]
+        );
+        assert_eq!(stats.bands[0].remaining, 3 * HOUR);
+        assert_eq!(stats.bands[5].remaining, 3 * HOUR);
+        assert_eq!(stats.open, 7);
+        assert_eq!(stats.overdue, 1);
+        assert_eq!(stats.remaining, 10 * HOUR);
+        assert!(stats.available > 19 * DAY);
+    }
+
+    #[test]
+    fn counts_completions_per_week() {
+        // Completed at a time out of range, so in no week.
+        let mut corrupt = completed(5, 0);
+        corrupt.completed = i64::MAX / 2;
+        let tasks = [
+            completed(0, 0),
+            completed(1, 7 * DAY),
+            completed(2, 7 * DAY),
+            completed(3, 100 * DAY),
+            open(4, DAY, HOUR),
+            corrupt,
+        ];
+        let stats = Stats::new(&tasks);
+
+        assert_eq!(stats.weeks.len(), WEEKS);
+        let this_week = Local::now().date_naive().week(chrono::Weekday::Mon);
+        assert_eq!(stats.weeks[WEEKS - 1].start, this_week.first_day());
+        assert_eq!(stats.weeks[WEEKS - 1].tasks, 1);
+        assert_eq!(stats.weeks[WEEKS - 2].tasks, 2);
+        assert_eq!(stats.weeks[WEEKS - 2].estimate, 2 * HOUR);
+        assert_eq!(stats.weeks.iter().map(|w| w.tasks).sum::<usize>(), 3);
+
+        assert_eq!(stats.completed, 5);
+        assert_eq!(stats.accuracy(), Some(1.0));
+    }
+
+    #[test]
+    fn averages_need_tasks_to_average() {
+        let stats = Stats::new(&[]);
+        assert_eq!(stats.average_completion, None);
+        assert_eq!(stats.average_duration, None);
+        assert_eq!(stats.accuracy(), None);
+        assert_eq!(stats.load(), None);
+    }
+}
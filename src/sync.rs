@@ -0,0 +1,362 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use todo_core::{
+    merge::{self, Conflict, Merged, Side},
+    storage, Backend, Error, Result, TaskStore, Uuid,
+};
+
+use crate::{passphrase, session};
+
+/// Where `sync` exchanges the task file.
+#[derive(Debug, Clone)]
+pub(crate) struct SyncOptions {
+    /// Remote name, or the path or URL of another repository.
+    pub(crate) remote: String,
+    /// Branch to sync, the checked out one if `None`.
+    pub(crate) branch: Option<String>,
+    /// Push the result back to the remote.
+    pub(crate) push: bool,
+}
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            remote: "origin".to_owned(),
+            branch: None,
+            push: true,
+        }
+    }
+}
+
+/// How the remote's changes were brought in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pull {
+    /// The remote had nothing new, or doesn't have the branch yet.
+    UpToDate,
+    FastForward,
+    Merge,
+}
+impl Pull {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Pull::UpToDate => "up_to_date",
+            Pull::FastForward => "fast_forward",
+            Pull::Merge => "merge",
+        }
+    }
+}
+
+/// What a sync did.
+#[derive(Debug, Clone)]
+pub(crate) struct Outcome {
+    pub(crate) branch: String,
+    /// Local changes to the task file were committed first.
+    pub(crate) committed: bool,
+    pub(crate) pull: Pull,
+    /// Set when the tasks had to be merged.
+    pub(crate) merged: Option<Merged>,
+    pub(crate) pushed: bool,
+}
+
+/// Commits `file_path` to the git repository it is in, brings in the
+/// remote's version of the branch and pushes the result.
+///
+/// When both sides changed, the task file is merged task by task against
+/// the version they have in common instead of by git, with `resolve`
+/// deciding fields changed on both sides. Other files take git's own merge.
+///
+/// The task file stays locked from the commit until the merged tasks are
+/// committed, except while `resolve` is asked about conflicts: the sync
+/// then starts over with the file locked again, keeping the answers given,
+/// and only asks about conflicts that changed in the meantime.
+pub(crate) fn run(
+    file_path: &Path,
+    options: &SyncOptions,
+    storage: Backend,
+    mut resolve: impl FnMut(&Conflict) -> Result<Side>,
+) -> Result<Outcome> {
+    // A fresh clone may not have the task file yet, only its directory.
+    let directory = match file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_path = directory
+        .canonicalize()
+        .map_err(|e| Error::Sync(format!("Can't find {}: {e}", directory.display())))?
+        .join(file_path.file_name().ok_or(Error::InvalidArguments)?);
+    let directory = file_path.parent().unwrap_or(Path::new("/"));
+    let root = PathBuf::from(
+        git(directory, &["rev-parse", "--show-toplevel"]).map_err(|_| {
+            Error::Sync(format!("{} isn't in a git repository", file_path.display()))
+        })?,
+    );
+    let relative = file_path
+        .strip_prefix(root.canonicalize()?)
+        .map_err(|_| {
+            Error::Sync(format!(
+                "{} is outside of {}",
+                file_path.display(),
+                root.display()
+            ))
+        })?
+        .to_string_lossy()
+        .into_owned();
+
+    let branch = match &options.branch {
+        Some(branch) => branch.clone(),
+        None => git(&root, &["symbolic-ref", "--short", "HEAD"])?,
+    };
+    let mut outcome = Outcome {
+        branch: branch.clone(),
+        committed: false,
+        pull: Pull::UpToDate,
+        merged: None,
+        pushed: false,
+    };
+    let remote = &options.remote;
+
+    let mut answers: Vec<(Conflict, Side)> = Vec::new();
+    let _lock = loop {
+        let lock = session::lock_file(&file_path, storage, true)?;
+        outcome.committed |= commit(&root, &relative, &file_path)?;
+
+        let exists = run_git(
+            &root,
+            &["ls-remote", "--exit-code", "--heads", remote, &branch],
+        )?;
+        if !exists.status.success() {
+            if exists.status.code() != Some(2) {
+                return Err(failure("ls-remote", &exists));
+            }
+            break lock;
+        }
+        git(&root, &["fetch", "--quiet", remote, &branch])?;
+        let theirs = git(&root, &["rev-parse", "FETCH_HEAD"])?;
+
+        let born = run_git(&root, &["rev-parse", "--verify", "--quiet", "HEAD"])?
+            .status
+            .success();
+        if !born {
+            // A repository without commits just takes the remote's.
+            git(&root, &["checkout", "--quiet", "-B", &branch, &theirs])?;
+            outcome.pull = Pull::FastForward;
+            break lock;
+        }
+        if is_ancestor(&root, &theirs, "HEAD")? {
+            // Nothing new on the remote.
+            break lock;
+        }
+        if is_ancestor(&root, "HEAD", &theirs)? {
+            git(&root, &["merge", "--quiet", "--ff-only", &theirs])?;
+            outcome.pull = Pull::FastForward;
+            break lock;
+        }
+
+        // Unrelated histories have no common version: merge against none.
+        let base = run_git(&root, &["merge-base", "HEAD", &theirs])?;
+        let base = Some(String::from_utf8_lossy(&base.stdout).trim().to_owned())
+            .filter(|_| base.status.success());
+        let mut unanswered = Vec::new();
+        let merged = merge::merge(
+            &match &base {
+                Some(base) => version(&root, base, &relative, &file_path, storage)?,
+                None => Vec::new(),
+            },
+            TaskStore::load(&file_path, storage)?.tasks(),
+            &version(&root, &theirs, &relative, &file_path, storage)?,
+            |conflict| match answers.iter().find(|(a, _)| same_conflict(a, conflict)) {
+                Some((_, side)) => Ok(*side),
+                None => {
+                    unanswered.push(conflict.clone());
+                    Ok(Side::Ours)
+                }
+            },
+        )?;
+        if !unanswered.is_empty() {
+            drop(lock);
+            for conflict in unanswered {
+                let side = resolve(&conflict)?;
+                answers.push((conflict, side));
+            }
+            continue;
+        }
+
+        merge_tasks(&root, &relative, &file_path, &theirs, &merged, storage)?;
+        outcome.pull = Pull::Merge;
+        outcome.merged = Some(merged);
+        break lock;
+    };
+
+    if options.push {
+        git(
+            &root,
+            &["push", "--quiet", remote, &format!("HEAD:{branch}")],
+        )?;
+        outcome.pushed = true;
+    }
+    Ok(outcome)
+}
+
+/// Commits the task file at `relative` if it changed, returning whether it
+/// did.
+fn commit(root: &Path, relative: &str, file_path: &Path) -> Result<bool> {
+    let changed = file_path.exists() && {
+        git(root, &["add", "--", relative])?;
+        !run_git(root, &["diff", "--cached", "--quiet", "--", relative])?
+            .status
+            .success()
+    };
+    if changed {
+        git(
+            root,
+            &["commit", "--quiet", "-m", "Update tasks", "--", relative],
+        )?;
+    }
+    Ok(changed)
+}
+
+/// Whether `a` and `b` are the same question: the same field of the same
+/// task, with the same values on both sides.
+fn same_conflict(a: &Conflict, b: &Conflict) -> bool {
+    (a.id, a.field, &a.ours, &a.theirs) == (b.id, b.field, &b.ours, &b.theirs)
+}
+
+/// Merges `theirs` into the checked out branch, with `merged` as the task
+/// file at `relative`.
+fn merge_tasks(
+    root: &Path,
+    relative: &str,
+    file_path: &Path,
+    theirs: &str,
+    merged: &Merged,
+    storage: Backend,
+) -> Result<()> {
+    // Git can't merge the task file itself, so it only has to merge the
+    // rest of the repository; the task file is then put back to our version
+    // and given the merged tasks.
+    let started = run_git(
+        root,
+        &[
+            "merge",
+            "--quiet",
+            "--no-ff",
+            "--no-commit",
+            "--allow-unrelated-histories",
+            theirs,
+        ],
+    )?;
+    let finish = || -> Result<()> {
+        let unmerged = git(root, &["diff", "--name-only", "--diff-filter=U"])?;
+        if let Some(other) = unmerged.lines().find(|path| *path != relative) {
+            return Err(Error::Sync(format!(
+                "{other} conflicts as well; merge it with git and sync again"
+            )));
+        }
+        if !started.status.success() && unmerged.is_empty() {
+            return Err(failure("merge", &started));
+        }
+
+        match show(root, "HEAD", relative)? {
+            Some(bytes) => fs::write(file_path, bytes)?,
+            None if file_path.exists() => fs::remove_file(file_path)?,
+            None => {}
+        }
+        let mut store = TaskStore::load(file_path, storage)?;
+        *store.tasks_mut() = merged.tasks.clone();
+        store.save()?;
+        git(root, &["add", "--", relative])?;
+        git(
+            root,
+            &["commit", "--quiet", "--no-edit", "-m", "Merge tasks"],
+        )?;
+        Ok(())
+    };
+    if let Err(e) = finish() {
+        let _ = run_git(root, &["merge", "--abort"]);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// The tasks of the task file as of `revision`, none if it didn't exist.
+fn version(
+    root: &Path,
+    revision: &str,
+    relative: &str,
+    file_path: &Path,
+    storage: Backend,
+) -> Result<Vec<todo_core::Task>> {
+    let Some(bytes) = show(root, revision, relative)? else {
+        return Ok(Vec::new());
+    };
+    // The copy goes into a new directory only the user can use, so nobody
+    // else can read it or put a file or link where it is written.
+    let directory = env::temp_dir().join(format!("todo-cli-sync-{}", Uuid::new_v4()));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&directory)?;
+
+    let copy = directory.join(file_path.file_name().unwrap_or("tasks".as_ref()));
+    let tasks = write_private(&copy, &bytes).and_then(|()| {
+        // Old versions of an encrypted file share its passphrase.
+        passphrase::alias(&copy, file_path);
+        storage::open(&copy, storage).and_then(|mut s| s.load())
+    });
+    let _ = fs::remove_dir_all(&directory);
+    tasks
+}
+
+/// Writes `bytes` to the new file `path`, readable by the user only.
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(bytes)?;
+    Ok(())
+}
+
+/// The contents of `relative` as of `revision`, `None` if it didn't exist.
+fn show(root: &Path, revision: &str, relative: &str) -> Result<Option<Vec<u8>>> {
+    let output = run_git(root, &["show", &format!("{revision}:{relative}")])?;
+    Ok(Some(output.stdout).filter(|_| output.status.success()))
+}
+
+fn is_ancestor(root: &Path, ancestor: &str, of: &str) -> Result<bool> {
+    let output = run_git(root, &["merge-base", "--is-ancestor", ancestor, of])?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(failure("merge-base", &output)),
+    }
+}
+
+/// Runs git in `directory`, returning its trimmed output and failing if it
+/// does.
+fn git(directory: &Path, args: &[&str]) -> Result<String> {
+    let output = run_git(directory, args)?;
+    if !output.status.success() {
+        return Err(failure(args[0], &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn run_git(directory: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .output()
+        .map_err(|e| Error::Sync(format!("Can't run git: {e}")))
+}
+
+fn failure(command: &str, output: &Output) -> Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Error::Sync(format!("git {command} failed: {}", stderr.trim()))
+}
@@ -0,0 +1,534 @@
+use std::{
+    io::{self, stdout, Write},
+    path::Path,
+    time::Duration,
+};
+
+use chrono::Local;
+use colored::*;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use regex::Regex;
+
+use crate::{next_id, session::lock_file};
+use todo_core::{
+    format::{
+        format_duration, progress_bar_sized, strip_colors, truncate, urgency_colour, visible_len,
+    },
+    parse::{
+        parse_due, parse_duration, parse_input, parse_progress, DUE_PATTERN, DURATION_PATTERN,
+    },
+    sort::{sort_tasks, SortKey},
+    task::Task,
+    Backend, Error, Result, TaskStore, Uuid,
+};
+
+/// Puts the terminal into raw mode on the alternate screen for as long as it
+/// is alive.
+struct TerminalGuard;
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), LeaveAlternateScreen, Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Add,
+    Edit,
+    Progress,
+}
+impl Action {
+    fn fields(&self) -> &'static [&'static str] {
+        match self {
+            Action::Add | Action::Edit => &["Due", "Estimate", "Name", "Description"],
+            Action::Progress => &["Amount (e.g. 2h 30m, 50%)"],
+        }
+    }
+}
+
+/// A sequence of single line inputs at the bottom of the screen.
+struct Prompt {
+    action: Action,
+    values: Vec<String>,
+    input: String,
+}
+
+enum Mode {
+    Normal,
+    Prompt(Prompt),
+    ConfirmRemove,
+}
+
+/// The task file is only locked while it is read and while a change is
+/// saved, never while waiting for keys, so other commands can use it in
+/// between. Changes are made to the tasks as they are in the file then.
+struct App {
+    store: TaskStore,
+    storage: Backend,
+    /// Open tasks, the ones shown.
+    tasks: Vec<Task>,
+    selected: usize,
+    offset: usize,
+    mode: Mode,
+    status: Option<(String, bool)>,
+}
+
+pub(crate) fn run(file_path: &Path, storage: Backend) -> Result<()> {
+    let store = {
+        let _lock = lock_file(file_path, storage, true)?;
+        let mut store = TaskStore::load(file_path, storage)?;
+        if store.is_changed() {
+            store.save()?;
+        }
+        store
+    };
+    let mut app = App {
+        store,
+        storage,
+        tasks: Vec::new(),
+        selected: 0,
+        offset: 0,
+        mode: Mode::Normal,
+        status: None,
+    };
+    app.split(None);
+
+    let _guard = TerminalGuard::enter()?;
+    loop {
+        app.draw()?;
+
+        // Redraw at least once a second so the time left keeps counting down.
+        if !event::poll(Duration::from_secs(1))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl App {
+    /// Reads the file again under its lock, keeping the selected task
+    /// selected.
+    fn reload(&mut self) -> Result<()> {
+        self.change(|_| Ok(()))
+    }
+
+    /// Reads the file again under its lock, applies `f` to its tasks and
+    /// saves them, keeping the selected task selected.
+    fn change<T>(&mut self, f: impl FnOnce(&mut Vec<Task>) -> Result<T>) -> Result<T> {
+        let keep = self.selected().map(Task::uuid);
+        let _lock = lock_file(self.store.path(), self.storage, true)?;
+        self.store.reload()?;
+        let result = f(self.store.tasks_mut());
+        if result.is_ok() || self.store.is_changed() {
+            self.store.save()?;
+        }
+        self.split(keep);
+        result
+    }
+
+    /// Shows the open tasks of the store by deadline, keeping the task with
+    /// UUID `keep` selected.
+    fn split(&mut self, keep: Option<Uuid>) {
+        self.tasks = self
+            .store
+            .tasks()
+            .iter()
+            .filter(|t| t.is_open())
+            .cloned()
+            .collect();
+        sort_tasks(&mut self.tasks, &[SortKey::Deadline], false);
+        if let Some(index) = keep.and_then(|uuid| self.tasks.iter().position(|t| t.uuid() == uuid))
+        {
+            self.selected = index;
+        }
+        self.selected = self.selected.min(self.tasks.len().saturating_sub(1));
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        self.tasks.get(self.selected)
+    }
+
+    /// Handles a key press, returning false when the TUI should exit.
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Ok(false);
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => return self.handle_normal_key(key),
+            Mode::ConfirmRemove => {
+                if let KeyCode::Char('y') | KeyCode::Char('Y') = key.code {
+                    let uuid = self.selected().ok_or(Error::TaskNotFound)?.uuid();
+                    let result = self.change(|tasks| {
+                        let index = position(tasks, uuid)?;
+                        Ok(tasks.remove(index).id())
+                    });
+                    self.status = Some(match result {
+                        Ok(id) => (format!("Removed task {id}"), false),
+                        Err(e) => (e.to_string(), true),
+                    });
+                }
+            }
+            Mode::Prompt(mut prompt) => match key.code {
+                KeyCode::Esc => self.status = None,
+                KeyCode::Enter => {
+                    if let Err(e) = self.submit(&mut prompt) {
+                        self.status = Some((e.to_string(), true));
+                    }
+                    if prompt.values.len() < prompt.action.fields().len() {
+                        self.mode = Mode::Prompt(prompt);
+                    }
+                }
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                    self.mode = Mode::Prompt(prompt);
+                }
+                KeyCode::Char(c) => {
+                    prompt.input.push(c);
+                    self.mode = Mode::Prompt(prompt);
+                }
+                _ => self.mode = Mode::Prompt(prompt),
+            },
+        }
+        Ok(true)
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> Result<bool> {
+        let page = terminal::size()?.1.saturating_sub(3) as usize;
+        let last = self.tasks.len().saturating_sub(1);
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char('r') => {
+                self.reload()?;
+                self.status = Some(("Reloaded".into(), false));
+            }
+            KeyCode::Char('a') => self.start_prompt(Action::Add),
+            KeyCode::Char('e') if self.selected().is_some() => self.start_prompt(Action::Edit),
+            KeyCode::Char('p') if self.selected().is_some() => self.start_prompt(Action::Progress),
+            KeyCode::Char('d') | KeyCode::Delete if self.selected().is_some() => {
+                self.mode = Mode::ConfirmRemove
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn start_prompt(&mut self, action: Action) {
+        let mut prompt = Prompt {
+            action,
+            values: Vec::new(),
+            input: String::new(),
+        };
+        prompt.input = self.prefill(&prompt);
+        self.status = None;
+        self.mode = Mode::Prompt(prompt);
+    }
+
+    /// Current value of the field being prompted for when editing.
+    fn prefill(&self, prompt: &Prompt) -> String {
+        let Some(task) = self.selected().filter(|_| prompt.action == Action::Edit) else {
+            return String::new();
+        };
+        match prompt.values.len() {
+            0 => task.format_due(),
+            1 => duration_input(task.estimated_time),
+            2 => task.name.clone(),
+            3 => task.description.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Validates the current input, moving on to the next field or applying
+    /// the action once every field has been given.
+    fn submit(&mut self, prompt: &mut Prompt) -> Result<()> {
+        self.status = None;
+        let input = prompt.input.trim().to_owned();
+        match (prompt.action, prompt.values.len()) {
+            (Action::Add | Action::Edit, 0) => {
+                parse_input(&input, &regex(&format!("^(?:{DUE_PATTERN})$"))?, |v| {
+                    parse_due(&v)
+                })?;
+            }
+            (Action::Add | Action::Edit, 1) => {
+                parse_input(&input, &regex(DURATION_PATTERN)?, |v| parse_duration(&v))?;
+            }
+            (Action::Add | Action::Edit, _) if input.is_empty() => {
+                return Err(Error::Input("Value cannot be empty".into()));
+            }
+            (Action::Progress, _) => {
+                let task = self.selected().ok_or(Error::TaskNotFound)?;
+                parse_progress(&input, task)?;
+            }
+            _ => {}
+        }
+
+        prompt.values.push(input);
+        prompt.input = self.prefill(prompt);
+        if prompt.values.len() == prompt.action.fields().len() {
+            self.apply(prompt)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, prompt: &Prompt) -> Result<()> {
+        let v = &prompt.values;
+        let selected = self.selected().map(Task::uuid);
+        match prompt.action {
+            Action::Add | Action::Edit => {
+                let deadline = parse_input(&v[0], &regex(&format!("^(?:{DUE_PATTERN})$"))?, |v| {
+                    parse_due(&v)
+                })?;
+                let estimated_time =
+                    parse_input(&v[1], &regex(DURATION_PATTERN)?, |v| parse_duration(&v))?;
+                let set = |task: &mut Task| {
+                    task.deadline = deadline;
+                    task.estimated_time = estimated_time;
+                    task.name = v[2].clone();
+                    task.description = v[3].clone();
+                };
+
+                let (id, uuid) = match prompt.action {
+                    Action::Add => {
+                        let mut task = Task::new();
+                        task.created = Local::now().timestamp();
+                        set(&mut task);
+                        let uuid = task.uuid();
+                        let id = self.change(|tasks| {
+                            task.set_id(next_id(tasks));
+                            let id = task.id();
+                            tasks.push(task);
+                            Ok(id)
+                        })?;
+                        (id, uuid)
+                    }
+                    _ => {
+                        let uuid = selected.ok_or(Error::TaskNotFound)?;
+                        let id = self.change(|tasks| {
+                            let index = position(tasks, uuid)?;
+                            set(&mut tasks[index]);
+                            Ok(tasks[index].id())
+                        })?;
+                        (id, uuid)
+                    }
+                };
+                self.split(Some(uuid));
+                let verb = if prompt.action == Action::Add {
+                    "Added"
+                } else {
+                    "Updated"
+                };
+                self.status = Some((format!("{verb} task {id}"), false));
+                Ok(())
+            }
+            Action::Progress => {
+                let uuid = selected.ok_or(Error::TaskNotFound)?;
+                let task = self.change(|tasks| {
+                    let index = position(tasks, uuid)?;
+                    let amount = parse_progress(&v[0], &tasks[index])?;
+                    tasks[index].log_progress(amount);
+                    Ok(tasks[index].clone())
+                })?;
+
+                let id = task.id();
+                self.status = Some(if task.is_completed() {
+                    (format!("Task {id} completed"), false)
+                } else {
+                    (
+                        format!(
+                            "Task {id} progress updated to {:.1}%",
+                            task.get_completion() * 100.0
+                        ),
+                        false,
+                    )
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let body = height.saturating_sub(2);
+        let list_width = (width * 45 / 100).max(20).min(width);
+        let detail_width = width.saturating_sub(list_width + 1);
+
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if body > 0 && self.selected >= self.offset + body {
+            self.offset = self.selected + 1 - body;
+        }
+
+        let mut lines = vec![self.title_line(width)];
+        let details = self.detail_lines();
+        for row in 0..body {
+            let list = match self.tasks.get(self.offset + row) {
+                Some(task) => self.list_line(task, self.offset + row == self.selected, list_width),
+                None => String::new(),
+            };
+            let detail = details.get(row).map(String::as_str).unwrap_or("");
+            lines.push(format!(
+                "{}{}{}{}",
+                list,
+                " ".repeat(list_width.saturating_sub(visible_len(&list))),
+                "│".truecolor(64, 64, 64),
+                truncate(detail, detail_width)
+            ));
+        }
+        lines.push(self.status_line(width));
+
+        let mut out = stdout();
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(0, row as u16),
+                Clear(ClearType::UntilNewLine),
+                Print(line)
+            )?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn title_line(&self, width: usize) -> String {
+        let title = format!(
+            " todo-cli  {}  {} tasks ",
+            self.store.path().display(),
+            self.tasks.len()
+        );
+        truncate(&format!("{:<width$}", title), width)
+            .black()
+            .on_white()
+            .bold()
+            .to_string()
+    }
+
+    fn list_line(&self, task: &Task, selected: bool, width: usize) -> String {
+        let tl = task.get_time_left();
+        let left = urgency_colour(&format_duration(tl), tl);
+        let bar = progress_bar_sized(task.get_completion(), 8);
+        let id = format!("{:>3}", task.id()).cyan().to_string();
+
+        // Marker, id, time left and a bar with up to "100%" after it.
+        let fixed = 2 + 3 + 1 + 1 + 20 + 1 + 8 + 5;
+        let name_width = width.saturating_sub(fixed).max(4);
+        let name = truncate(&task.name, name_width);
+        let name = format!("{:<name_width$}", name);
+        let name = if selected {
+            name.bold().underline().to_string()
+        } else {
+            name
+        };
+
+        let marker = if selected {
+            "▶ ".bright_white().bold()
+        } else {
+            "  ".normal()
+        };
+        let line = format!(
+            "{marker}{id} {name} {}{} {bar}",
+            left,
+            " ".repeat(20usize.saturating_sub(visible_len(&left)))
+        );
+        truncate(&line, width)
+    }
+
+    fn detail_lines(&self) -> Vec<String> {
+        match self.selected() {
+            Some(task) => format!("{}\n{}", task, task.render_details())
+                .lines()
+                .map(|l| format!(" {l}"))
+                .collect(),
+            None => vec![format!(" {}", "No tasks, press a to add one".italic())],
+        }
+    }
+
+    fn status_line(&self, width: usize) -> String {
+        let line = match &self.mode {
+            Mode::Prompt(prompt) => {
+                let field = prompt.action.fields()[prompt.values.len()];
+                let error = match &self.status {
+                    Some((message, true)) => format!("  {}", message.red()),
+                    _ => String::new(),
+                };
+                format!("{}: {}█{}", field.bold(), prompt.input, error)
+            }
+            Mode::ConfirmRemove => format!(
+                "{} {}",
+                format!(
+                    "Remove task {}?",
+                    self.selected().map(Task::id).unwrap_or_default()
+                )
+                .red()
+                .bold(),
+                "(y/n)".bold()
+            ),
+            Mode::Normal => match &self.status {
+                Some((message, true)) => message.red().to_string(),
+                Some((message, false)) => message.green().to_string(),
+                None => "a add  e edit  p progress  d remove  r reload  j/k move  q quit"
+                    .truecolor(128, 128, 128)
+                    .to_string(),
+            },
+        };
+        truncate(&line, width)
+    }
+}
+
+/// Index of the task with UUID `uuid`, which may have been removed by another
+/// command since it was shown.
+fn position(tasks: &[Task], uuid: Uuid) -> Result<usize> {
+    tasks
+        .iter()
+        .position(|t| t.uuid() == uuid)
+        .ok_or(Error::TaskNotFound)
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::Input(e.to_string()))
+}
+
+/// Formats seconds the way the estimate prompt accepts them, e.g. `2h 30m`.
+fn duration_input(seconds: i64) -> String {
+    let parts = [
+        (seconds / 3600, "h"),
+        (seconds % 3600 / 60, "m"),
+        (seconds % 60, "s"),
+    ];
+    let s = parts
+        .iter()
+        .filter(|(n, _)| *n != 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if s.is_empty() {
+        strip_colors(&format_duration(0))
+    } else {
+        s
+    }
+}
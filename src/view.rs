@@ -0,0 +1,140 @@
+use std::str::FromStr;
+
+use colored::*;
+
+use todo_core::{
+    format::{format_duration, progress_bar_sized, table, terminal_width, urgency_colour},
+    task::Task,
+    Error,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum View {
+    Card,
+    Table,
+}
+impl FromStr for View {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "card" => Ok(View::Card),
+            "table" => Ok(View::Table),
+            other => Err(Error::Input(format!(
+                "Unknown view \"{other}\" (expected card or table)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Column {
+    Id,
+    /// The first digits of the task's UUID.
+    Uuid,
+    Name,
+    Due,
+    Left,
+    Remaining,
+    Progress,
+}
+impl Column {
+    pub(crate) const NAMES: [&'static str; 7] =
+        ["id", "uuid", "name", "due", "left", "remaining", "progress"];
+
+    pub(crate) const DEFAULT: [Column; 6] = [
+        Column::Id,
+        Column::Name,
+        Column::Due,
+        Column::Left,
+        Column::Remaining,
+        Column::Progress,
+    ];
+
+    fn header(&self) -> &'static str {
+        match self {
+            Column::Id => "Id",
+            Column::Uuid => "UUID",
+            Column::Name => "Name",
+            Column::Due => "Due",
+            Column::Left => "Time left",
+            Column::Remaining => "Remaining",
+            Column::Progress => "Progress",
+        }
+    }
+
+    fn cell(&self, task: &Task) -> String {
+        match self {
+            Column::Id => task.id().to_string().cyan().to_string(),
+            Column::Uuid => task.uuid().short().truecolor(128, 128, 128).to_string(),
+            Column::Name => task.name.bold().to_string(),
+            Column::Due => task.format_due().truecolor(255, 140, 0).to_string(),
+            Column::Left => {
+                let tl = task.get_time_left();
+                urgency_colour(&format_duration(tl), tl)
+            }
+            Column::Remaining => format_duration(task.get_remaining()),
+            Column::Progress => progress_bar_sized(task.get_completion(), 10),
+        }
+    }
+}
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "uuid" => Ok(Column::Uuid),
+            "name" => Ok(Column::Name),
+            "due" | "deadline" => Ok(Column::Due),
+            "left" | "time-left" => Ok(Column::Left),
+            "remaining" => Ok(Column::Remaining),
+            "progress" => Ok(Column::Progress),
+            other => Err(Error::Input(format!(
+                "Unknown column \"{other}\" (expected one of: {})",
+                Column::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Parses a comma separated list of columns, e.g. `id,name,left`.
+pub(crate) fn parse_columns(s: &str) -> Result<Vec<Column>, Error> {
+    s.split(',')
+        .filter(|c| !c.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// One row per task, shrinking the name column to fit the terminal.
+pub(crate) fn task_table(tasks: &[Task], columns: &[Column]) -> String {
+    let headers: Vec<&str> = columns.iter().map(Column::header).collect();
+    let rows: Vec<Vec<String>> = tasks
+        .iter()
+        .map(|t| columns.iter().map(|c| c.cell(t)).collect())
+        .collect();
+    let flex = columns.iter().position(|c| *c == Column::Name);
+
+    table(&headers, &rows, flex, terminal_width())
+}
+
+/// Like [`task_table`], with the project of each task in a first column.
+pub(crate) fn project_table(tasks: &[(String, Task)], columns: &[Column]) -> String {
+    let headers: Vec<&str> = std::iter::once("Project")
+        .chain(columns.iter().map(Column::header))
+        .collect();
+    let rows: Vec<Vec<String>> = tasks
+        .iter()
+        .map(|(project, t)| {
+            std::iter::once(project.magenta().to_string())
+                .chain(columns.iter().map(|c| c.cell(t)))
+                .collect()
+        })
+        .collect();
+    let flex = columns
+        .iter()
+        .position(|c| *c == Column::Name)
+        .map(|i| i + 1);
+
+    table(&headers, &rows, flex, terminal_width())
+}
@@ -0,0 +1,201 @@
+use std::{collections::HashSet, path::Path, process::Command, thread, time::Duration};
+
+use chrono::Local;
+use colored::*;
+use serde_json::json;
+
+use crate::{
+    output::{self, task_json},
+    session::lock_file,
+};
+use todo_core::{
+    format::{format_duration, strip_colors},
+    parse,
+    storage::{self, Backend},
+    task::Task,
+    Filter, Result, Uuid,
+};
+
+/// How `watch` checks the task file and how it reminds.
+#[derive(Debug, Clone)]
+pub(crate) struct WatchOptions {
+    /// Seconds between two reads of the task file.
+    pub(crate) interval: i64,
+    /// Reminders are due this many seconds before each deadline; 0 is the
+    /// deadline itself.
+    pub(crate) offsets: Vec<i64>,
+    /// Ring the terminal bell with each reminder.
+    pub(crate) bell: bool,
+    /// Run through `sh -c` for each reminder, with the details in
+    /// `TODO_CLI_*` environment variables.
+    pub(crate) command: Option<String>,
+}
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            interval: 60,
+            offsets: vec![24 * 60 * 60, 60 * 60, 0],
+            bell: false,
+            command: None,
+        }
+    }
+}
+
+/// Parses a comma separated list of offsets such as `1d,2h,15m,0`.
+pub(crate) fn parse_offsets(s: &str) -> Result<Vec<i64>> {
+    s.split(',')
+        .filter(|o| !o.trim().is_empty())
+        .map(parse::duration)
+        .collect()
+}
+
+enum Reminder {
+    /// The deadline is `offset` seconds away, or has just passed for 0.
+    Deadline { offset: i64 },
+    /// The remaining estimate no longer fits in the time left.
+    AtRisk,
+}
+
+/// Re-reads `file_path` every interval and reminds of approaching deadlines
+/// and of tasks that can no longer be finished in time. Runs until killed.
+pub(crate) fn run(file_path: &Path, options: &WatchOptions, storage: Backend) -> Result<()> {
+    let mut offsets = options.offsets.clone();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    if output::is_text() {
+        println!(
+            "{}",
+            format!(
+                "Watching {} every {}, press Ctrl-C to stop.",
+                file_path.display(),
+                strip_colors(&format_duration(options.interval)).trim_end()
+            )
+            .truecolor(128, 128, 128)
+        );
+    }
+
+    // Reminders already given, by task UUID, deadline and offset, so each
+    // one is only given once even as the file is read again.
+    let mut fired: HashSet<(Uuid, i64, i64)> = HashSet::new();
+    let mut at_risk: HashSet<(Uuid, i64)> = HashSet::new();
+    let mut first = true;
+    let open: Filter = "open".parse()?;
+
+    loop {
+        // Locked only while reading, so commands can change the file between
+        // two reads.
+        let read = lock_file(file_path, storage, true)
+            .and_then(|_lock| storage::open(file_path, storage)?.query(&open));
+        let tasks = match read {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                eprintln!("{}", format!("Error: {e}").red());
+                Vec::new()
+            }
+        };
+
+        for task in &tasks {
+            let tl = task.get_time_left();
+            let passed: Vec<i64> = offsets.iter().copied().filter(|o| tl <= *o).collect();
+            let new = passed
+                .iter()
+                .copied()
+                .find(|o| !fired.contains(&(task.uuid(), task.deadline, *o)));
+            // Deadlines that passed before watching started aren't news.
+            if let Some(offset) = new.filter(|_| !(first && tl < 0)) {
+                remind(task, Reminder::Deadline { offset }, options);
+            }
+            fired.extend(passed.iter().map(|o| (task.uuid(), task.deadline, *o)));
+
+            let key = (task.uuid(), task.deadline);
+            if tl > 0 && task.get_remaining() > tl {
+                if at_risk.insert(key) {
+                    remind(task, Reminder::AtRisk, options);
+                }
+            } else {
+                at_risk.remove(&key);
+            }
+        }
+
+        first = false;
+        thread::sleep(Duration::from_secs(options.interval.max(1) as u64));
+    }
+}
+
+fn remind(task: &Task, reminder: Reminder, options: &WatchOptions) {
+    let duration = |d: i64| strip_colors(&format_duration(d)).trim_end().to_owned();
+    let (kind, message) = match reminder {
+        Reminder::Deadline { offset: 0 } => (
+            "due",
+            format!("Task {} \"{}\" is due now", task.id(), task.name),
+        ),
+        Reminder::Deadline { .. } => (
+            "deadline",
+            format!(
+                "Task {} \"{}\" is due in {} ({})",
+                task.id(),
+                task.name,
+                duration(task.get_time_left()),
+                task.format_due()
+            ),
+        ),
+        Reminder::AtRisk => (
+            "at_risk",
+            format!(
+                "Task {} \"{}\" needs {} more but only {} is left",
+                task.id(),
+                task.name,
+                duration(task.get_remaining()),
+                duration(task.get_time_left())
+            ),
+        ),
+    };
+
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    output::report(
+        || {
+            let text = format!(
+                "{} {}",
+                now.truecolor(128, 128, 128),
+                match kind {
+                    "deadline" => message.yellow(),
+                    _ => message.bright_red(),
+                }
+            );
+            if options.bell {
+                format!("\x07{text}")
+            } else {
+                text
+            }
+        },
+        || {
+            json!({
+                "time": now,
+                "kind": kind,
+                "message": message,
+                "task": task_json(task),
+            })
+        },
+    );
+
+    if let Some(command) = &options.command {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("TODO_CLI_MESSAGE", &message)
+            .env("TODO_CLI_KIND", kind)
+            .env("TODO_CLI_TASK_ID", task.id().to_string())
+            .env("TODO_CLI_TASK_UUID", task.uuid().to_string())
+            .env("TODO_CLI_TASK_NAME", &task.name)
+            .env("TODO_CLI_DEADLINE", task.format_due())
+            .status();
+        match status {
+            Ok(status) if !status.success() => {
+                eprintln!("{}", format!("Reminder command failed: {status}").red())
+            }
+            Err(e) => eprintln!("{}", format!("Reminder command failed: {e}").red()),
+            Ok(_) => {}
+        }
+    }
+}
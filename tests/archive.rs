@@ -0,0 +1,55 @@
+mod common;
+
+use common::{run_ok, TestDir};
+use serde_json::Value;
+
+fn names(directory: &std::path::Path, args: &[&str]) -> Vec<String> {
+    let mut args = args.to_vec();
+    args.extend(["--output", "json"]);
+    let tasks: Value = serde_json::from_str(&run_ok(directory, &args, "")).unwrap();
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn archives_completed_tasks_and_brings_them_back() {
+    let dir = TestDir::new("archive");
+    let path = dir.task_file(&[
+        ("2100-01-01 12:00:00", "1h", "write report"),
+        ("2100-01-02 12:00:00", "1h", "pay rent"),
+        ("2100-01-03 12:00:00", "1h", "fix sink"),
+    ]);
+    let file = path.to_str().unwrap();
+    run_ok(dir.path(), &["progress", "0", "1h", file], "");
+    run_ok(dir.path(), &["progress", "2", "1h", file], "");
+
+    // Just completed, so not stale unless the age allows it.
+    run_ok(dir.path(), &["archive", file, "--yes"], "");
+    assert_eq!(names(dir.path(), &["list", file, "--all"]).len(), 3);
+
+    run_ok(
+        dir.path(),
+        &["archive", file, "--older-than", "0", "--yes"],
+        "",
+    );
+    assert!(dir.path().join("task_list.archive").exists());
+    assert_eq!(names(dir.path(), &["list", file, "--all"]), ["pay rent"]);
+    assert_eq!(
+        names(dir.path(), &["list", file, "--archived"]),
+        ["write report", "fix sink"]
+    );
+
+    run_ok(dir.path(), &["unarchive", "1", file], "");
+    assert_eq!(
+        names(dir.path(), &["list", file, "--all"]),
+        ["pay rent", "fix sink"]
+    );
+    assert_eq!(
+        names(dir.path(), &["list", file, "--archived"]),
+        ["write report"]
+    );
+}
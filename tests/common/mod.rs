@@ -0,0 +1,70 @@
+//! Helpers shared by the integration tests, which drive the built binary.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// Runs the CLI in `directory` with `args`, answering its prompts with
+/// `input`.
+pub fn run(directory: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_todo-cli"))
+        .args(args)
+        .current_dir(directory)
+        .env("TODO_CLI_CONFIG", directory.join("config"))
+        .env("TODO_CLI_DATA", directory.join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Like [`run`], but fails the test unless the command succeeds and returns
+/// what it printed.
+pub fn run_ok(directory: &Path, args: &[&str], input: &str) -> String {
+    let output = run(directory, args, input);
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// An empty directory of its own for `test`, removed when dropped.
+pub struct TestDir(PathBuf);
+impl TestDir {
+    pub fn new(test: &str) -> Self {
+        let path = env::temp_dir().join(format!("todo-cli-test-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir(&path).unwrap();
+        TestDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// `task_list` in the directory, holding `tasks` given as due date,
+    /// estimate and name and added in that order.
+    pub fn task_file(&self, tasks: &[(&str, &str, &str)]) -> PathBuf {
+        let path = self.0.join("task_list");
+        for (due, estimate, name) in tasks {
+            let input = format!("{due}\n{estimate}\n{name}\n\n");
+            run_ok(&self.0, &["add", path.to_str().unwrap()], &input);
+        }
+        path
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
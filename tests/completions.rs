@@ -0,0 +1,37 @@
+mod common;
+
+use common::{run_ok, TestDir};
+
+#[test]
+fn completes_task_ids_with_their_names() {
+    let dir = TestDir::new("complete-ids");
+    let path = dir.task_file(&[
+        ("2100-01-01 12:00:00", "1h", "write report"),
+        ("2100-01-02 12:00:00", "1h", "pay rent"),
+    ]);
+
+    let output = run_ok(dir.path(), &["__complete-ids", path.to_str().unwrap()], "");
+    assert_eq!(output, "0\twrite report\n1\tpay rent\n");
+}
+
+#[test]
+fn completing_ids_of_a_missing_file_prints_nothing() {
+    let dir = TestDir::new("complete-ids-missing");
+    let missing = dir.path().join("missing");
+
+    let output = run_ok(
+        dir.path(),
+        &["__complete-ids", missing.to_str().unwrap()],
+        "",
+    );
+    assert!(output.is_empty());
+}
+
+#[test]
+fn prints_a_script_per_shell() {
+    let dir = TestDir::new("completions");
+    for shell in ["bash", "zsh", "fish"] {
+        let output = run_ok(dir.path(), &["completions", shell], "");
+        assert!(output.contains("__complete-ids"), "{shell}");
+    }
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use std::fs;
+
+use common::{run_ok, TestDir};
+use serde_json::Value;
+
+/// A record in the legacy format, which has no extension block.
+fn legacy_record(id: i64, estimated_time: i64, name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for field in [id, 0, 4_102_444_800, estimated_time] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    for text in [name, ""] {
+        bytes.extend_from_slice(&text.len().to_be_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn reads_legacy_files_and_upgrades_them_on_the_next_write() {
+    let dir = TestDir::new("legacy");
+    let path = dir.path().join("task_list");
+    let mut legacy = legacy_record(0, 3600, "old task");
+    legacy.extend(legacy_record(1, 7200, "older task"));
+    fs::write(&path, &legacy).unwrap();
+    let path = path.to_str().unwrap();
+
+    let list = |dir: &TestDir| -> Value {
+        let output = run_ok(dir.path(), &["list", path, "--output", "json"], "");
+        serde_json::from_str(&output).unwrap()
+    };
+    let tasks = list(&dir);
+    assert_eq!(tasks[0]["name"], "old task");
+    assert_eq!(tasks[1]["name"], "older task");
+    assert_eq!(tasks[1]["estimated_time"], 7200);
+
+    run_ok(dir.path(), &["progress", "1", "30m", path], "");
+    assert!(fs::read(path).unwrap().starts_with(b"TDL\x02"));
+
+    let tasks = list(&dir);
+    assert_eq!(tasks[0]["name"], "old task");
+    assert_eq!(tasks[1]["name"], "older task");
+    assert_eq!(tasks[1]["progress"], 1800);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::path::Path;
+
+use common::{run_ok, TestDir};
+
+/// The task names `list` prints for `path` with `args`, in the order printed.
+fn list<'a>(path: &Path, args: &[&str], names: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec!["list"];
+    command.extend(args);
+    command.push(path.to_str().unwrap());
+    let output = run_ok(path.parent().unwrap(), &command, "");
+
+    let mut printed: Vec<_> = names
+        .iter()
+        .map(|name| (output.find(name).expect(name), *name))
+        .collect();
+    printed.sort();
+    printed.into_iter().map(|(_, name)| name).collect()
+}
+
+const TASKS: [(&str, &str, &str); 4] = [
+    ("2100-01-03 12:00:00", "1h", "delta task"),
+    ("2100-01-01 12:00:00", "3h", "bravo task"),
+    ("2100-01-02 12:00:00", "3h", "charlie task"),
+    ("2100-01-04 12:00:00", "1h", "alpha task"),
+];
+const NAMES: [&str; 4] = ["delta task", "bravo task", "charlie task", "alpha task"];
+
+#[test]
+fn lists_by_deadline_rather_than_in_file_order() {
+    let dir = TestDir::new("deadline");
+    let path = dir.task_file(&TASKS);
+    assert_eq!(
+        list(&path, &[], &NAMES),
+        ["bravo task", "charlie task", "delta task", "alpha task"]
+    );
+}
+
+#[test]
+fn sorts_by_each_key_in_turn_and_reverses() {
+    let dir = TestDir::new("keys");
+    let path = dir.task_file(&TASKS);
+    assert_eq!(
+        list(&path, &["--sort", "name"], &NAMES),
+        ["alpha task", "bravo task", "charlie task", "delta task"]
+    );
+    assert_eq!(
+        list(&path, &["--sort", "estimate,deadline"], &NAMES),
+        ["delta task", "alpha task", "bravo task", "charlie task"]
+    );
+    assert_eq!(
+        list(&path, &["--sort", "estimate,deadline", "--reverse"], &NAMES),
+        ["charlie task", "bravo task", "alpha task", "delta task"]
+    );
+}
+
+#[test]
+fn cancelled_tasks_are_only_listed_on_request() {
+    let dir = TestDir::new("cancelled");
+    let path = dir.task_file(&[
+        ("2100-01-01 12:00:00", "1h", "keep"),
+        ("2100-01-02 12:00:00", "1h", "drop"),
+    ]);
+    let file = path.to_str().unwrap();
+    let output = run_ok(dir.path(), &["cancel", "1", file], "");
+    assert!(output.contains("cancelled task with id 1"), "{output}");
+
+    let output = run_ok(dir.path(), &["list", file], "");
+    assert!(
+        output.contains("keep") && !output.contains("drop"),
+        "{output}"
+    );
+    assert_eq!(list(&path, &["--all"], &["keep", "drop"]), ["keep", "drop"]);
+    assert_eq!(
+        list(&path, &["--all", "--where", "cancelled"], &["drop"]),
+        ["drop"]
+    );
+    let output = run_ok(
+        dir.path(),
+        &["list", "--all", "--where", "cancelled", file],
+        "",
+    );
+    assert!(!output.contains("keep"), "{output}");
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::fs;
+
+use common::{run, run_ok, TestDir};
+use serde_json::Value;
+
+const TASKS: [(&str, &str, &str); 2] = [
+    ("2100-01-02 12:00:00", "2h", "second"),
+    ("2100-01-01 12:00:00", "1h", "first"),
+];
+
+#[test]
+fn lists_tasks_as_a_json_array() {
+    let dir = TestDir::new("json");
+    let path = dir.task_file(&TASKS);
+    let output = run_ok(
+        dir.path(),
+        &["list", path.to_str().unwrap(), "--output", "json"],
+        "",
+    );
+
+    let tasks: Value = serde_json::from_str(&output).unwrap();
+    let tasks = tasks.as_array().unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0]["id"], 1);
+    assert_eq!(tasks[0]["name"], "first");
+    assert_eq!(tasks[0]["estimated_time"], 3600);
+    assert_eq!(tasks[0]["progress"], 0);
+    assert_eq!(tasks[1]["name"], "second");
+}
+
+#[test]
+fn lists_tasks_as_one_json_object_per_line() {
+    let dir = TestDir::new("ndjson");
+    let path = dir.task_file(&TASKS);
+    let output = run_ok(
+        dir.path(),
+        &["--output", "ndjson", "list", path.to_str().unwrap()],
+        "",
+    );
+
+    let names: Vec<Value> = output
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap()["name"].clone())
+        .collect();
+    assert_eq!(names, ["first", "second"]);
+}
+
+/// The exit code and the JSON error `args` fail with.
+fn failure(dir: &TestDir, args: &[&str]) -> (i32, Value) {
+    let mut command = args.to_vec();
+    command.extend(["--output", "json"]);
+    let output = run(dir.path(), &command, "");
+
+    let error: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(error["error"]["code"], output.status.code().unwrap());
+    (
+        output.status.code().unwrap(),
+        error["error"]["kind"].clone(),
+    )
+}
+
+#[test]
+fn exits_with_a_code_per_error_kind() {
+    let dir = TestDir::new("exit-codes");
+    let path = dir.task_file(&TASKS);
+    let path = path.to_str().unwrap();
+    let corrupt = dir.path().join("corrupt");
+    fs::write(&corrupt, b"not a task file").unwrap();
+    let missing = dir.path().join("missing");
+
+    assert_eq!(
+        failure(&dir, &["list", missing.to_str().unwrap()]),
+        (2, "io".into())
+    );
+    assert_eq!(
+        failure(&dir, &["list", path, "--sort", "bogus"]),
+        (4, "input".into())
+    );
+    assert_eq!(
+        failure(&dir, &["remove", "7", path]),
+        (5, "task_not_found".into())
+    );
+    assert_eq!(failure(&dir, &["bogus"]), (6, "invalid_command".into()));
+    assert_eq!(
+        failure(&dir, &["list", corrupt.to_str().unwrap()]),
+        (7, "invalid_file_format".into())
+    );
+    assert_eq!(failure(&dir, &["remove"]), (8, "invalid_arguments".into()));
+}
+
+#[test]
+fn chart_capacity_takes_any_duration() {
+    let dir = TestDir::new("chart-capacity");
+    let path = dir.task_file(&TASKS);
+    let output = run_ok(
+        dir.path(),
+        &[
+            "chart",
+            path.to_str().unwrap(),
+            "--capacity",
+            "1d",
+            "--output",
+            "json",
+        ],
+        "",
+    );
+
+    let chart: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(chart["capacity"], 86400);
+}
+
+#[test]
+fn changes_by_match_are_confirmed_unless_the_name_is_exact() {
+    let dir = TestDir::new("match-confirm");
+    let path = dir.task_file(&TASKS);
+    let path = path.to_str().unwrap();
+    let names = || -> Vec<Value> {
+        let output = run_ok(dir.path(), &["list", path, "--output", "json"], "");
+        let tasks: Value = serde_json::from_str(&output).unwrap();
+        tasks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].clone())
+            .collect()
+    };
+
+    run_ok(dir.path(), &["remove", "--match", "sec", path], "n\n");
+    assert_eq!(names(), ["first", "second"]);
+    run_ok(dir.path(), &["remove", "--match", "sec", path], "y\n");
+    assert_eq!(names(), ["first"]);
+    // Without anything to answer with, as the name is exact.
+    let output = run_ok(dir.path(), &["remove", "--match", "First", path], "");
+    assert!(output.contains("removed"), "{output}");
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{run, run_ok, TestDir};
+use serde_json::Value;
+
+fn names(output: &str) -> Vec<String> {
+    let tasks: Value = serde_json::from_str(output).unwrap();
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn commands_without_a_file_use_the_current_project() {
+    let dir = TestDir::new("current-project");
+    run_ok(dir.path(), &["project", "create", "work"], "");
+    run_ok(dir.path(), &["project", "create", "home"], "");
+    run_ok(dir.path(), &["project", "switch", "work"], "");
+    run_ok(
+        dir.path(),
+        &["add"],
+        "2100-01-01 12:00:00\n1h\nwrite report\n\n",
+    );
+    run_ok(
+        dir.path(),
+        &["add", "--project", "home"],
+        "2100-01-01 12:00:00\n1h\npay rent\n\n",
+    );
+
+    let list = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.extend(["--output", "json"]);
+        names(&run_ok(dir.path(), &args, ""))
+    };
+    assert_eq!(list(&["list"]), ["write report"]);
+    assert_eq!(list(&["list", "--project", "home"]), ["pay rent"]);
+    assert!(!dir.path().join("task_list").exists());
+
+    run_ok(dir.path(), &["project", "switch", "--none"], "");
+    let output = run(dir.path(), &["list"], "");
+    assert!(!output.status.success());
+    assert!(run(dir.path(), &["list", "--project", "garden"], "")
+        .status
+        .code()
+        .is_some_and(|code| code != 0));
+}
+
+#[test]
+fn moves_tasks_between_projects_and_lists_them_all() {
+    let dir = TestDir::new("move-project");
+    run_ok(dir.path(), &["project", "create", "work"], "");
+    run_ok(dir.path(), &["project", "create", "home"], "");
+    for (project, name) in [
+        ("home", "pay rent"),
+        ("work", "write report"),
+        ("work", "fix sink"),
+    ] {
+        let input = format!("2100-01-01 12:00:00\n1h\n{name}\n\n");
+        run_ok(dir.path(), &["add", "--project", project], &input);
+    }
+
+    run_ok(
+        dir.path(),
+        &["move", "1", "--to", "home", "--project", "work"],
+        "",
+    );
+
+    let output = run_ok(
+        dir.path(),
+        &["list", "--project", "home", "--output", "json"],
+        "",
+    );
+    let tasks: Value = serde_json::from_str(&output).unwrap();
+    let tasks = tasks.as_array().unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[1]["name"], "fix sink");
+    assert_eq!(tasks[1]["id"], 1);
+
+    let output = run_ok(
+        dir.path(),
+        &["list", "--all-projects", "--output", "json"],
+        "",
+    );
+    let tasks: Value = serde_json::from_str(&output).unwrap();
+    let projects: Vec<(&str, &str)> = tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| (t["project"].as_str().unwrap(), t["name"].as_str().unwrap()))
+        .collect();
+    assert_eq!(projects.len(), 3);
+    assert!(projects.contains(&("home", "fix sink")));
+    assert!(projects.contains(&("work", "write report")));
+}
+
+#[test]
+fn the_shell_keeps_projects_in_the_configured_data_dir() {
+    let dir = TestDir::new("shell-data-dir");
+    let data_dir = dir.path().join("elsewhere");
+    std::fs::write(
+        dir.path().join("config"),
+        format!("data_dir = {}\n", data_dir.display()),
+    )
+    .unwrap();
+
+    run_ok(dir.path(), &["shell"], "project create work\nexit\n");
+    assert!(data_dir.join("projects").join("work").exists());
+    assert!(!dir.path().join("data").join("projects").exists());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use common::{run, run_ok, TestDir};
+use serde_json::Value;
+
+fn git(directory: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(directory)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?}: {output:?}");
+}
+
+/// A clone of `remote` named `name` in `dir`, with a committer set.
+fn clone(dir: &TestDir, remote: &Path, name: &str) -> std::path::PathBuf {
+    git(
+        dir.path(),
+        &["clone", "--quiet", remote.to_str().unwrap(), name],
+    );
+    let clone = dir.path().join(name);
+    git(&clone, &["config", "user.name", "Tester"]);
+    git(&clone, &["config", "user.email", "tester@example.com"]);
+    clone
+}
+
+fn names(directory: &Path) -> Vec<String> {
+    let output = run_ok(directory, &["list", "tasks", "--output", "json"], "");
+    let tasks: Value = serde_json::from_str(&output).unwrap();
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn merges_tasks_changed_on_both_sides_asking_about_conflicts() {
+    let Ok(_) = Command::new("git").arg("--version").output() else {
+        return;
+    };
+    let dir = TestDir::new("sync");
+    let remote = dir.path().join("remote.git");
+    git(dir.path(), &["init", "--quiet", "--bare", "remote.git"]);
+    let a = clone(&dir, &remote, "a");
+    let b = clone(&dir, &remote, "b");
+
+    for name in ["write report", "pay rent"] {
+        let input = format!("2100-01-01 12:00:00\n1h\n{name}\n\n");
+        run_ok(&a, &["add", "tasks"], &input);
+    }
+    run_ok(&a, &["sync", "tasks"], "");
+    run_ok(&b, &["sync", "tasks"], "");
+    assert_eq!(names(&b), ["write report", "pay rent"]);
+
+    run_ok(&a, &["set", "0", "name=write the report", "tasks"], "");
+    run_ok(&a, &["set", "1", "priority=high", "tasks"], "");
+    run_ok(&a, &["sync", "tasks"], "");
+    run_ok(&b, &["set", "0", "name=draft the report", "tasks"], "");
+    run_ok(
+        &b,
+        &["add", "tasks"],
+        "2100-01-02 12:00:00\n1h\nfix sink\n\n",
+    );
+
+    let output = run(&b, &["sync", "tasks"], "t\n");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(names(&b), ["write the report", "pay rent", "fix sink"]);
+    let output = run_ok(&b, &["show", "1", "tasks", "--output", "json"], "");
+    let task: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(task["task"]["priority"], "high");
+
+    // Everything was committed and pushed.
+    let status = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .current_dir(&b)
+        .output()
+        .unwrap();
+    assert!(status.stdout.is_empty());
+    run_ok(&a, &["sync", "tasks"], "");
+    assert_eq!(
+        fs::read(a.join("tasks")).unwrap(),
+        fs::read(b.join("tasks")).unwrap()
+    );
+}
@@ -0,0 +1,79 @@
+use std::io;
+
+use thiserror::Error as ThisError;
+
+/// Everything that can go wrong, from reading a task file to parsing the
+/// command line of a tool built on this crate.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Parse error: {0}")]
+    Parse(String),
+
+    #[error("Invalid input: {0}")]
+    Input(String),
+
+    #[error("Task not found")]
+    TaskNotFound,
+
+    #[error("Invalid command")]
+    InvalidCommand,
+
+    #[error("Invalid file format")]
+    InvalidFileFormat,
+
+    #[error("Invalid arguments")]
+    InvalidArguments,
+
+    #[error("Ambiguous match, candidates: {0}")]
+    AmbiguousMatch(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    #[error("Sync error: {0}")]
+    Sync(String),
+
+    #[error("Task file {0} is in use by another command")]
+    Locked(String),
+}
+
+impl Error {
+    /// Stable identifier of the variant for machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Parse(_) => "parse",
+            Error::Input(_) => "input",
+            Error::TaskNotFound => "task_not_found",
+            Error::InvalidCommand => "invalid_command",
+            Error::InvalidFileFormat => "invalid_file_format",
+            Error::InvalidArguments => "invalid_arguments",
+            Error::AmbiguousMatch(_) => "ambiguous_match",
+            Error::Storage(_) => "storage",
+            Error::Sync(_) => "sync",
+            Error::Locked(_) => "locked",
+        }
+    }
+
+    /// Process exit code, stable per variant so scripts can tell them apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 2,
+            Error::Parse(_) => 3,
+            Error::Input(_) => 4,
+            Error::TaskNotFound => 5,
+            Error::InvalidCommand => 6,
+            Error::InvalidFileFormat => 7,
+            Error::InvalidArguments => 8,
+            Error::AmbiguousMatch(_) => 9,
+            Error::Storage(_) => 10,
+            Error::Sync(_) => 11,
+            Error::Locked(_) => 12,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
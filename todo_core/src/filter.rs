@@ -0,0 +1,183 @@
+use std::str::FromStr;
+
+use chrono::{Days, Local};
+
+use crate::{
+    task::{Priority, Task},
+    Error,
+};
+
+/// A single condition of a `--where` filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Condition {
+    Open,
+    Completed,
+    Cancelled,
+    Overdue,
+    /// The remaining estimate no longer fits in the time left.
+    AtRisk,
+    DueToday,
+    /// Due within the next seven days.
+    DueWeek,
+    Priority(Priority),
+    /// The name contains the text, ignoring case.
+    Name(String),
+}
+impl Condition {
+    fn matches(&self, task: &Task) -> bool {
+        let due_within = |days: u64| task.is_open() && task.deadline < day_start(days);
+
+        match self {
+            Condition::Open => task.is_open(),
+            Condition::Completed => task.is_completed(),
+            Condition::Cancelled => task.is_cancelled(),
+            Condition::Overdue => task.is_overdue(),
+            Condition::AtRisk => task.is_open() && task.get_slack() < 0,
+            Condition::DueToday => due_within(1),
+            Condition::DueWeek => due_within(7),
+            Condition::Priority(priority) => task.priority == *priority,
+            Condition::Name(text) => task.name.to_lowercase().contains(&text.to_lowercase()),
+        }
+    }
+}
+/// Timestamp of the start of the day `days` days from today.
+pub(crate) fn day_start(days: u64) -> i64 {
+    let day = Local::now().date_naive() + Days::new(days);
+    day.and_hms_opt(0, 0, 0)
+        .and_then(|t| t.and_local_timezone(Local).earliest())
+        .map_or(i64::MAX, |t| t.timestamp())
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((key, value)) = s.split_once('=') {
+            return match key.trim().to_lowercase().as_str() {
+                "priority" => Ok(Condition::Priority(value.parse()?)),
+                "name" => Ok(Condition::Name(value.trim().to_owned())),
+                other => Err(Error::Input(format!(
+                    "Unknown filter field \"{other}\" (expected priority or name)"
+                ))),
+            };
+        }
+
+        match s.to_lowercase().as_str() {
+            "open" => Ok(Condition::Open),
+            "completed" | "done" => Ok(Condition::Completed),
+            "cancelled" | "canceled" => Ok(Condition::Cancelled),
+            "overdue" => Ok(Condition::Overdue),
+            "at-risk" => Ok(Condition::AtRisk),
+            "due-today" => Ok(Condition::DueToday),
+            "due-week" => Ok(Condition::DueWeek),
+            other => Err(Error::Input(format!(
+                "Unknown filter \"{other}\" (expected one of: {})",
+                Filter::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Comma separated conditions a task has to meet all of, e.g.
+/// `overdue,priority=high`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter(Vec<Condition>);
+impl Filter {
+    pub const NAMES: [&'static str; 9] = [
+        "open",
+        "completed",
+        "cancelled",
+        "overdue",
+        "at-risk",
+        "due-today",
+        "due-week",
+        "priority=",
+        "name=",
+    ];
+
+    pub fn matches(&self, task: &Task) -> bool {
+        self.0.iter().all(|c| c.matches(task))
+    }
+
+    /// The filter matching the tasks both `self` and `other` match.
+    pub fn and(mut self, other: Filter) -> Filter {
+        self.0.extend(other.0);
+        self
+    }
+
+    pub(crate) fn conditions(&self) -> &[Condition] {
+        &self.0
+    }
+}
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let conditions = s
+            .split(',')
+            .filter(|c| !c.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if conditions.is_empty() {
+            return Err(Error::Input("Empty filter".into()));
+        }
+        Ok(Filter(conditions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(priority: Priority, name: &str, completed: bool) -> Task {
+        let mut task = Task::with_id(0);
+        task.deadline = Local::now().timestamp() + 3 * 24 * 60 * 60;
+        task.estimated_time = 60 * 60;
+        task.priority = priority;
+        task.name = name.into();
+        if completed {
+            task.completed = 1;
+        }
+        task
+    }
+
+    #[test]
+    fn every_condition_has_to_hold() {
+        let filter: Filter = "open, priority=high,name=Report".parse().unwrap();
+        assert!(filter.matches(&task(Priority::High, "write report", false)));
+        assert!(!filter.matches(&task(Priority::High, "write report", true)));
+        assert!(!filter.matches(&task(Priority::Low, "write report", false)));
+        assert!(!filter.matches(&task(Priority::High, "pay rent", false)));
+    }
+
+    #[test]
+    fn due_conditions_follow_the_deadline() {
+        let open = task(Priority::Normal, "", false);
+        assert!("due-week".parse::<Filter>().unwrap().matches(&open));
+        assert!(!"due-today".parse::<Filter>().unwrap().matches(&open));
+        assert!(!"overdue".parse::<Filter>().unwrap().matches(&open));
+    }
+
+    #[test]
+    fn cancelled_tasks_are_not_open() {
+        let mut cancelled = task(Priority::Normal, "", false);
+        cancelled.deadline = 1;
+        cancelled.cancelled = 1;
+        for name in ["open", "completed", "overdue", "due-week", "at-risk"] {
+            assert!(
+                !name.parse::<Filter>().unwrap().matches(&cancelled),
+                "{name}"
+            );
+        }
+        assert!("cancelled".parse::<Filter>().unwrap().matches(&cancelled));
+    }
+
+    #[test]
+    fn unknown_or_empty_filters_are_an_error() {
+        assert!("soon".parse::<Filter>().is_err());
+        assert!("colour=red".parse::<Filter>().is_err());
+        assert!("priority=whenever".parse::<Filter>().is_err());
+        assert!(" , ".parse::<Filter>().is_err());
+    }
+}
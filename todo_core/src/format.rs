@@ -0,0 +1,315 @@
+use std::cmp::max;
+
+use colored::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn progress_bar(progress: f32) -> String {
+    progress_bar_sized(progress, 25)
+}
+
+pub fn progress_bar_sized(progress: f32, total_len: usize) -> String {
+    let progress_len = (progress.clamp(0.0, 1.0) * (total_len as f32)).round() as u32;
+    let percentage = format!(" {:.0}%", progress * 100.0);
+    format!(
+        "{}{}{}",
+        "━".repeat(progress_len as usize).green(),
+        "━"
+            .repeat(total_len - progress_len as usize)
+            .truecolor(64, 64, 64),
+        percentage.cyan()
+    )
+}
+
+pub fn format_duration(duration: i64) -> String {
+    let sign = if duration >= 0 { "" } else { "-" };
+    let mut remaining = duration.abs();
+    let months = remaining / 86400 / 30;
+    remaining -= months * 86400 * 30;
+    let days = remaining / 86400;
+    remaining -= days * 86400;
+    let hours = remaining / 3600;
+    remaining -= hours * 3600;
+    let minutes = remaining / 60;
+    remaining -= minutes * 60;
+
+    let month_s = if months != 0 {
+        format!("{}mo ", months).truecolor(120, 103, 205)
+    } else {
+        "".into()
+    };
+    let days_s = if days != 0 {
+        format!("{}d ", days).cyan()
+    } else {
+        "".into()
+    };
+
+    let hour_s = if hours != 0 {
+        format!("{}h ", hours).purple()
+    } else {
+        "".into()
+    };
+    let minutes_s = if minutes != 0 {
+        format!("{}m ", minutes).yellow()
+    } else {
+        "".into()
+    };
+    let seconds_s = if remaining != 0
+        || (month_s.is_empty() && days_s.is_empty() && hour_s.is_empty() && minutes_s.is_empty())
+    {
+        format!("{}s", remaining).green()
+    } else {
+        "".into()
+    };
+
+    format!(
+        "{sign}{}{}{}{}{}",
+        month_s, days_s, hour_s, minutes_s, seconds_s
+    )
+}
+
+/// Colours an already formatted time left by how close the deadline is.
+pub fn urgency_colour(text: &str, time_left: i64) -> String {
+    let text = strip_colors(text);
+    if time_left >= 7 * 24 * 60 * 60 {
+        text.blue()
+    } else if time_left >= 2 * 24 * 60 * 60 {
+        text.green()
+    } else if time_left >= 24 * 60 * 60 {
+        text.yellow()
+    } else if time_left >= 5 * 60 * 60 {
+        text.red()
+    } else {
+        text.bright_red()
+    }
+    .to_string()
+}
+
+/// Applies `style` to `text`, additionally marking the characters at
+/// `positions` (char indices).
+pub fn highlight(text: &str, positions: &[usize], style: impl Fn(&str) -> ColoredString) -> String {
+    let mut out = String::new();
+    let mut run = String::new();
+    let mut run_marked = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let marked = positions.contains(&i);
+        if marked != run_marked && !run.is_empty() {
+            out += &highlight_run(&run, run_marked, &style);
+            run.clear();
+        }
+        run_marked = marked;
+        run.push(c);
+    }
+    out + &highlight_run(&run, run_marked, &style)
+}
+
+fn highlight_run(run: &str, marked: bool, style: &impl Fn(&str) -> ColoredString) -> String {
+    if marked {
+        style(run).black().on_yellow().to_string()
+    } else {
+        style(run).to_string()
+    }
+}
+
+lazy_static! {
+    static ref ansi_re: Regex = Regex::new(r"\x1B\[[0-9;]*[a-zA-Z]").unwrap();
+}
+
+pub fn strip_colors(s: &str) -> String {
+    ansi_re.replace_all(s, "").to_string()
+}
+
+pub fn card(strings: Vec<(String, String)>) -> String {
+    let max_len = strings
+        .iter()
+        .map(|(f, s)| (f.chars().count(), strip_colors(s).chars().count()))
+        .reduce(|cur, s| (max(cur.0, s.0), max(cur.1, s.1)))
+        .unwrap_or((0, 0));
+
+    let content = &strings
+        .iter()
+        .map(|(f, s)| {
+            format!(
+                "┣━╾╌{:<width$}·{}{}{}┃\n",
+                f,
+                " ".repeat(2),
+                s,
+                " ".repeat(max_len.1 - strip_colors(s).chars().count()),
+                width = max_len.0 + 2,
+            )
+        })
+        .collect::<String>();
+
+    let line_width = ansi_re
+        .replace_all(content.lines().next().unwrap_or(""), "")
+        .chars()
+        .count();
+
+    format!("┏{}┓\n", "━".repeat(line_width - 2))
+        + content
+        + &format!("┗{}┛", "━".repeat(line_width - 2))
+}
+
+/// Width of the terminal stdout is attached to, if any.
+///
+/// Falls back to `$COLUMNS` so the width can be forced when piping.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size()
+        .map(|(w, _)| w.0 as usize)
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+}
+
+/// Visible width of `s`, ignoring colour codes.
+pub fn visible_len(s: &str) -> usize {
+    strip_colors(s).chars().count()
+}
+
+/// Cuts `s` down to `width` visible characters, ending in `…` when shortened.
+/// Colour codes are kept intact and reset after the cut.
+pub fn truncate(s: &str, width: usize) -> String {
+    if visible_len(s) <= width {
+        return s.to_owned();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut out = String::new();
+    let mut visible = 0;
+    let mut rest = s;
+    while visible < width - 1 {
+        if let Some(m) = ansi_re.find(rest).filter(|m| m.start() == 0) {
+            out.push_str(m.as_str());
+            rest = &rest[m.end()..];
+            continue;
+        }
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+        visible += 1;
+    }
+    out.push('…');
+    if ansi_re.is_match(s) {
+        out.push_str("\x1B[0m");
+    }
+    out
+}
+
+/// Renders rows as aligned columns under a header line.
+///
+/// When the table is wider than `max_width`, the `flex` column is shrunk
+/// (down to a minimum of 8 characters) and any overflow left after that is
+/// cut off at the end of each line.
+pub fn table(
+    headers: &[&str],
+    rows: &[Vec<String>],
+    flex: Option<usize>,
+    max_width: Option<usize>,
+) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = max(*w, visible_len(cell));
+        }
+    }
+
+    let separators = 2 * widths.len().saturating_sub(1);
+    let total = widths.iter().sum::<usize>() + separators;
+    if let (Some(max_width), Some(flex)) = (max_width, flex) {
+        if total > max_width && flex < widths.len() {
+            let excess = total - max_width;
+            widths[flex] = widths[flex].saturating_sub(excess).max(8.min(widths[flex]));
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        let s = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, w)| {
+                let cell = truncate(cell, *w);
+                let pad = w - visible_len(&cell);
+                cell + &" ".repeat(pad)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        let s = s.trim_end().to_owned();
+        match max_width {
+            Some(max_width) => truncate(&s, max_width),
+            None => s,
+        }
+    };
+
+    let header = line(
+        headers
+            .iter()
+            .map(|h| h.truecolor(128, 128, 128).bold().to_string())
+            .collect(),
+    );
+    let rule = "━"
+        .repeat(visible_len(&header))
+        .truecolor(64, 64, 64)
+        .to_string();
+
+    let mut out = vec![header, rule];
+    out.extend(rows.iter().map(|r| line(r.clone())));
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(row: &[&str]) -> Vec<String> {
+        row.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn truncate_ends_shortened_text_in_an_ellipsis() {
+        assert_eq!(truncate("short", 5), "short");
+        assert_eq!(truncate("longer text", 5), "long…");
+        assert_eq!(truncate("longer text", 0), "");
+        assert_eq!(truncate("ünïcödé", 4), "ünï…");
+    }
+
+    #[test]
+    fn truncate_keeps_colour_codes_and_resets_them() {
+        let cut = truncate("\x1B[1mbold text\x1B[0m", 5);
+        assert_eq!(cut, "\x1B[1mbold…\x1B[0m");
+        assert_eq!(visible_len(&cut), 5);
+    }
+
+    #[test]
+    fn table_shrinks_the_flex_column_to_fit() {
+        let rows = [cells(&["1", "a rather long task name", "2h"])];
+        let lines: Vec<String> = table(&["Id", "Name", "Left"], &rows, Some(1), Some(20))
+            .lines()
+            .map(strip_colors)
+            .collect();
+
+        assert_eq!(lines[2], "1   a rather …  2h");
+        assert!(lines.iter().all(|l| l.chars().count() <= 20));
+    }
+
+    #[test]
+    fn table_cuts_lines_once_the_flex_column_is_at_its_minimum() {
+        let rows = [cells(&["1", "a rather long task name", "in 3 days"])];
+        let table = table(&["Id", "Name", "Left"], &rows, Some(1), Some(16));
+        let row = strip_colors(table.lines().nth(2).unwrap());
+
+        assert_eq!(row, "1   a rathe…  i…");
+        assert_eq!(row.chars().count(), 16);
+    }
+
+    #[test]
+    fn table_is_left_alone_without_a_width() {
+        let rows = [cells(&["1", "a rather long task name", "2h"])];
+        let table = table(&["Id", "Name", "Left"], &rows, Some(1), None);
+        let row = strip_colors(table.lines().nth(2).unwrap());
+
+        assert_eq!(row, "1   a rather long task name  2h");
+    }
+}
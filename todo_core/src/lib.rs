@@ -0,0 +1,31 @@
+//! Task model, task file format and helpers shared by `todo-cli` and other
+//! tools working with its task files.
+//!
+//! ```no_run
+//! use todo_core::{Backend, Filter, TaskStore};
+//!
+//! let store = TaskStore::load("task_list".as_ref(), Backend::Binary)?;
+//! for task in store.query(&"overdue".parse::<Filter>()?) {
+//!     println!("{} {}", task.id(), task.name);
+//! }
+//! # Ok::<(), todo_core::Error>(())
+//! ```
+
+mod error;
+pub mod filter;
+pub mod format;
+pub mod merge;
+pub mod parse;
+pub mod search;
+pub mod sort;
+pub mod storage;
+pub mod store;
+pub mod task;
+pub mod uuid;
+
+pub use error::{Error, Result};
+pub use filter::Filter;
+pub use storage::{Backend, Storage};
+pub use store::TaskStore;
+pub use task::{Priority, Task};
+pub use uuid::Uuid;
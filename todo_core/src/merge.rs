@@ -0,0 +1,440 @@
+//! Three-way merging of task lists, for combining two copies of a task file
+//! that were changed independently since a common version.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    format::format_duration,
+    task::{format_timestamp, LogEntry, Task},
+    uuid::Uuid,
+    Result,
+};
+
+/// Which copy wins a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+/// A field changed differently in both copies, or a task removed in one
+/// copy and changed in the other (`field` is then `"removed"`).
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub id: i64,
+    /// Name of the task, as in our copy if it is still there.
+    pub name: String,
+    pub field: &'static str,
+    /// The field as shown to the user in each copy.
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// The result of [`merge`].
+#[derive(Debug, Clone, Default)]
+pub struct Merged {
+    pub tasks: Vec<Task>,
+    /// Tasks that would have shared an id with another, with that id and
+    /// the new id they got.
+    pub renumbered: Vec<(i64, i64)>,
+    /// The conflicts that had to be resolved.
+    pub conflicts: Vec<(Conflict, Side)>,
+}
+
+/// Merges the changes made in `ours` and `theirs` since `base`, task by task
+/// (matched by UUID) and field by field. Fields changed in only one copy take
+/// that change, progress logged in both copies is added up, and everything
+/// else is handed to `resolve`. Tasks that end up sharing an id, such as ones
+/// added in both copies, keep it in the order they were merged and the
+/// others get new ids.
+///
+/// Tasks of `base` and `theirs` stored before tasks had UUIDs are matched by
+/// id instead: they take the UUID of the task with their id in `ours`, or
+/// else in `base`.
+pub fn merge(
+    base: &[Task],
+    ours: &[Task],
+    theirs: &[Task],
+    mut resolve: impl FnMut(&Conflict) -> Result<Side>,
+) -> Result<Merged> {
+    let base = with_uuids(base, &[ours]);
+    let theirs = with_uuids(theirs, &[ours, &base]);
+    let (base, theirs) = (base.as_slice(), theirs.as_slice());
+
+    let base_by_uuid: HashMap<Uuid, &Task> = base.iter().map(|t| (t.uuid(), t)).collect();
+    let theirs_by_uuid: HashMap<Uuid, &Task> = theirs.iter().map(|t| (t.uuid(), t)).collect();
+    let ours_uuids: HashSet<Uuid> = ours.iter().map(|t| t.uuid()).collect();
+
+    let mut merged = Merged::default();
+    let mut decide = |conflict: Conflict, merged: &mut Merged| -> Result<Side> {
+        let side = resolve(&conflict)?;
+        merged.conflicts.push((conflict, side));
+        Ok(side)
+    };
+
+    for our in ours {
+        let uuid = our.uuid();
+        match (base_by_uuid.get(&uuid), theirs_by_uuid.get(&uuid)) {
+            (Some(base), Some(their)) => {
+                let task = merge_task(base, our, their, &mut |c| decide(c, &mut merged))?;
+                merged.tasks.push(task);
+            }
+            (Some(base), None) => {
+                // Removed in their copy.
+                if !same(base, our) {
+                    let conflict = removal(our, Side::Theirs);
+                    if decide(conflict, &mut merged)? == Side::Ours {
+                        merged.tasks.push(our.clone());
+                    }
+                }
+            }
+            (None, Some(their)) => {
+                // Added in both copies, which only happens to tasks from
+                // before UUIDs that were copied between unrelated files.
+                if same(our, their) {
+                    merged.tasks.push(our.clone());
+                } else {
+                    let conflict = Conflict {
+                        id: our.id(),
+                        name: our.name.clone(),
+                        field: "added",
+                        ours: summary(our),
+                        theirs: summary(their),
+                    };
+                    let kept = match decide(conflict, &mut merged)? {
+                        Side::Ours => our,
+                        Side::Theirs => their,
+                    };
+                    merged.tasks.push(kept.clone());
+                }
+            }
+            (None, None) => merged.tasks.push(our.clone()),
+        }
+    }
+
+    for their in theirs {
+        let uuid = their.uuid();
+        if ours_uuids.contains(&uuid) {
+            continue;
+        }
+        match base_by_uuid.get(&uuid) {
+            // Added in their copy.
+            None => merged.tasks.push(their.clone()),
+            // Removed in our copy.
+            Some(base) => {
+                if !same(base, their) {
+                    let conflict = removal(their, Side::Ours);
+                    if decide(conflict, &mut merged)? == Side::Theirs {
+                        merged.tasks.push(their.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut next = base
+        .iter()
+        .chain(ours)
+        .chain(theirs)
+        .map(|t| t.id())
+        .max()
+        .unwrap_or(0);
+    let mut used = HashSet::new();
+    for task in &mut merged.tasks {
+        if !used.insert(task.id()) {
+            next += 1;
+            merged.renumbered.push((task.id(), next));
+            task.set_id(next);
+            used.insert(next);
+        }
+    }
+    Ok(merged)
+}
+
+/// A task in a few words, for conflicts about the whole task.
+fn summary(task: &Task) -> String {
+    format!("{} due {}", task.name, task.format_due())
+}
+
+fn same(a: &Task, b: &Task) -> bool {
+    a.serialize() == b.serialize()
+}
+
+/// The conflict of `task` changed in one copy and removed in the other.
+fn removal(task: &Task, removed_in: Side) -> Conflict {
+    let (ours, theirs) = match removed_in {
+        Side::Ours => ("removed", "changed"),
+        Side::Theirs => ("changed", "removed"),
+    };
+    Conflict {
+        id: task.id(),
+        name: task.name.clone(),
+        field: "removed",
+        ours: ours.to_owned(),
+        theirs: theirs.to_owned(),
+    }
+}
+
+/// Merges the fields of a task present in all three copies.
+fn merge_task(
+    base: &Task,
+    ours: &Task,
+    theirs: &Task,
+    decide: &mut dyn FnMut(Conflict) -> Result<Side>,
+) -> Result<Task> {
+    let mut task = ours.clone();
+
+    // Which copy's value a field takes, if not ours.
+    let mut pick_field = |name: &'static str,
+                          changed: (bool, bool, bool),
+                          render: &dyn Fn(&Task) -> String|
+     -> Result<Option<Side>> {
+        let (ours_changed, theirs_changed, equal) = changed;
+        Ok(match (ours_changed, theirs_changed) {
+            (_, false) => None,
+            (false, true) => Some(Side::Theirs),
+            (true, true) if equal => None,
+            (true, true) => Some(decide(Conflict {
+                id: ours.id(),
+                name: ours.name.clone(),
+                field: name,
+                ours: render(ours),
+                theirs: render(theirs),
+            })?),
+        })
+    };
+
+    macro_rules! merge_field {
+        ($name:literal, $field:ident, $render:expr) => {
+            let changed = (
+                ours.$field != base.$field,
+                theirs.$field != base.$field,
+                ours.$field == theirs.$field,
+            );
+            if pick_field($name, changed, &$render)? == Some(Side::Theirs) {
+                task.$field = theirs.$field.clone();
+            }
+        };
+    }
+
+    // Either copy may have renumbered the task.
+    let changed = (
+        ours.id() != base.id(),
+        theirs.id() != base.id(),
+        ours.id() == theirs.id(),
+    );
+    if pick_field("id", changed, &|t: &Task| t.id().to_string())? == Some(Side::Theirs) {
+        task.set_id(theirs.id());
+    }
+    merge_field!("name", name, |t: &Task| t.name.clone());
+    merge_field!("description", description, |t: &Task| t.description.clone());
+    merge_field!("deadline", deadline, |t: &Task| t.format_due());
+    merge_field!("estimate", estimated_time, |t: &Task| format_duration(
+        t.estimated_time
+    ));
+    merge_field!("priority", priority, |t: &Task| t
+        .priority
+        .name()
+        .to_owned());
+    merge_field!("created", created, |t: &Task| format_timestamp(t.created));
+    merge_field!("completed", completed, |t: &Task| match t.completed {
+        0 => "open".to_owned(),
+        completed => format_timestamp(completed),
+    });
+    merge_field!("cancelled", cancelled, |t: &Task| match t.cancelled {
+        0 => "not cancelled".to_owned(),
+        cancelled => format_timestamp(cancelled),
+    });
+    merge_field!("overdue_handled", overdue_handled, |t: &Task| {
+        format_timestamp(t.overdue_handled)
+    });
+
+    // Progress logged in both copies adds up, as long as neither copy
+    // rewrote the entries they had in common.
+    if ours.log.starts_with(&base.log) && theirs.log.starts_with(&base.log) {
+        let ours_new = &ours.log[base.log.len()..];
+        let theirs_new = &theirs.log[base.log.len()..];
+        if ours_new != theirs_new {
+            let mut log: Vec<LogEntry> = ours.log.clone();
+            log.extend_from_slice(theirs_new);
+            task.log = log;
+            task.progress = ours.progress + theirs.progress - base.progress;
+            return Ok(task);
+        }
+    }
+    let changed = (
+        ours.progress != base.progress || ours.log != base.log,
+        theirs.progress != base.progress || theirs.log != base.log,
+        ours.progress == theirs.progress && ours.log == theirs.log,
+    );
+    if pick_field("progress", changed, &|t: &Task| format_duration(t.progress))?
+        == Some(Side::Theirs)
+    {
+        task.progress = theirs.progress;
+        task.log = theirs.log.clone();
+    }
+    Ok(task)
+}
+
+/// `tasks`, with those stored without a UUID given the UUID of the first task
+/// with the same id in `sources`, or a new one.
+fn with_uuids(tasks: &[Task], sources: &[&[Task]]) -> Vec<Task> {
+    let mut tasks = tasks.to_vec();
+    for task in tasks.iter_mut().filter(|t| t.uuid().is_nil()) {
+        let uuid = sources
+            .iter()
+            .find_map(|source| source.iter().find(|t| t.id() == task.id()))
+            .map_or_else(Uuid::new_v4, |t| t.uuid());
+        task.set_uuid(uuid);
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, name: &str) -> Task {
+        let mut task = Task::with_id(id);
+        task.name = name.to_owned();
+        task.deadline = 1_700_000_000;
+        task.estimated_time = 4 * 60 * 60;
+        task
+    }
+
+    fn no_conflicts(conflict: &Conflict) -> Result<Side> {
+        panic!("unexpected conflict on {}", conflict.field)
+    }
+
+    #[test]
+    fn takes_changes_made_on_one_side() {
+        let base = vec![task(0, "write report")];
+        let mut ours = base.clone();
+        ours[0].name = "write the report".into();
+        let mut theirs = base.clone();
+        theirs[0].deadline += 60;
+
+        let merged = merge(&base, &ours, &theirs, no_conflicts).unwrap();
+        assert_eq!(merged.tasks.len(), 1);
+        assert_eq!(merged.tasks[0].name, "write the report");
+        assert_eq!(merged.tasks[0].deadline, base[0].deadline + 60);
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn adds_up_progress_logged_on_both_sides() {
+        let mut base = vec![task(0, "read")];
+        base[0].log_progress(10 * 60);
+        let mut ours = base.clone();
+        ours[0].log_progress(20 * 60);
+        let mut theirs = base.clone();
+        theirs[0].log_progress(30 * 60);
+
+        let merged = merge(&base, &ours, &theirs, no_conflicts).unwrap();
+        assert_eq!(merged.tasks[0].progress, 60 * 60);
+        assert_eq!(merged.tasks[0].log.len(), 3);
+    }
+
+    #[test]
+    fn asks_about_fields_changed_differently() {
+        let base = vec![task(0, "plan")];
+        let mut ours = base.clone();
+        ours[0].name = "plan trip".into();
+        let mut theirs = base.clone();
+        theirs[0].name = "plan holiday".into();
+
+        let merged = merge(&base, &ours, &theirs, |conflict| {
+            assert_eq!(conflict.field, "name");
+            assert_eq!(conflict.ours, "plan trip");
+            assert_eq!(conflict.theirs, "plan holiday");
+            Ok(Side::Theirs)
+        })
+        .unwrap();
+        assert_eq!(merged.tasks[0].name, "plan holiday");
+        assert_eq!(merged.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn drops_tasks_removed_on_one_side_unless_changed_on_the_other() {
+        let base = vec![task(0, "a"), task(1, "b")];
+        let mut ours = base.clone();
+        ours[1].name = "b changed".into();
+        let theirs: Vec<Task> = Vec::new();
+
+        let merged = merge(&base, &ours, &theirs, |conflict| {
+            assert_eq!(conflict.field, "removed");
+            assert_eq!(conflict.id, 1);
+            Ok(Side::Ours)
+        })
+        .unwrap();
+        let names: Vec<&str> = merged.tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["b changed"]);
+    }
+
+    #[test]
+    fn renumbers_tasks_added_on_both_sides_with_the_same_id() {
+        let base = vec![task(0, "old")];
+        let mut ours = base.clone();
+        ours.push(task(1, "ours"));
+        let mut theirs = base.clone();
+        theirs.push(task(1, "theirs"));
+
+        let merged = merge(&base, &ours, &theirs, no_conflicts).unwrap();
+        let tasks: Vec<(i64, &str)> = merged
+            .tasks
+            .iter()
+            .map(|t| (t.id(), t.name.as_str()))
+            .collect();
+        assert_eq!(tasks, [(0, "old"), (1, "ours"), (2, "theirs")]);
+        assert_eq!(merged.renumbered, [(1, 2)]);
+    }
+
+    #[test]
+    fn follows_tasks_renumbered_on_one_side() {
+        let base = vec![task(0, "a"), task(3, "b")];
+        let mut ours = base.clone();
+        ours.remove(0);
+        ours[0].set_id(0);
+        let mut theirs = base.clone();
+        theirs[1].name = "b renamed".into();
+
+        let merged = merge(&base, &ours, &theirs, |conflict| {
+            assert_eq!(conflict.field, "removed");
+            Ok(Side::Ours)
+        })
+        .unwrap();
+        let tasks: Vec<(i64, &str)> = merged
+            .tasks
+            .iter()
+            .map(|t| (t.id(), t.name.as_str()))
+            .collect();
+        assert_eq!(tasks, [(0, "b renamed")]);
+        assert_eq!(merged.tasks[0].uuid(), base[1].uuid());
+    }
+
+    #[test]
+    fn matches_tasks_without_uuids_by_id() {
+        let legacy = |id, name| {
+            let mut task = Task::stored(id);
+            task.name = String::from(name);
+            task
+        };
+        let base = vec![legacy(0, "a"), legacy(1, "b")];
+        let mut theirs = base.clone();
+        theirs[1].name = "b renamed".into();
+        // Our side was loaded since, which gave its tasks UUIDs.
+        let ours: Vec<Task> = base
+            .iter()
+            .map(|t| {
+                let mut task = t.clone();
+                task.set_uuid(Uuid::new_v4());
+                task
+            })
+            .collect();
+
+        let merged = merge(&base, &ours, &theirs, no_conflicts).unwrap();
+        let names: Vec<&str> = merged.tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["a", "b renamed"]);
+        assert_eq!(merged.tasks[1].uuid(), ours[1].uuid());
+    }
+}
@@ -0,0 +1,317 @@
+//! Parsing of deadlines, durations, progress and field changes as typed by
+//! users, shared by the command line, the TUI and the HTTP API.
+
+use chrono::{Local, TimeZone};
+use regex::Regex;
+
+use crate::{
+    task::{Priority, Task},
+    Error, Result,
+};
+
+/// Unanchored pattern for a deadline, either a date with an optional time or
+/// just a time today.
+pub const DUE_PATTERN: &str = r"(\d{4}-\d{2}-\d{2})(?: (\d{2}:\d{2}:\d{2}))?|(\d{2}:\d{2}:\d{2})";
+
+/// Converts the groups captured by `DUE_PATTERN` into a timestamp. Missing
+/// parts default to the current date or time.
+pub fn parse_due(v: &[Option<String>]) -> Result<i64> {
+    let date = match &v[0] {
+        Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"),
+        None => Ok(Local::now().date_naive()),
+    }
+    .map_err(|_| Error::Input("Invalid date format".into()))?;
+
+    let time = match &v[1] {
+        Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
+        None => match &v[2] {
+            Some(time) => chrono::NaiveTime::parse_from_str(time, "%H:%M:%S"),
+            None => Ok(Local::now().time()),
+        },
+    }
+    .map_err(|_| Error::Input("Invalid time format".into()))?;
+
+    chrono::NaiveDateTime::new(date, time)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| Error::Input("No such local time".into()))
+}
+
+/// Pattern for a duration in weeks, days, hours, minutes and seconds, each
+/// optional, e.g. `1d 12h` or `2h 30m`. A bare number is seconds.
+pub const DURATION_PATTERN: &str =
+    r"^(?:(\d+)w\s*)?(?:(\d+)d\s*)?(?:(\d+)h\s*)?(?:(\d+)m\s*)?(?:(\d+)s?)?$";
+
+/// Seconds in each unit of `DURATION_PATTERN`, in the order of its groups.
+const DURATION_UNITS: [i64; 5] = [7 * 86400, 86400, 3600, 60, 1];
+
+/// Converts the groups captured by `DURATION_PATTERN` into seconds.
+pub fn parse_duration(v: &[Option<String>]) -> Result<i64> {
+    v.iter()
+        .zip(DURATION_UNITS)
+        .try_fold(0i64, |total, (part, unit)| {
+            let n = part.as_deref().unwrap_or("0").parse::<i64>().ok()?;
+            total.checked_add(n.checked_mul(unit)?)
+        })
+        .ok_or_else(|| Error::Parse("Invalid duration".into()))
+}
+
+/// Parses a duration such as `1d`, `2h 30m` or `90`, as given to options
+/// and settings.
+pub fn duration(input: &str) -> Result<i64> {
+    let input = input.trim();
+    let re = Regex::new(DURATION_PATTERN).map_err(|e| Error::Input(e.to_string()))?;
+    if input.is_empty() || !re.is_match(input) {
+        return Err(Error::Input(format!(
+            "Invalid duration \"{input}\" (expected e.g. 1d, 2h 30m or 15m)"
+        )));
+    }
+    parse_input(input, &re, |v| parse_duration(&v))
+}
+
+/// Matches `input` against `regex` and hands the capture groups to `f`.
+pub fn parse_input<V, F>(input: &str, regex: &Regex, f: F) -> Result<V>
+where
+    F: Fn(Vec<Option<String>>) -> Result<V>,
+{
+    let captures = regex
+        .captures(input.trim())
+        .ok_or_else(|| Error::Input("Invalid format, please try again".into()))?;
+
+    f(captures
+        .iter()
+        .skip(1)
+        .map(|x| x.map(|x| x.as_str().to_owned()))
+        .collect())
+}
+
+/// Parses progress made on `task`: a duration such as `1h 30m`, or a
+/// percentage of its estimate such as `25%`.
+pub fn parse_progress(input: &str, task: &Task) -> Result<i64> {
+    let time_re = Regex::new(DURATION_PATTERN).map_err(|e| Error::Input(e.to_string()))?;
+
+    let percent_re = Regex::new(r"^(\d+)%$").map_err(|e| Error::Input(e.to_string()))?;
+
+    if time_re.is_match(input) {
+        parse_input(input, &time_re, |v| parse_duration(&v))
+    } else if let Some(caps) = percent_re.captures(input) {
+        let percent = caps[1]
+            .parse::<f32>()
+            .map_err(|_| Error::Parse("Invalid percentage".into()))?;
+        Ok((task.estimated_time as f32 * (percent / 100.0)).round() as i64)
+    } else {
+        Err(Error::Input("Invalid progress format".into()))
+    }
+}
+
+/// Parses a new deadline for a task currently due at `deadline`: either a
+/// date as accepted by `add`, or `+` and a duration such as `+2d` or
+/// `+1w 4h`.
+///
+/// Offsets count from the deadline, or, once it has passed, from the same
+/// time of day today.
+pub fn parse_when(input: &str, deadline: i64) -> Result<i64> {
+    let due_re =
+        Regex::new(&format!("^(?:{DUE_PATTERN})$")).map_err(|e| Error::Input(e.to_string()))?;
+    let input = input.trim();
+
+    if let Some(offset) = input.strip_prefix('+') {
+        let offset = duration(offset).map_err(|_| {
+            Error::Input(format!(
+                "Invalid offset \"{input}\" (expected e.g. +2d or +1w 4h)"
+            ))
+        })?;
+        return base(deadline)
+            .checked_add(offset)
+            .ok_or_else(|| Error::Parse("Invalid offset".into()));
+    }
+
+    if !due_re.is_match(input) {
+        return Err(Error::Input(format!(
+            "Invalid deadline \"{input}\" (expected e.g. +2d or YYYY-MM-DD HH:MM:SS)"
+        )));
+    }
+    parse_input(input, &due_re, |v| parse_due(&v))
+}
+
+/// Where offsets count from: the deadline if still ahead, else the
+/// deadline's time of day today, or now if it has none.
+fn base(deadline: i64) -> i64 {
+    let now = Local::now();
+    if deadline >= now.timestamp() {
+        return deadline;
+    }
+    Local
+        .timestamp_opt(deadline, 0)
+        .single()
+        .and_then(|d| {
+            now.date_naive()
+                .and_time(d.time())
+                .and_local_timezone(Local)
+                .earliest()
+        })
+        .map_or(now.timestamp(), |t| t.timestamp())
+}
+
+/// A change to one field of a task, given as `field=value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Name(String),
+    Description(String),
+    Priority(Priority),
+    Estimate(i64),
+    /// A new deadline, as accepted by [`parse_when`].
+    Due(String),
+    /// Completes the task, or reopens it if unset.
+    Completed(bool),
+    Cancelled,
+}
+impl Field {
+    pub const NAMES: [&'static str; 6] = [
+        "name",
+        "description",
+        "priority",
+        "estimate",
+        "due",
+        "status",
+    ];
+
+    pub fn parse(assignment: &str) -> Result<Self> {
+        let (key, value) = assignment.split_once('=').ok_or_else(|| {
+            Error::Input(format!(
+                "Invalid assignment \"{assignment}\" (expected field=value)"
+            ))
+        })?;
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "name" if value.is_empty() => Err(Error::Input("Name can't be empty".into())),
+            "name" => Ok(Field::Name(value.to_owned())),
+            "description" => Ok(Field::Description(value.to_owned())),
+            "priority" => Ok(Field::Priority(value.parse()?)),
+            "estimate" => duration(value).map(Field::Estimate).map_err(|_| {
+                Error::Input(format!(
+                    "Invalid estimate \"{value}\" (expected e.g. 2h 30m)"
+                ))
+            }),
+            "due" => {
+                // Validate now rather than halfway through the tasks.
+                parse_when(value, Local::now().timestamp())?;
+                Ok(Field::Due(value.to_owned()))
+            }
+            "status" => match value.to_lowercase().as_str() {
+                "open" => Ok(Field::Completed(false)),
+                "completed" | "done" => Ok(Field::Completed(true)),
+                "cancelled" | "canceled" => Ok(Field::Cancelled),
+                other => Err(Error::Input(format!(
+                    "Unknown status \"{other}\" (expected open, completed or cancelled)"
+                ))),
+            },
+            other => Err(Error::Input(format!(
+                "Unknown field \"{other}\" (expected one of: {})",
+                Field::NAMES.join(", ")
+            ))),
+        }
+    }
+
+    pub fn apply(&self, task: &mut Task) -> Result<()> {
+        match self {
+            Field::Name(name) => task.name = name.clone(),
+            Field::Description(description) => task.description = description.clone(),
+            Field::Priority(priority) => task.priority = *priority,
+            Field::Estimate(estimate) => task.estimated_time = *estimate,
+            Field::Due(when) => task.deadline = parse_when(when, task.deadline)?,
+            Field::Completed(true) => {
+                task.cancelled = 0;
+                if !task.is_completed() {
+                    task.completed = Local::now().timestamp();
+                }
+            }
+            Field::Completed(false) => {
+                task.completed = 0;
+                task.cancelled = 0;
+            }
+            Field::Cancelled => {
+                task.completed = 0;
+                if !task.is_cancelled() {
+                    task.cancelled = Local::now().timestamp();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_a_duration_or_a_share_of_the_estimate() {
+        let mut task = Task::new();
+        task.estimated_time = 4 * 3600;
+        assert_eq!(parse_progress("1h 30m", &task).unwrap(), 5400);
+        assert_eq!(parse_progress("1d", &task).unwrap(), 86400);
+        assert_eq!(parse_progress("25%", &task).unwrap(), 3600);
+        assert!(parse_progress("soon", &task).is_err());
+    }
+
+    #[test]
+    fn durations_share_one_grammar() {
+        assert_eq!(duration("1d").unwrap(), 86400);
+        assert_eq!(duration("1w 2d 3h 4m 5s").unwrap(), 788_645);
+        assert_eq!(duration("2h30m").unwrap(), 9000);
+        assert_eq!(duration("90").unwrap(), 90);
+        assert_eq!(duration("0").unwrap(), 0);
+        for invalid in ["", "2x", "1h 1d", "99999999999999999w"] {
+            assert!(duration(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn offsets_count_from_a_future_deadline() {
+        let deadline = Local::now().timestamp() + 86400;
+        assert_eq!(parse_when("+2d", deadline).unwrap(), deadline + 2 * 86400);
+        assert_eq!(
+            parse_when("+1w 4h", deadline).unwrap(),
+            deadline + 7 * 86400 + 4 * 3600
+        );
+        assert_eq!(parse_when("+90m", deadline).unwrap(), deadline + 5400);
+        assert!(parse_when("+", deadline).is_err());
+
+        assert!(parse_when("tomorrow", deadline).is_err());
+    }
+
+    #[test]
+    fn offsets_from_a_deadline_out_of_range_count_from_now() {
+        let before = Local::now().timestamp();
+        let moved = parse_when("+1h", -i64::MAX / 2).unwrap();
+        assert!((before + 3600..=Local::now().timestamp() + 3600).contains(&moved));
+    }
+
+    #[test]
+    fn fields_are_parsed_and_applied() {
+        let mut task = Task::new();
+        for assignment in ["name = pay rent", "estimate=2h 30m", "priority=high"] {
+            Field::parse(assignment).unwrap().apply(&mut task).unwrap();
+        }
+        assert_eq!(task.name, "pay rent");
+        assert_eq!(task.estimated_time, 9000);
+        assert_eq!(task.priority, Priority::High);
+
+        Field::parse("status=cancelled")
+            .unwrap()
+            .apply(&mut task)
+            .unwrap();
+        assert!(task.is_cancelled() && !task.is_open());
+        Field::parse("status=open")
+            .unwrap()
+            .apply(&mut task)
+            .unwrap();
+        assert!(task.is_open());
+
+        assert!(Field::parse("name=").is_err());
+        assert!(Field::parse("colour=red").is_err());
+        assert!(Field::parse("estimate=soon").is_err());
+    }
+}
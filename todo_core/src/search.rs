@@ -0,0 +1,201 @@
+use crate::task::Task;
+
+/// How well a query matched a task, with the matched character positions in
+/// the name and description for highlighting.
+#[derive(Debug, Clone, Default)]
+pub struct Match {
+    pub score: i64,
+    pub name: Vec<usize>,
+    pub description: Vec<usize>,
+    /// Every query term appears verbatim in the name.
+    pub exact_name: bool,
+}
+
+/// Matches `term` against `text`, ignoring case. The positions are those of
+/// the matched characters in `text`.
+///
+/// A verbatim occurrence scores highest; otherwise the characters of `term`
+/// must appear in order, with consecutive characters and characters at the
+/// start of a word scoring extra and gaps costing a little.
+pub fn fuzzy_match(term: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let term: Vec<char> = term.to_lowercase().chars().collect();
+    // Some characters lowercase to several, so each lowercased character
+    // keeps the position of the one it came from.
+    let (text, origin): (Vec<char>, Vec<usize>) = text
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| c.to_lowercase().map(move |l| (l, i)))
+        .unzip();
+    if term.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    if let Some(start) = text.windows(term.len()).position(|w| w == term.as_slice()) {
+        let word_start = start == 0 || !text[start - 1].is_alphanumeric();
+        let score = 100 + 10 * term.len() as i64 + if word_start { 20 } else { 0 };
+        let mut positions: Vec<usize> = origin[start..start + term.len()].to_vec();
+        positions.dedup();
+        return Some((score, positions));
+    }
+
+    let mut positions = Vec::with_capacity(term.len());
+    let mut score = 0;
+    let mut next = 0;
+    for c in &term {
+        let found = next + text[next..].iter().position(|t| t == c)?;
+
+        score += 1;
+        if next > 0 && found == next {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - next).min(5) as i64;
+
+        positions.push(origin[found]);
+        next = found + 1;
+    }
+    positions.dedup();
+    Some((score, positions))
+}
+
+/// Matches every whitespace separated term of `query` against the name and
+/// description of `task`. Name matches count double.
+pub fn match_task(query: &str, task: &Task) -> Option<Match> {
+    let mut result = Match {
+        exact_name: true,
+        ..Default::default()
+    };
+
+    for term in query.split_whitespace() {
+        let name = fuzzy_match(term, &task.name);
+        let description = fuzzy_match(term, &task.description);
+
+        let name_score = name.as_ref().map(|(s, _)| s * 2);
+        let description_score = description.as_ref().map(|(s, _)| *s);
+        result.score += name_score.max(description_score)?;
+        result.exact_name &= task.name.to_lowercase().contains(&term.to_lowercase());
+
+        if let Some((_, positions)) = name {
+            result.name.extend(positions);
+        }
+        if let Some((_, positions)) = description {
+            result.description.extend(positions);
+        }
+    }
+
+    result.name.sort_unstable();
+    result.name.dedup();
+    result.description.sort_unstable();
+    result.description.dedup();
+    Some(result)
+}
+
+/// Every open task matching `query`, best match first.
+pub fn search<'a>(query: &str, tasks: &'a [Task]) -> Vec<(&'a Task, Match)> {
+    let mut results: Vec<_> = tasks
+        .iter()
+        .filter(|t| t.is_open())
+        .filter_map(|t| match_task(query, t).map(|m| (t, m)))
+        .collect();
+    results.sort_by(|(a, ma), (b, mb)| mb.score.cmp(&ma.score).then(a.id().cmp(&b.id())));
+    results
+}
+
+/// The single task `query` refers to, if it is unambiguous: either the only
+/// match, or the only task whose name contains every term verbatim.
+///
+/// On ambiguity the candidates are returned as the error.
+pub fn unique_match<'a>(query: &str, tasks: &'a [Task]) -> Result<Option<&'a Task>, Vec<&'a Task>> {
+    let results = search(query, tasks);
+    if results.len() <= 1 {
+        return Ok(results.first().map(|(t, _)| *t));
+    }
+
+    let exact: Vec<_> = results.iter().filter(|(_, m)| m.exact_name).collect();
+    match exact.as_slice() {
+        [(task, _)] => Ok(Some(*task)),
+        _ => Err(results.into_iter().map(|(t, _)| t).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(names: &[&str]) -> Vec<Task> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                let mut task = Task::with_id(id as i64);
+                task.name = name.to_string();
+                task
+            })
+            .collect()
+    }
+
+    fn ids(tasks: &[&Task]) -> Vec<i64> {
+        tasks.iter().map(|t| t.id()).collect()
+    }
+
+    #[test]
+    fn verbatim_matches_beat_scattered_ones() {
+        let (verbatim, positions) = fuzzy_match("Rep", "write report").unwrap();
+        assert_eq!(positions, [6, 7, 8]);
+
+        let (scattered, positions) = fuzzy_match("wrt", "write report").unwrap();
+        assert_eq!(positions, [0, 1, 3]);
+        assert!(verbatim > scattered);
+
+        assert_eq!(fuzzy_match("tw", "write report"), None);
+    }
+
+    #[test]
+    fn positions_are_those_of_the_original_characters() {
+        // "İ" lowercases to "i" and a combining dot.
+        let (_, positions) = fuzzy_match("stan", "İstanbul").unwrap();
+        assert_eq!(positions, [1, 2, 3, 4]);
+        let (_, positions) = fuzzy_match("sb", "İstanbul").unwrap();
+        assert_eq!(positions, [1, 5]);
+        let (_, positions) = fuzzy_match("i̇s", "İstanbul").unwrap();
+        assert_eq!(positions, [0, 1]);
+        let (_, positions) = fuzzy_match("il", "İstanbul").unwrap();
+        assert_eq!(positions, [0, 7]);
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        let task = &tasks(&["write report"])[0];
+        assert!(match_task("write rep", task).is_some());
+        assert!(match_task("write invoice", task).is_none());
+    }
+
+    #[test]
+    fn search_ranks_the_best_match_first() {
+        let tasks = tasks(&["syntax notes", "tax return", "pay rent"]);
+        let results: Vec<_> = search("tax", &tasks).into_iter().map(|(t, _)| t).collect();
+        assert_eq!(ids(&results), [1, 0]);
+    }
+
+    #[test]
+    fn unique_match_needs_a_single_candidate() {
+        let tasks = tasks(&["write report", "write invoice", "pay rent"]);
+
+        assert_eq!(unique_match("rent", &tasks).unwrap().unwrap().id(), 2);
+        assert!(unique_match("holiday", &tasks).unwrap().is_none());
+
+        let candidates = unique_match("write", &tasks).unwrap_err();
+        assert_eq!(ids(&candidates), [0, 1]);
+    }
+
+    #[test]
+    fn unique_match_prefers_the_only_verbatim_name() {
+        let tasks = tasks(&["write report", "wrap presents"]);
+
+        assert_eq!(unique_match("report", &tasks).unwrap().unwrap().id(), 0);
+        let candidates = unique_match("wr", &tasks).unwrap_err();
+        assert_eq!(candidates.len(), 2);
+    }
+}
@@ -0,0 +1,91 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::{task::Task, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Deadline,
+    Remaining,
+    Completion,
+    Estimate,
+    Id,
+    Name,
+    Urgency,
+    /// Highest priority first.
+    Priority,
+}
+impl SortKey {
+    pub const NAMES: [&'static str; 8] = [
+        "deadline",
+        "remaining",
+        "completion",
+        "estimate",
+        "id",
+        "name",
+        "urgency",
+        "priority",
+    ];
+
+    pub fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        match self {
+            SortKey::Deadline => a.deadline.cmp(&b.deadline),
+            SortKey::Remaining => a.get_remaining().cmp(&b.get_remaining()),
+            SortKey::Completion => a.get_completion().total_cmp(&b.get_completion()),
+            SortKey::Estimate => a.estimated_time.cmp(&b.estimated_time),
+            SortKey::Id => a.id().cmp(&b.id()),
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Urgency => a.get_slack().cmp(&b.get_slack()),
+            SortKey::Priority => b.priority.cmp(&a.priority),
+        }
+    }
+}
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "deadline" | "due" => Ok(SortKey::Deadline),
+            "remaining" => Ok(SortKey::Remaining),
+            "completion" | "progress" => Ok(SortKey::Completion),
+            "estimate" => Ok(SortKey::Estimate),
+            "id" => Ok(SortKey::Id),
+            "name" => Ok(SortKey::Name),
+            "urgency" => Ok(SortKey::Urgency),
+            "priority" => Ok(SortKey::Priority),
+            other => Err(Error::Input(format!(
+                "Unknown sort key \"{other}\" (expected one of: {})",
+                SortKey::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Parses a comma separated list of sort keys, e.g. `deadline,name`.
+pub fn parse_keys(s: &str) -> Result<Vec<SortKey>, Error> {
+    s.split(',')
+        .filter(|k| !k.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Sorts by each key in turn, falling back to the id so the order is stable.
+pub fn sort_tasks(tasks: &mut [Task], keys: &[SortKey], reverse: bool) {
+    tasks.sort_by(|a, b| compare_tasks(a, b, keys, reverse));
+}
+
+/// The order [`sort_tasks`] puts `a` and `b` in, for sorting tasks held
+/// alongside other data.
+pub fn compare_tasks(a: &Task, b: &Task, keys: &[SortKey], reverse: bool) -> Ordering {
+    let ordering = keys
+        .iter()
+        .chain([SortKey::Id].iter())
+        .map(|k| k.compare(a, b))
+        .find(|o| *o != Ordering::Equal)
+        .unwrap_or(Ordering::Equal);
+
+    if reverse {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
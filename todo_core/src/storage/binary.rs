@@ -0,0 +1,120 @@
+use std::{
+    fs::{remove_file, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{Changes, Storage};
+use crate::{
+    task::{Task, FILE_HEADER},
+    Error, Result,
+};
+
+/// The original task file: a header followed by one binary record per task.
+///
+/// Records can only be appended, so every other change rewrites the file.
+pub struct BinaryFile {
+    path: PathBuf,
+}
+impl BinaryFile {
+    pub fn new(path: &Path) -> Self {
+        BinaryFile {
+            path: path.to_owned(),
+        }
+    }
+}
+impl Storage for BinaryFile {
+    fn load(&mut self) -> Result<Vec<Task>> {
+        if self.path.exists() {
+            read_tasks(&self.path)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn add(&mut self, task: &Task) -> Result<()> {
+        save_tasks(std::slice::from_ref(task), &self.path, false)
+    }
+
+    fn save(&mut self, tasks: &[Task], changes: &Changes) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        persist_tasks(tasks, &self.path)
+    }
+}
+
+/// Writes `tasks` to the task file, replacing its contents when `overwrite`
+/// is set and appending to it otherwise.
+pub fn save_tasks(tasks: &[Task], file_path: &Path, overwrite: bool) -> Result<()> {
+    // Appending to a legacy file would mix record formats, so upgrade it first.
+    if !overwrite && file_path.exists() && !has_header(file_path)? {
+        let mut all = read_tasks(file_path)?;
+        all.extend_from_slice(tasks);
+        return save_tasks(&all, file_path, true);
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .append(!overwrite)
+        .create(true)
+        .truncate(overwrite)
+        .open(file_path)
+        .map_err(Error::Io)?;
+
+    if overwrite || file.metadata()?.len() == 0 {
+        file.write_all(FILE_HEADER).map_err(Error::Io)?;
+    }
+
+    for task in tasks {
+        file.write_all(&task.serialize()).map_err(Error::Io)?;
+    }
+    Ok(())
+}
+
+/// Overwrites the task file with `tasks`, removing it once no tasks are left.
+pub fn persist_tasks(tasks: &[Task], file_path: &Path) -> Result<()> {
+    if tasks.is_empty() {
+        if file_path.exists() {
+            remove_file(file_path)?;
+        }
+        Ok(())
+    } else {
+        save_tasks(tasks, file_path, true)
+    }
+}
+
+/// Whether the task file starts with [`FILE_HEADER`].
+pub fn has_header(file_path: &Path) -> Result<bool> {
+    let mut header = [0; FILE_HEADER.len()];
+    let mut f = File::open(file_path).map_err(Error::Io)?;
+    match f.read_exact(&mut header) {
+        Ok(()) => Ok(&header == FILE_HEADER),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(Error::Io(e)),
+    }
+}
+
+/// Reads every task of the task file, in either record format.
+pub fn read_tasks(file_path: &Path) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+    let extended = has_header(file_path)?;
+    let f = File::open(file_path).map_err(Error::Io)?;
+
+    let total_size = f.metadata()?.len();
+    let mut br = BufReader::new(f);
+    if extended {
+        br.seek_relative(FILE_HEADER.len() as i64)?;
+    }
+
+    while br.stream_position()? < total_size {
+        let task = if extended {
+            Task::from_extended(&mut br)
+        } else {
+            Task::from(&mut br)
+        };
+        tasks.push(task.map_err(|_| Error::InvalidFileFormat)?);
+    }
+
+    Ok(tasks)
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng, Payload},
+    Key, KeyInit, XChaCha20Poly1305, XNonce,
+};
+
+use super::{Changes, Storage};
+use crate::{
+    task::{Task, FILE_HEADER},
+    Error, Result,
+};
+
+/// Written at the start of encrypted task files.
+pub const ENCRYPTED_HEADER: &[u8; 4] = b"TDE\x01";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Highest Argon2 memory (in KiB), time and parallelism costs accepted from
+/// a file, well above the defaults new files are written with, so a crafted
+/// header can't make reading the file take any amount of memory or time.
+const MAX_M_COST: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+/// Header, the three Argon2 parameters, salt and nonce.
+const PREAMBLE_LEN: usize = ENCRYPTED_HEADER.len() + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+/// Supplies the passphrases of encrypted files.
+pub trait PassphraseProvider: Send + Sync {
+    /// The passphrase of the encrypted file at `path`. `new` is set when the
+    /// passphrase is for a file about to be created.
+    fn passphrase(&self, path: &Path, new: bool) -> Result<String>;
+
+    /// Called once a passphrase from [`PassphraseProvider::passphrase`]
+    /// decrypted the file at `path`, or encrypted it when new, so only
+    /// passphrases known to be right are kept.
+    fn accepted(&self, _path: &Path, _passphrase: &str) {}
+}
+
+static PROVIDER: OnceLock<Box<dyn PassphraseProvider>> = OnceLock::new();
+/// Passphrases asked for ahead of reading, by path, each kept for the next
+/// read of its file only.
+static AHEAD: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
+
+/// Sets where [`Encrypted`] storage without a passphrase of its own gets
+/// one. Only the first call has an effect.
+pub fn set_passphrase_provider(provider: Box<dyn PassphraseProvider>) {
+    let _ = PROVIDER.set(provider);
+}
+
+/// Asks the passphrase provider for the passphrase of the existing encrypted
+/// file at `path` ahead of reading it, so that a prompt for it can come
+/// before the file is locked.
+pub fn request_passphrase(path: &Path) -> Result<()> {
+    let passphrase = Encrypted::new(path).passphrase(false)?;
+    AHEAD
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(path.to_owned(), passphrase);
+    Ok(())
+}
+
+/// The task records, encrypted as a whole with XChaCha20-Poly1305 under a
+/// key derived from a passphrase with Argon2id.
+///
+/// Layout: the header, the Argon2 memory, time and parallelism costs (each
+/// a big-endian `u32`), the salt, the nonce and the ciphertext of a binary
+/// task file. Everything before the ciphertext is authenticated with it, so
+/// tampering with any byte fails decryption. Every save writes a new nonce.
+pub struct Encrypted {
+    path: PathBuf,
+    passphrase: Option<String>,
+    /// The key and the salt and parameters it was derived with, kept after
+    /// the first load or save so the passphrase is only stretched once.
+    key: Option<(Key, [u8; SALT_LEN], Params)>,
+}
+impl Encrypted {
+    /// Storage that asks the passphrase provider for the passphrase.
+    pub fn new(path: &Path) -> Self {
+        Encrypted {
+            path: path.to_owned(),
+            passphrase: None,
+            key: None,
+        }
+    }
+
+    pub fn with_passphrase(path: &Path, passphrase: &str) -> Self {
+        Encrypted {
+            passphrase: Some(passphrase.to_owned()),
+            ..Encrypted::new(path)
+        }
+    }
+
+    /// Re-encrypts the file under `passphrase` with a fresh salt, returning
+    /// the number of tasks in it.
+    pub fn rekey(&mut self, passphrase: &str) -> Result<usize> {
+        let tasks = self.load()?;
+        self.passphrase = Some(passphrase.to_owned());
+        self.key = None;
+        self.write(&tasks)?;
+        Ok(tasks.len())
+    }
+
+    fn passphrase(&self, new: bool) -> Result<String> {
+        if let Some(passphrase) = &self.passphrase {
+            return Ok(passphrase.clone());
+        }
+        let ahead = AHEAD
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|a| a.remove(&self.path));
+        if let Some(passphrase) = ahead {
+            return Ok(passphrase);
+        }
+        match PROVIDER.get() {
+            Some(provider) => provider.passphrase(&self.path, new),
+            None => Err(Error::Input(format!(
+                "{} is encrypted and no passphrase was given",
+                self.path.display()
+            ))),
+        }
+    }
+
+    /// Tells the passphrase provider that `passphrase` worked, unless it
+    /// came from elsewhere.
+    fn accept(&self, passphrase: &str) {
+        if let (None, Some(provider)) = (&self.passphrase, PROVIDER.get()) {
+            provider.accepted(&self.path, passphrase);
+        }
+    }
+
+    /// The key for new writes, derived with a fresh salt if none is known,
+    /// along with the passphrase it was derived from in that case.
+    fn key(&mut self) -> Result<(Key, [u8; SALT_LEN], Params, Option<String>)> {
+        if let Some((key, salt, params)) = &self.key {
+            return Ok((*key, *salt, params.clone(), None));
+        }
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let params = Params::default();
+        let passphrase = self.passphrase(!self.path.exists())?;
+        let key = derive(&passphrase, &salt, &params)?;
+        self.key = Some((key, salt, params.clone()));
+        Ok((key, salt, params, Some(passphrase)))
+    }
+
+    fn write(&mut self, tasks: &[Task]) -> Result<()> {
+        let (key, salt, params, passphrase) = self.key()?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let mut bytes = Vec::from(*ENCRYPTED_HEADER);
+        for cost in [params.m_cost(), params.t_cost(), params.p_cost()] {
+            bytes.extend_from_slice(&cost.to_be_bytes());
+        }
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+
+        let mut plaintext = Vec::from(*FILE_HEADER);
+        for task in tasks {
+            plaintext.extend(task.serialize());
+        }
+        let ciphertext = XChaCha20Poly1305::new(&key)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: &bytes,
+                },
+            )
+            .map_err(|_| Error::Storage("Encryption failed".into()))?;
+        bytes.extend(ciphertext);
+
+        // Write beside the file and swap, so it is never half written.
+        let temporary = self.path.with_extension("saving");
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, &self.path)?;
+        if let Some(passphrase) = passphrase {
+            self.accept(&passphrase);
+        }
+        Ok(())
+    }
+}
+impl Storage for Encrypted {
+    fn load(&mut self) -> Result<Vec<Task>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let bytes = fs::read(&self.path)?;
+        if bytes.len() < PREAMBLE_LEN || !bytes.starts_with(ENCRYPTED_HEADER) {
+            return Err(Error::InvalidFileFormat);
+        }
+        let (preamble, ciphertext) = bytes.split_at(PREAMBLE_LEN);
+
+        let cost = |i: usize| {
+            let start = ENCRYPTED_HEADER.len() + i * 4;
+            u32::from_be_bytes(preamble[start..start + 4].try_into().unwrap())
+        };
+        let (m_cost, t_cost, p_cost) = (cost(0), cost(1), cost(2));
+        if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+            return Err(Error::InvalidFileFormat);
+        }
+        let params =
+            Params::new(m_cost, t_cost, p_cost, None).map_err(|_| Error::InvalidFileFormat)?;
+        let salt_start = ENCRYPTED_HEADER.len() + 3 * 4;
+        let salt: [u8; SALT_LEN] = preamble[salt_start..salt_start + SALT_LEN]
+            .try_into()
+            .unwrap();
+        let nonce = XNonce::from_slice(&preamble[salt_start + SALT_LEN..]);
+
+        let (key, passphrase) = match &self.key {
+            Some((key, known_salt, _)) if *known_salt == salt => (*key, None),
+            _ => {
+                let passphrase = self.passphrase(false)?;
+                (derive(&passphrase, &salt, &params)?, Some(passphrase))
+            }
+        };
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: preamble,
+                },
+            )
+            .map_err(|_| {
+                Error::Input(format!(
+                    "Wrong passphrase for {}, or the file was tampered with",
+                    self.path.display()
+                ))
+            })?;
+        self.key = Some((key, salt, params));
+        if let Some(passphrase) = passphrase {
+            self.accept(&passphrase);
+        }
+
+        let mut records = plaintext
+            .strip_prefix(FILE_HEADER)
+            .ok_or(Error::InvalidFileFormat)?;
+        let mut tasks = Vec::new();
+        while !records.is_empty() {
+            tasks.push(Task::from_extended(&mut records).map_err(|_| Error::InvalidFileFormat)?);
+        }
+        Ok(tasks)
+    }
+
+    fn add(&mut self, task: &Task) -> Result<()> {
+        // The file is authenticated as a whole, so adding rewrites it.
+        let mut tasks = self.load()?;
+        tasks.push(task.clone());
+        self.write(&tasks)
+    }
+
+    fn save(&mut self, tasks: &[Task], changes: &Changes) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.write(tasks)
+    }
+}
+
+fn derive(passphrase: &str, salt: &[u8], params: &Params) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::Storage(format!("Key derivation failed: {e}")))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+
+    fn sealed(name: &str, passphrase: &str) -> (PathBuf, Vec<Task>) {
+        let path = temp_path(name);
+        let mut task = Task::with_id(0);
+        task.name = "plan the surprise party".into();
+        task.log_progress(15 * 60);
+        let tasks = vec![task, Task::with_id(1)];
+        Encrypted::with_passphrase(&path, passphrase)
+            .write(&tasks)
+            .unwrap();
+        (path, tasks)
+    }
+
+    fn serialized(tasks: &[Task]) -> Vec<Vec<u8>> {
+        tasks.iter().map(Task::serialize).collect()
+    }
+
+    #[test]
+    fn reads_back_what_it_wrote() {
+        let (path, tasks) = sealed("round-trip.secret", "correct horse");
+        let loaded = Encrypted::with_passphrase(&path, "correct horse")
+            .load()
+            .unwrap();
+        assert_eq!(serialized(&loaded), serialized(&tasks));
+
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(ENCRYPTED_HEADER));
+        assert!(!bytes.windows(8).any(|w| w == b"surprise"));
+    }
+
+    #[test]
+    fn refuses_a_wrong_passphrase() {
+        let (path, _) = sealed("wrong.secret", "correct horse");
+        let loaded = Encrypted::with_passphrase(&path, "wrong horse").load();
+        assert!(matches!(loaded, Err(Error::Input(_))));
+    }
+
+    #[test]
+    fn refuses_tampered_files() {
+        let (path, _) = sealed("tampered.secret", "correct horse");
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        let loaded = Encrypted::with_passphrase(&path, "correct horse").load();
+        assert!(matches!(loaded, Err(Error::Input(_))));
+    }
+
+    #[test]
+    fn rekeying_replaces_the_passphrase() {
+        let (path, tasks) = sealed("rekey.secret", "correct horse");
+        let rekeyed = Encrypted::with_passphrase(&path, "correct horse")
+            .rekey("battery staple")
+            .unwrap();
+        assert_eq!(rekeyed, tasks.len());
+
+        assert!(Encrypted::with_passphrase(&path, "correct horse")
+            .load()
+            .is_err());
+        let loaded = Encrypted::with_passphrase(&path, "battery staple")
+            .load()
+            .unwrap();
+        assert_eq!(serialized(&loaded), serialized(&tasks));
+    }
+
+    #[test]
+    fn refuses_costs_above_the_maximum() {
+        let (path, _) = sealed("costs.secret", "correct horse");
+        let bytes = fs::read(&path).unwrap();
+        for (i, cost) in [MAX_M_COST, MAX_T_COST, MAX_P_COST].into_iter().enumerate() {
+            let mut crafted = bytes.clone();
+            let start = ENCRYPTED_HEADER.len() + i * 4;
+            crafted[start..start + 4].copy_from_slice(&(cost + 1).to_be_bytes());
+            fs::write(&path, &crafted).unwrap();
+            assert!(matches!(
+                Encrypted::with_passphrase(&path, "correct horse").load(),
+                Err(Error::InvalidFileFormat)
+            ));
+        }
+    }
+
+    static ANSWERS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+    static ACCEPTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    /// Answers with the passphrases in `ANSWERS`, in turn.
+    struct Queue;
+    impl PassphraseProvider for Queue {
+        fn passphrase(&self, _path: &Path, _new: bool) -> Result<String> {
+            Ok(ANSWERS.lock().unwrap().remove(0).to_owned())
+        }
+
+        fn accepted(&self, _path: &Path, passphrase: &str) {
+            ACCEPTED.lock().unwrap().push(passphrase.to_owned());
+        }
+    }
+
+    // The only test to use the passphrase provider, which is set once.
+    #[test]
+    fn only_passphrases_that_decrypt_are_accepted() {
+        let (path, _) = sealed("provider.secret", "correct horse");
+        *ANSWERS.lock().unwrap() = vec!["wrong horse", "correct horse", "wrong horse"];
+        set_passphrase_provider(Box::new(Queue));
+
+        assert!(Encrypted::new(&path).load().is_err());
+        assert!(ACCEPTED.lock().unwrap().is_empty());
+
+        // Asked ahead, the passphrase serves the next read only.
+        request_passphrase(&path).unwrap();
+        assert!(Encrypted::new(&path).load().is_ok());
+        assert_eq!(*ACCEPTED.lock().unwrap(), ["correct horse"]);
+        assert!(Encrypted::new(&path).load().is_err());
+        assert_eq!(ACCEPTED.lock().unwrap().len(), 1);
+    }
+}
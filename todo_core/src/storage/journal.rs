@@ -0,0 +1,535 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+
+use super::{Changes, Storage};
+use crate::{
+    task::{read_bytes, read_i64, read_str, read_u8, read_usize, LogEntry, Priority, Task},
+    uuid::Uuid,
+    Error, Result,
+};
+
+/// Written at the start of journal files.
+pub const JOURNAL_HEADER: &[u8; 4] = b"TDJ\x02";
+
+/// The header of journals whose entries name tasks by id rather than UUID.
+/// They are still read, and rewritten in the current form on the next save.
+const LEGACY_HEADER: &[u8; 4] = b"TDJ\x01";
+
+/// Entries beyond one per task after which the journal is compacted.
+const COMPACT_AFTER: usize = 256;
+
+/// Operations recorded in the journal. Unknown operations are skipped when
+/// replaying, so new ones can be added without breaking older files.
+///
+/// Apart from `OP_ADD`, whose payload is the whole task, each payload starts
+/// with the UUID of the task it applies to.
+const OP_ADD: u8 = 1;
+const OP_SET: u8 = 2;
+const OP_PROGRESS: u8 = 3;
+const OP_REMOVE: u8 = 4;
+
+/// Fields an `OP_SET` entry can change.
+const FIELD_NAME: u8 = 1;
+const FIELD_DESCRIPTION: u8 = 2;
+const FIELD_PROGRESS: u8 = 3;
+const FIELD_DEADLINE: u8 = 4;
+const FIELD_ESTIMATE: u8 = 5;
+const FIELD_CREATED: u8 = 6;
+const FIELD_COMPLETED: u8 = 7;
+const FIELD_PRIORITY: u8 = 8;
+const FIELD_OVERDUE_HANDLED: u8 = 9;
+const FIELD_ID: u8 = 10;
+const FIELD_CANCELLED: u8 = 11;
+
+/// An append-only log of operations, replayed to get the tasks.
+///
+/// Every change appends entries for just what changed: an added task, a
+/// field set to a new value, progress logged or a task removed. Each entry
+/// records when it was made, so the file doubles as an audit trail. Once the
+/// entries far outnumber the tasks, the file is compacted into one `add`
+/// entry per task.
+pub struct Journal {
+    path: PathBuf,
+    /// The tasks as of the last entry, to work out which entries a save
+    /// needs.
+    known: HashMap<Uuid, Task>,
+    entries: usize,
+    /// Whether the file has the [`LEGACY_HEADER`].
+    legacy: bool,
+}
+impl Journal {
+    pub fn new(path: &Path) -> Self {
+        Journal {
+            path: path.to_owned(),
+            known: HashMap::new(),
+            entries: 0,
+            legacy: false,
+        }
+    }
+
+    /// Appends `entries`, each an operation with its payload.
+    fn append(&mut self, entries: &[(u8, Vec<u8>)]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+
+        let now = Local::now().timestamp();
+        let mut bytes = Vec::new();
+        if file.metadata()?.len() == 0 {
+            bytes.extend_from_slice(JOURNAL_HEADER);
+        }
+        for (op, payload) in entries {
+            bytes.push(*op);
+            bytes.extend_from_slice(&now.to_be_bytes());
+            bytes.extend_from_slice(&payload.len().to_be_bytes());
+            bytes.extend_from_slice(payload);
+        }
+        // One write, so a crash can't leave half of a save behind.
+        file.write_all(&bytes)?;
+        self.entries += entries.len();
+        Ok(())
+    }
+
+    /// Rewrites the journal as one `add` entry per task.
+    fn compact(&mut self, tasks: &[Task]) -> Result<()> {
+        let mut bytes = Vec::from(*JOURNAL_HEADER);
+        let now = Local::now().timestamp();
+        for task in tasks {
+            let payload = task.serialize();
+            bytes.push(OP_ADD);
+            bytes.extend_from_slice(&now.to_be_bytes());
+            bytes.extend_from_slice(&payload.len().to_be_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        // Write beside the journal and swap, so it is never half written.
+        let temporary = self.path.with_extension("compacting");
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, &self.path)?;
+        self.entries = tasks.len();
+        self.legacy = false;
+        Ok(())
+    }
+}
+impl Storage for Journal {
+    fn load(&mut self) -> Result<Vec<Task>> {
+        self.known.clear();
+        self.entries = 0;
+        self.legacy = false;
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = File::open(&self.path)?;
+        let total_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut header = [0; JOURNAL_HEADER.len()];
+        reader.read_exact(&mut header)?;
+        self.legacy = match &header {
+            JOURNAL_HEADER => false,
+            LEGACY_HEADER => true,
+            _ => return Err(Error::InvalidFileFormat),
+        };
+
+        let mut tasks: Vec<Task> = Vec::new();
+        while reader.stream_position()? < total_size {
+            let replayed = if self.legacy {
+                replay_legacy(&mut reader, &mut tasks)
+            } else {
+                replay(&mut reader, &mut tasks)
+            };
+            replayed.map_err(|_| Error::InvalidFileFormat)?;
+            self.entries += 1;
+        }
+
+        self.known = tasks.iter().map(|t| (t.uuid(), t.clone())).collect();
+        Ok(tasks)
+    }
+
+    fn add(&mut self, task: &Task) -> Result<()> {
+        self.append(&[(OP_ADD, task.serialize())])?;
+        self.known.insert(task.uuid(), task.clone());
+        Ok(())
+    }
+
+    fn save(&mut self, tasks: &[Task], changes: &Changes) -> Result<()> {
+        if self.legacy && !changes.is_empty() {
+            self.compact(tasks)?;
+            self.known = tasks.iter().map(|t| (t.uuid(), t.clone())).collect();
+            return Ok(());
+        }
+
+        let mut entries = Vec::new();
+        for uuid in &changes.removed {
+            entries.push((OP_REMOVE, uuid.as_bytes().to_vec()));
+        }
+        for task in &changes.written {
+            match self.known.get(&task.uuid()) {
+                Some(old) => entries.extend(differences(old, task)),
+                None => entries.push((OP_ADD, task.serialize())),
+            }
+        }
+        self.append(&entries)?;
+        self.known = tasks.iter().map(|t| (t.uuid(), t.clone())).collect();
+
+        if self.entries > tasks.len() + COMPACT_AFTER {
+            self.compact(tasks)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the next entry of `reader` to `tasks`.
+fn replay<T: Read>(
+    reader: &mut T,
+    tasks: &mut Vec<Task>,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let op = read_u8(reader)?;
+    let _time = read_i64(reader)?;
+    let len = read_usize(reader)?;
+    let mut payload = &read_bytes(reader, len)?[..];
+
+    if op == OP_ADD {
+        let task = Task::from_extended(&mut payload)?;
+        // Adding an existing task replaces it in place.
+        match tasks.iter_mut().find(|t| t.uuid() == task.uuid()) {
+            Some(existing) => *existing = task,
+            None => tasks.push(task),
+        }
+        return Ok(());
+    }
+    if !matches!(op, OP_SET | OP_PROGRESS | OP_REMOVE) {
+        return Ok(());
+    }
+    let uuid = Uuid::from_bytes(read_bytes(&mut payload, 16)?.try_into().unwrap());
+    match op {
+        OP_SET => {
+            let field = read_u8(&mut payload)?;
+            if let Some(task) = tasks.iter_mut().find(|t| t.uuid() == uuid) {
+                set(task, field, &mut payload)?;
+            }
+        }
+        OP_PROGRESS => {
+            let entry = LogEntry {
+                timestamp: read_i64(&mut payload)?,
+                amount: read_i64(&mut payload)?,
+            };
+            if let Some(task) = tasks.iter_mut().find(|t| t.uuid() == uuid) {
+                task.progress += entry.amount;
+                task.log.push(entry);
+            }
+        }
+        OP_REMOVE => tasks.retain(|t| t.uuid() != uuid),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Applies the next entry of a journal with the [`LEGACY_HEADER`], whose
+/// entries name tasks by id, to `tasks`.
+fn replay_legacy<T: Read>(
+    reader: &mut T,
+    tasks: &mut Vec<Task>,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let op = read_u8(reader)?;
+    let _time = read_i64(reader)?;
+    let len = read_usize(reader)?;
+    let mut payload = &read_bytes(reader, len)?[..];
+
+    if op == OP_ADD {
+        let task = Task::from_extended(&mut payload)?;
+        match tasks.iter_mut().find(|t| t.id() == task.id()) {
+            Some(existing) => *existing = task,
+            None => tasks.push(task),
+        }
+        return Ok(());
+    }
+    if !matches!(op, OP_SET | OP_PROGRESS | OP_REMOVE) {
+        return Ok(());
+    }
+    let id = read_i64(&mut payload)?;
+    match op {
+        OP_SET => {
+            let field = read_u8(&mut payload)?;
+            if let Some(task) = tasks.iter_mut().find(|t| t.id() == id) {
+                set(task, field, &mut payload)?;
+            }
+        }
+        OP_PROGRESS => {
+            let entry = LogEntry {
+                timestamp: read_i64(&mut payload)?,
+                amount: read_i64(&mut payload)?,
+            };
+            if let Some(task) = tasks.iter_mut().find(|t| t.id() == id) {
+                task.progress += entry.amount;
+                task.log.push(entry);
+            }
+        }
+        OP_REMOVE => tasks.retain(|t| t.id() != id),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn set<T: Read>(
+    task: &mut Task,
+    field: u8,
+    value: &mut T,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    match field {
+        FIELD_NAME => {
+            let len = read_usize(value)?;
+            task.name = read_str(value, len)?;
+        }
+        FIELD_DESCRIPTION => {
+            let len = read_usize(value)?;
+            task.description = read_str(value, len)?;
+        }
+        FIELD_PROGRESS => task.progress = read_i64(value)?,
+        FIELD_DEADLINE => task.deadline = read_i64(value)?,
+        FIELD_ESTIMATE => task.estimated_time = read_i64(value)?,
+        FIELD_CREATED => task.created = read_i64(value)?,
+        FIELD_COMPLETED => task.completed = read_i64(value)?,
+        FIELD_PRIORITY => task.priority = Priority::from_u8(read_u8(value)?),
+        FIELD_OVERDUE_HANDLED => task.overdue_handled = read_i64(value)?,
+        FIELD_ID => task.set_id(read_i64(value)?),
+        FIELD_CANCELLED => task.cancelled = read_i64(value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Entries that turn `old` into `new`.
+fn differences(old: &Task, new: &Task) -> Vec<(u8, Vec<u8>)> {
+    // Progress is only ever logged, so anything else that touched the log
+    // is recorded as the whole task.
+    if !new.log.starts_with(&old.log) {
+        return vec![(OP_ADD, new.serialize())];
+    }
+
+    let uuid = new.uuid();
+    let uuid = uuid.as_bytes();
+    let mut entries = Vec::new();
+    let mut progress = old.progress;
+    for entry in &new.log[old.log.len()..] {
+        let mut payload = uuid.to_vec();
+        payload.extend_from_slice(&entry.timestamp.to_be_bytes());
+        payload.extend_from_slice(&entry.amount.to_be_bytes());
+        entries.push((OP_PROGRESS, payload));
+        progress += entry.amount;
+    }
+
+    let text = |s: &str| {
+        let mut value = s.len().to_be_bytes().to_vec();
+        value.extend_from_slice(s.as_bytes());
+        value
+    };
+    let fields = [
+        (
+            FIELD_ID,
+            old.id() != new.id(),
+            new.id().to_be_bytes().to_vec(),
+        ),
+        (FIELD_NAME, old.name != new.name, text(&new.name)),
+        (
+            FIELD_DESCRIPTION,
+            old.description != new.description,
+            text(&new.description),
+        ),
+        (
+            FIELD_PROGRESS,
+            progress != new.progress,
+            new.progress.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_DEADLINE,
+            old.deadline != new.deadline,
+            new.deadline.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_ESTIMATE,
+            old.estimated_time != new.estimated_time,
+            new.estimated_time.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_CREATED,
+            old.created != new.created,
+            new.created.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_COMPLETED,
+            old.completed != new.completed,
+            new.completed.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_CANCELLED,
+            old.cancelled != new.cancelled,
+            new.cancelled.to_be_bytes().to_vec(),
+        ),
+        (
+            FIELD_PRIORITY,
+            old.priority != new.priority,
+            vec![new.priority as u8],
+        ),
+        (
+            FIELD_OVERDUE_HANDLED,
+            old.overdue_handled != new.overdue_handled,
+            new.overdue_handled.to_be_bytes().to_vec(),
+        ),
+    ];
+    for (field, changed, value) in fields {
+        if changed {
+            let mut payload = uuid.to_vec();
+            payload.push(field);
+            payload.extend(value);
+            entries.push((OP_SET, payload));
+        }
+    }
+    entries
+}
+
+/// Whether `magic`, the start of a file, is that of a journal.
+pub(crate) fn is_journal(magic: &[u8]) -> bool {
+    magic.starts_with(JOURNAL_HEADER) || magic.starts_with(LEGACY_HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sort::SortKey, storage::temp_path, TaskStore};
+
+    fn store(path: &Path) -> TaskStore {
+        TaskStore::with_storage(path, Box::new(Journal::new(path))).unwrap()
+    }
+
+    fn stored(store: &TaskStore) -> Vec<Vec<u8>> {
+        store.tasks().iter().map(Task::serialize).collect()
+    }
+
+    #[test]
+    fn replays_every_kind_of_change() {
+        let path = temp_path("replay.journal");
+        let mut tasks = store(&path);
+        for id in 0..3 {
+            let mut task = Task::with_id(id);
+            task.name = format!("task {id}");
+            tasks.add(task).unwrap();
+        }
+        tasks
+            .update(0, |t| {
+                t.name = "renamed".into();
+                t.priority = Priority::High;
+            })
+            .unwrap();
+        tasks.update(1, |t| t.log_progress(30 * 60)).unwrap();
+        tasks.update(1, |t| t.cancelled = 1_800_000_000).unwrap();
+        tasks.remove(2).unwrap();
+        tasks.save().unwrap();
+
+        let size = fs::metadata(&path).unwrap().len();
+        let replayed = store(&path);
+        assert_eq!(stored(&replayed), stored(&tasks));
+        assert_eq!(replayed.tasks()[0].name, "renamed");
+        assert_eq!(replayed.tasks()[1].progress, 30 * 60);
+        assert!(replayed.tasks()[1].is_cancelled());
+        assert_eq!(replayed.tasks().len(), 2);
+        // Reading doesn't rewrite the journal.
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+    }
+
+    #[test]
+    fn compacts_once_changes_pile_up() {
+        let path = temp_path("compact.journal");
+        let mut tasks = store(&path);
+        tasks.add(Task::with_id(0)).unwrap();
+        for minutes in 1..=COMPACT_AFTER as i64 + 10 {
+            tasks
+                .update(0, |t| t.estimated_time = minutes * 60)
+                .unwrap();
+            tasks.save().unwrap();
+        }
+
+        let mut journal = Journal::new(&path);
+        let replayed = journal.load().unwrap();
+        assert!(journal.entries <= COMPACT_AFTER, "{}", journal.entries);
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].serialize(), tasks.tasks()[0].serialize());
+    }
+
+    #[test]
+    fn skips_unknown_operations() {
+        let path = temp_path("unknown.journal");
+        let mut tasks = store(&path);
+        tasks.add(Task::with_id(0)).unwrap();
+        Journal::new(&path).append(&[(99, vec![1, 2, 3])]).unwrap();
+        tasks.add(Task::with_id(1)).unwrap();
+
+        assert_eq!(stored(&store(&path)), stored(&tasks));
+    }
+
+    #[test]
+    fn replays_renumbered_tasks() {
+        let path = temp_path("renumber.journal");
+        let mut tasks = store(&path);
+        for (id, name) in [(4, "later"), (1, "sooner")] {
+            let mut task = Task::with_id(id);
+            task.name = name.into();
+            tasks.add(task).unwrap();
+        }
+        tasks.update(1, |t| t.log_progress(600)).unwrap();
+        tasks.save().unwrap();
+        tasks.renumber(&[SortKey::Name]);
+        tasks.update(1, |t| t.log_progress(300)).unwrap();
+        tasks.save().unwrap();
+
+        let replayed = store(&path);
+        assert_eq!(stored(&replayed), stored(&tasks));
+        assert_eq!(replayed.get(0).unwrap().name, "later");
+        assert_eq!(replayed.get(1).unwrap().progress, 900);
+    }
+
+    #[test]
+    fn rewrites_journals_that_name_tasks_by_id() {
+        let path = temp_path("legacy.journal");
+        let entry = |op: u8, payload: Vec<u8>| {
+            let mut bytes = vec![op];
+            bytes.extend_from_slice(&0i64.to_be_bytes());
+            bytes.extend_from_slice(&payload.len().to_be_bytes());
+            bytes.extend(payload);
+            bytes
+        };
+        let mut first = Task::stored(3);
+        first.name = "first".into();
+        let mut progress = 3i64.to_be_bytes().to_vec();
+        progress.extend_from_slice(&100i64.to_be_bytes());
+        progress.extend_from_slice(&600i64.to_be_bytes());
+        let mut bytes = LEGACY_HEADER.to_vec();
+        bytes.extend(entry(OP_ADD, first.serialize()));
+        bytes.extend(entry(OP_ADD, Task::stored(5).serialize()));
+        bytes.extend(entry(OP_PROGRESS, progress));
+        bytes.extend(entry(OP_REMOVE, 5i64.to_be_bytes().to_vec()));
+        fs::write(&path, bytes).unwrap();
+
+        let mut tasks = store(&path);
+        assert_eq!(tasks.tasks().len(), 1);
+        assert_eq!(tasks.tasks()[0].progress, 600);
+        assert!(!tasks.tasks()[0].uuid().is_nil());
+        assert!(tasks.is_changed());
+        tasks.save().unwrap();
+
+        assert!(fs::read(&path).unwrap().starts_with(JOURNAL_HEADER));
+        let replayed = store(&path);
+        assert_eq!(stored(&replayed), stored(&tasks));
+        assert!(!replayed.is_changed());
+    }
+}
@@ -0,0 +1,154 @@
+//! Where task files keep their tasks.
+
+mod binary;
+mod encrypted;
+mod journal;
+mod sqlite;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+pub use binary::{has_header, persist_tasks, read_tasks, save_tasks, BinaryFile};
+pub use encrypted::{
+    request_passphrase, set_passphrase_provider, Encrypted, PassphraseProvider, ENCRYPTED_HEADER,
+};
+pub use journal::{Journal, JOURNAL_HEADER};
+pub use sqlite::Sqlite;
+
+use crate::{filter::Filter, task::Task, uuid::Uuid, Error, Result};
+
+/// What changed between loading the tasks and saving them.
+#[derive(Debug, Default)]
+pub struct Changes<'a> {
+    /// New or modified tasks.
+    pub written: Vec<&'a Task>,
+    /// UUIDs of removed tasks.
+    pub removed: Vec<Uuid>,
+}
+impl Changes<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.written.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A backend that keeps the tasks of one task file.
+pub trait Storage {
+    /// Every task, in the order they were added.
+    fn load(&mut self) -> Result<Vec<Task>>;
+
+    /// The tasks matching `filter`. Backends with indexes only read the
+    /// tasks that can match.
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect())
+    }
+
+    /// Stores one new task.
+    fn add(&mut self, task: &Task) -> Result<()>;
+
+    /// Stores `changes`, after which the backend holds exactly `tasks`.
+    fn save(&mut self, tasks: &[Task], changes: &Changes) -> Result<()>;
+}
+
+/// The kinds of storage a task file can use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The binary record format.
+    #[default]
+    Binary,
+    /// An SQLite database.
+    Sqlite,
+    /// An append-only journal of changes.
+    Journal,
+    /// The binary record format, encrypted with a passphrase.
+    Encrypted,
+}
+impl Backend {
+    pub const NAMES: [&'static str; 4] = ["binary", "sqlite", "journal", "encrypted"];
+
+    /// Extensions that pick a backend whatever the default.
+    const EXTENSIONS: [(&'static str, Backend); 4] = [
+        ("db", Backend::Sqlite),
+        ("sqlite", Backend::Sqlite),
+        ("sqlite3", Backend::Sqlite),
+        ("journal", Backend::Journal),
+    ];
+
+    pub fn name(self) -> &'static str {
+        Backend::NAMES[self as usize]
+    }
+
+    /// The backend of the file at `path`: what an existing file holds, else
+    /// what its extension says, else `default`.
+    pub fn for_path(path: &Path, default: Backend) -> Result<Backend> {
+        let mut magic = Vec::new();
+        match File::open(path).and_then(|f| f.take(16).read_to_end(&mut magic)) {
+            Ok(_) if magic.as_slice() == b"SQLite format 3\0" => return Ok(Backend::Sqlite),
+            Ok(_) if journal::is_journal(&magic) => return Ok(Backend::Journal),
+            Ok(_) if magic.starts_with(ENCRYPTED_HEADER) => return Ok(Backend::Encrypted),
+            Ok(_) => return Ok(Backend::Binary),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::Io(e)),
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        Ok(Backend::EXTENSIONS
+            .iter()
+            .find(|(e, _)| *e == extension)
+            .map_or(default, |(_, backend)| *backend))
+    }
+
+    /// Storage of this kind for the file at `path`.
+    pub fn open(self, path: &Path) -> Box<dyn Storage> {
+        match self {
+            Backend::Binary => Box::new(BinaryFile::new(path)),
+            Backend::Sqlite => Box::new(Sqlite::new(path)),
+            Backend::Journal => Box::new(Journal::new(path)),
+            Backend::Encrypted => Box::new(Encrypted::new(path)),
+        }
+    }
+}
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "binary" => Ok(Backend::Binary),
+            "sqlite" => Ok(Backend::Sqlite),
+            "journal" => Ok(Backend::Journal),
+            "encrypted" => Ok(Backend::Encrypted),
+            other => Err(Error::Input(format!(
+                "Unknown storage \"{other}\" (expected one of: {})",
+                Backend::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Storage for the file at `path`, with `default` deciding for new files
+/// without a known extension.
+pub fn open(path: &Path, default: Backend) -> Result<Box<dyn Storage>> {
+    Ok(Backend::for_path(path, default)?.open(path))
+}
+
+/// A path named `name` in an empty temporary directory, for the backends'
+/// tests.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("todo-core-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir(&directory).unwrap();
+    directory.join(name)
+}
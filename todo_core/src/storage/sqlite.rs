@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension, Row};
+
+use super::{Changes, Storage};
+use crate::{
+    filter::{day_start, Condition, Filter},
+    task::{LogEntry, Priority, Task},
+    uuid::Uuid,
+    Error, Result,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tasks (
+        id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        progress INTEGER NOT NULL,
+        deadline INTEGER NOT NULL,
+        estimated_time INTEGER NOT NULL,
+        created INTEGER NOT NULL,
+        completed INTEGER NOT NULL,
+        priority INTEGER NOT NULL,
+        overdue_handled INTEGER NOT NULL,
+        uuid TEXT NOT NULL UNIQUE,
+        cancelled INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS tasks_deadline ON tasks (deadline);
+    CREATE INDEX IF NOT EXISTS tasks_status ON tasks (completed, cancelled, deadline);
+    CREATE TABLE IF NOT EXISTS log (
+        task_uuid TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        amount INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS log_task ON log (task_uuid);
+";
+
+/// The `user_version` of databases with [`SCHEMA`]. Older ones keyed tasks
+/// and their logs by id.
+const VERSION: i64 = 2;
+
+const COLUMNS: &str = "id, name, description, progress, deadline, estimated_time, created, \
+                       completed, priority, overdue_handled, uuid, cancelled";
+
+/// An SQLite database with a row per task, so single changes don't rewrite
+/// the file and queries by deadline or status use indexes.
+///
+/// Tasks are read in the order they were added, like from the other
+/// backends: rows are updated in place and keyed by UUID, so renumbering
+/// neither reorders them nor has to move their logs.
+pub struct Sqlite {
+    path: PathBuf,
+    connection: Option<Connection>,
+}
+impl Sqlite {
+    pub fn new(path: &Path) -> Self {
+        Sqlite {
+            path: path.to_owned(),
+            connection: None,
+        }
+    }
+
+    /// The open database, created with its schema on first use.
+    fn connection(&mut self) -> Result<&mut Connection> {
+        if self.connection.is_none() {
+            let mut connection = Connection::open(&self.path).map_err(db)?;
+            let transaction = connection.transaction().map_err(db)?;
+            let version: i64 = transaction
+                .query_row("PRAGMA user_version", [], |row| row.get(0))
+                .map_err(db)?;
+            let existing = column(&transaction, "id")?.is_some();
+            // Databases from before cancelled tasks lack the column.
+            if existing && column(&transaction, "cancelled")?.is_none() {
+                transaction
+                    .execute_batch(
+                        "ALTER TABLE tasks ADD COLUMN cancelled INTEGER NOT NULL DEFAULT 0;
+                         DROP INDEX IF EXISTS tasks_status;",
+                    )
+                    .map_err(db)?;
+            }
+            if existing && version < VERSION {
+                upgrade(&transaction)?;
+            }
+            transaction.execute_batch(SCHEMA).map_err(db)?;
+            transaction
+                .execute_batch(&format!("PRAGMA user_version = {VERSION}"))
+                .map_err(db)?;
+            transaction.commit().map_err(db)?;
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    /// Tasks selected by the SQL condition `clause`, with their logs, in the
+    /// order they were added.
+    fn select(&mut self, clause: &str, values: Vec<Value>) -> Result<Vec<Task>> {
+        // Reading shouldn't leave an empty database behind.
+        if self.connection.is_none() && !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let connection = self.connection()?;
+
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {COLUMNS} FROM tasks WHERE {clause} ORDER BY rowid"
+            ))
+            .map_err(db)?;
+        let mut tasks = statement
+            .query_map(params_from_iter(values.clone()), task_from_row)
+            .map_err(db)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(db)?;
+
+        let index: HashMap<String, usize> = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.uuid().to_string(), i))
+            .collect();
+        // Only the log entries of the selected tasks, through the index on
+        // `task_uuid`.
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT task_uuid, timestamp, amount FROM log \
+                 WHERE task_uuid IN (SELECT uuid FROM tasks WHERE {clause}) ORDER BY rowid"
+            ))
+            .map_err(db)?;
+        let entries = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    LogEntry {
+                        timestamp: row.get(1)?,
+                        amount: row.get(2)?,
+                    },
+                ))
+            })
+            .map_err(db)?;
+        for entry in entries {
+            let (task_uuid, entry) = entry.map_err(db)?;
+            if let Some(&i) = index.get(&task_uuid) {
+                tasks[i].log.push(entry);
+            }
+        }
+        Ok(tasks)
+    }
+}
+/// Open tasks due before a time.
+const OPEN_DUE_BEFORE: &str = "completed = 0 AND cancelled = 0 AND deadline < ?";
+
+impl Storage for Sqlite {
+    fn load(&mut self) -> Result<Vec<Task>> {
+        self.select("1", Vec::new())
+    }
+
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>> {
+        let now = Local::now().timestamp();
+        let mut clauses = Vec::new();
+        let mut values = Vec::new();
+        for condition in filter.conditions() {
+            let (clause, value) = match condition {
+                Condition::Open | Condition::AtRisk => ("completed = 0 AND cancelled = 0", None),
+                Condition::Completed => ("completed != 0", None),
+                Condition::Cancelled => ("cancelled != 0", None),
+                Condition::Overdue => (OPEN_DUE_BEFORE, Some(now)),
+                Condition::DueToday => (OPEN_DUE_BEFORE, Some(day_start(1))),
+                Condition::DueWeek => (OPEN_DUE_BEFORE, Some(day_start(7))),
+                Condition::Priority(priority) => ("priority = ?", Some(*priority as i64)),
+                // Matched below, as SQLite only lowercases ASCII.
+                Condition::Name(_) => continue,
+            };
+            clauses.push(clause);
+            values.extend(value.map(Value::Integer));
+        }
+
+        let clause = if clauses.is_empty() {
+            "1".to_owned()
+        } else {
+            clauses.join(" AND ")
+        };
+        // The clauses narrow the rows down; the filter decides exactly.
+        Ok(self
+            .select(&clause, values)?
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect())
+    }
+
+    fn add(&mut self, task: &Task) -> Result<()> {
+        let transaction = self.connection()?.transaction().map_err(db)?;
+        write(&transaction, task)?;
+        transaction.commit().map_err(db)
+    }
+
+    fn save(&mut self, _tasks: &[Task], changes: &Changes) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let transaction = self.connection()?.transaction().map_err(db)?;
+        for uuid in &changes.removed {
+            let uuid = uuid.to_string();
+            transaction
+                .execute("DELETE FROM log WHERE task_uuid = ?", [&uuid])
+                .map_err(db)?;
+            transaction
+                .execute("DELETE FROM tasks WHERE uuid = ?", [&uuid])
+                .map_err(db)?;
+        }
+        for task in &changes.written {
+            write(&transaction, task)?;
+        }
+        transaction.commit().map_err(db)
+    }
+}
+
+/// Inserts `task`, or updates it in place, and its log.
+fn write(connection: &Connection, task: &Task) -> Result<()> {
+    let uuid = task.uuid().to_string();
+    let updates = COLUMNS
+        .split(", ")
+        .map(|c| format!("{c} = excluded.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    connection
+        .execute(
+            &format!(
+                "INSERT INTO tasks ({COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (uuid) DO UPDATE SET {updates}"
+            ),
+            params![
+                task.id(),
+                task.name,
+                task.description,
+                task.progress,
+                task.deadline,
+                task.estimated_time,
+                task.created,
+                task.completed,
+                task.priority as i64,
+                task.overdue_handled,
+                uuid,
+                task.cancelled,
+            ],
+        )
+        .map_err(db)?;
+    connection
+        .execute("DELETE FROM log WHERE task_uuid = ?", [&uuid])
+        .map_err(db)?;
+    for entry in &task.log {
+        connection
+            .execute(
+                "INSERT INTO log (task_uuid, timestamp, amount) VALUES (?, ?, ?)",
+                params![uuid, entry.timestamp, entry.amount],
+            )
+            .map_err(db)?;
+    }
+    Ok(())
+}
+
+/// Moves a database keyed by id over to [`SCHEMA`], giving its tasks UUIDs
+/// and their log entries the UUID of their task.
+///
+/// The tasks are copied in the order of their row ids, which for the oldest
+/// databases, where the id was the row id, is id order.
+fn upgrade(connection: &Connection) -> Result<()> {
+    if column(connection, "uuid")?.is_none() {
+        connection
+            .execute_batch("ALTER TABLE tasks ADD COLUMN uuid TEXT NOT NULL DEFAULT ''")
+            .map_err(db)?;
+    }
+    let missing = connection
+        .prepare("SELECT rowid FROM tasks WHERE uuid = ''")
+        .map_err(db)?
+        .query_map([], |row| row.get::<_, i64>(0))
+        .map_err(db)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(db)?;
+    for rowid in missing {
+        connection
+            .execute(
+                "UPDATE tasks SET uuid = ? WHERE rowid = ?",
+                params![Uuid::new_v4().to_string(), rowid],
+            )
+            .map_err(db)?;
+    }
+    connection
+        .execute_batch(&format!(
+            "ALTER TABLE tasks RENAME TO tasks_by_id;
+             ALTER TABLE log RENAME TO log_by_id;
+             DROP INDEX IF EXISTS tasks_deadline;
+             DROP INDEX IF EXISTS tasks_status;
+             DROP INDEX IF EXISTS log_task;
+             {SCHEMA}
+             INSERT INTO tasks ({COLUMNS}) SELECT {COLUMNS} FROM tasks_by_id ORDER BY rowid;
+             INSERT INTO log (task_uuid, timestamp, amount)
+                 SELECT t.uuid, l.timestamp, l.amount
+                 FROM log_by_id l JOIN tasks_by_id t ON t.id = l.task_id
+                 ORDER BY l.rowid;
+             DROP TABLE tasks_by_id;
+             DROP TABLE log_by_id;"
+        ))
+        .map_err(db)
+}
+
+/// Whether the tasks table has the column `name`, and if so whether it is
+/// the primary key.
+fn column(connection: &Connection, name: &str) -> Result<Option<bool>> {
+    connection
+        .query_row(
+            "SELECT pk FROM pragma_table_info('tasks') WHERE name = ?",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|pk| pk.map(|pk| pk != 0))
+        .map_err(db)
+}
+
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    let mut task = Task::stored(row.get(0)?);
+    task.name = row.get(1)?;
+    task.description = row.get(2)?;
+    task.progress = row.get(3)?;
+    task.deadline = row.get(4)?;
+    task.estimated_time = row.get(5)?;
+    task.created = row.get(6)?;
+    task.completed = row.get(7)?;
+    task.priority = Priority::from_u8(row.get(8)?);
+    task.overdue_handled = row.get(9)?;
+    let uuid: String = row.get(10)?;
+    task.set_uuid(uuid.parse().unwrap_or(Uuid::NIL));
+    task.cancelled = row.get(11)?;
+    Ok(task)
+}
+
+fn db(e: rusqlite::Error) -> Error {
+    Error::Storage(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+
+    fn task(id: i64, name: &str) -> Task {
+        let mut task = Task::with_id(id);
+        task.name = name.into();
+        task.description = format!("about {name}");
+        task.deadline = 4_102_444_800 + id;
+        task.estimated_time = 7200;
+        task.created = 1_800_000_000;
+        task.priority = Priority::High;
+        task.overdue_handled = 1_700_000_000;
+        task
+    }
+
+    fn serialized(tasks: &[Task]) -> Vec<Vec<u8>> {
+        tasks.iter().map(Task::serialize).collect()
+    }
+
+    #[test]
+    fn tasks_survive_a_round_trip() {
+        let path = temp_path("round-trip.db");
+        let mut first = task(0, "write report");
+        first.log_progress(1800);
+        first.log_progress(5400);
+        let second = task(1, "pay rent");
+
+        let mut sqlite = Sqlite::new(&path);
+        sqlite.add(&first).unwrap();
+        sqlite.add(&second).unwrap();
+
+        let loaded = Sqlite::new(&path).load().unwrap();
+        assert_eq!(serialized(&loaded), serialized(&[first, second]));
+        assert!(loaded[0].is_completed());
+    }
+
+    #[test]
+    fn saving_rewrites_changed_tasks_and_their_logs() {
+        let path = temp_path("save.db");
+        let mut tasks = vec![task(0, "write report"), task(1, "pay rent")];
+        tasks[0].log_progress(600);
+        let mut sqlite = Sqlite::new(&path);
+        for task in &tasks {
+            sqlite.add(task).unwrap();
+        }
+
+        tasks[0].log_progress(1200);
+        tasks[0].name = "write the report".into();
+        let removed = tasks.remove(1);
+        let changes = Changes {
+            written: vec![&tasks[0]],
+            removed: vec![removed.uuid()],
+        };
+        sqlite.save(&tasks, &changes).unwrap();
+
+        let loaded = Sqlite::new(&path).load().unwrap();
+        assert_eq!(serialized(&loaded), serialized(&tasks));
+        assert_eq!(loaded[0].log.len(), 2);
+        assert_eq!(loaded[0].log[1].amount, 1200);
+
+        let connection = Connection::open(&path).unwrap();
+        let orphans: i64 = connection
+            .query_row(
+                "SELECT COUNT(*) FROM log WHERE task_uuid = ?",
+                [removed.uuid().to_string()],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphans, 0);
+    }
+
+    #[test]
+    fn queries_read_only_matching_tasks() {
+        let path = temp_path("query.db");
+        let mut done = task(0, "write report");
+        done.log_progress(7200);
+        let mut sqlite = Sqlite::new(&path);
+        sqlite.add(&done).unwrap();
+        sqlite.add(&task(1, "pay rent")).unwrap();
+        sqlite.add(&task(2, "rent a car")).unwrap();
+        let mut cancelled = task(3, "rent a flat");
+        cancelled.deadline = 1;
+        cancelled.cancelled = 1_800_000_000;
+        sqlite.add(&cancelled).unwrap();
+
+        let ids = |filter: &str| -> Vec<i64> {
+            let filter = filter.parse().unwrap();
+            let tasks = Sqlite::new(&path).query(&filter).unwrap();
+            tasks.iter().map(Task::id).collect()
+        };
+        assert_eq!(ids("open"), [1, 2]);
+        assert_eq!(ids("completed"), [0]);
+        assert_eq!(ids("cancelled"), [3]);
+        assert_eq!(ids("overdue"), Vec::<i64>::new());
+        assert_eq!(ids("open,name=RENT A"), [2]);
+    }
+
+    #[test]
+    fn reading_a_missing_database_creates_nothing() {
+        let path = temp_path("missing.db");
+        assert!(Sqlite::new(&path).load().unwrap().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn keeps_tasks_in_the_order_they_were_added() {
+        let path = temp_path("order.db");
+        let mut sqlite = Sqlite::new(&path);
+        let mut tasks = vec![task(5, "first"), task(2, "second"), task(9, "third")];
+        tasks[2].log_progress(600);
+        for task in &tasks {
+            sqlite.add(task).unwrap();
+        }
+
+        // The third task takes over the id of the removed one.
+        tasks[0].name = "first, renamed".into();
+        tasks[2].set_id(2);
+        let removed = tasks.remove(1);
+        let changes = Changes {
+            written: vec![&tasks[0], &tasks[1]],
+            removed: vec![removed.uuid()],
+        };
+        sqlite.save(&tasks, &changes).unwrap();
+
+        let loaded = Sqlite::new(&path).load().unwrap();
+        assert_eq!(serialized(&loaded), serialized(&tasks));
+        assert_eq!(loaded[1].log.len(), 1);
+    }
+
+    #[test]
+    fn upgrades_databases_keyed_by_id() {
+        let path = temp_path("old-schema.db");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE tasks (
+                     id INTEGER PRIMARY KEY,
+                     name TEXT NOT NULL,
+                     description TEXT NOT NULL,
+                     progress INTEGER NOT NULL,
+                     deadline INTEGER NOT NULL,
+                     estimated_time INTEGER NOT NULL,
+                     created INTEGER NOT NULL,
+                     completed INTEGER NOT NULL,
+                     priority INTEGER NOT NULL,
+                     overdue_handled INTEGER NOT NULL
+                 );
+                 CREATE INDEX tasks_deadline ON tasks (deadline);
+                 CREATE INDEX tasks_status ON tasks (completed, deadline);
+                 CREATE TABLE log (
+                     task_id INTEGER NOT NULL,
+                     timestamp INTEGER NOT NULL,
+                     amount INTEGER NOT NULL
+                 );
+                 CREATE INDEX log_task ON log (task_id);
+                 INSERT INTO tasks VALUES (7, 'later', '', 0, 2, 3600, 1, 0, 1, 0);
+                 INSERT INTO tasks VALUES (3, 'sooner', '', 600, 1, 3600, 1, 0, 1, 0);
+                 INSERT INTO log VALUES (3, 100, 600);",
+            )
+            .unwrap();
+        drop(connection);
+
+        let mut sqlite = Sqlite::new(&path);
+        let loaded = sqlite.load().unwrap();
+        let tasks: Vec<(i64, &str)> = loaded.iter().map(|t| (t.id(), t.name.as_str())).collect();
+        assert_eq!(tasks, [(3, "sooner"), (7, "later")]);
+        assert!(loaded.iter().all(|t| !t.uuid().is_nil()));
+        assert_ne!(loaded[0].uuid(), loaded[1].uuid());
+        assert_eq!(loaded[0].log.len(), 1);
+        assert!(loaded[1].log.is_empty());
+
+        let connection = sqlite.connection().unwrap();
+        assert_eq!(column(connection, "id").unwrap(), Some(false));
+        let logged: String = connection
+            .query_row("SELECT task_uuid FROM log", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(logged, loaded[0].uuid().to_string());
+
+        // New tasks go after the old ones, whatever their id.
+        sqlite.add(&task(1, "new")).unwrap();
+        let ids: Vec<i64> = sqlite.load().unwrap().iter().map(Task::id).collect();
+        assert_eq!(ids, [3, 7, 1]);
+
+        // The UUIDs are written, so they stay the same from now on.
+        let reloaded = Sqlite::new(&path).load().unwrap();
+        assert_eq!(reloaded[0].uuid(), loaded[0].uuid());
+    }
+
+    #[test]
+    fn upgrades_databases_with_empty_uuids() {
+        let path = temp_path("empty-uuids.db");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE tasks (
+                     id INTEGER NOT NULL UNIQUE,
+                     name TEXT NOT NULL,
+                     description TEXT NOT NULL,
+                     progress INTEGER NOT NULL,
+                     deadline INTEGER NOT NULL,
+                     estimated_time INTEGER NOT NULL,
+                     created INTEGER NOT NULL,
+                     completed INTEGER NOT NULL,
+                     priority INTEGER NOT NULL,
+                     overdue_handled INTEGER NOT NULL,
+                     uuid TEXT NOT NULL DEFAULT ''
+                 );
+                 CREATE TABLE log (
+                     task_id INTEGER NOT NULL,
+                     timestamp INTEGER NOT NULL,
+                     amount INTEGER NOT NULL
+                 );
+                 CREATE INDEX log_task ON log (task_id);
+                 INSERT INTO tasks VALUES
+                     (4, 'kept', '', 0, 2, 3600, 1, 0, 1, 0, '00000000-0000-4000-8000-000000000001');
+                 INSERT INTO tasks VALUES (1, 'new', '', 300, 1, 3600, 1, 0, 1, 0, '');
+                 INSERT INTO log VALUES (1, 100, 300);",
+            )
+            .unwrap();
+        drop(connection);
+
+        let loaded = Sqlite::new(&path).load().unwrap();
+        let ids: Vec<i64> = loaded.iter().map(Task::id).collect();
+        assert_eq!(ids, [4, 1]);
+        assert_eq!(
+            loaded[0].uuid().to_string(),
+            "00000000-0000-4000-8000-000000000001"
+        );
+        assert!(!loaded[1].uuid().is_nil());
+        assert_eq!(loaded[1].log.len(), 1);
+        assert_eq!(loaded[1].log[0].amount, 300);
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions, TryLockError},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    filter::Filter,
+    sort::{sort_tasks, SortKey},
+    storage::{self, Backend, Changes, Storage},
+    task::Task,
+    uuid::Uuid,
+    Error, Result,
+};
+
+/// The tasks of one task file, kept in memory.
+///
+/// [`TaskStore::add`] stores the new task straight away; every other change
+/// stays in memory until [`TaskStore::save`] hands what changed to the
+/// storage backend.
+///
+/// Tasks read without a UUID, from files written before tasks had them, get
+/// a random one when loaded. It counts as a change, so the next save stores
+/// it.
+pub struct TaskStore {
+    path: PathBuf,
+    storage: Box<dyn Storage>,
+    tasks: Vec<Task>,
+    /// The stored form of each task as last loaded or saved, by UUID, to
+    /// tell which tasks changed.
+    saved: HashMap<Uuid, Vec<u8>>,
+}
+impl TaskStore {
+    /// Reads the task file at `path`, or starts an empty store if it doesn't
+    /// exist yet. `default` is the backend used for a new file without a
+    /// known extension.
+    pub fn load(path: &Path, default: Backend) -> Result<Self> {
+        Self::with_storage(path, storage::open(path, default)?)
+    }
+
+    /// Like [`TaskStore::load`], but fails if the file doesn't exist.
+    pub fn open(path: &Path, default: Backend) -> Result<Self> {
+        if !path.exists() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Task file {} not found", path.display()),
+            )));
+        }
+        Self::load(path, default)
+    }
+
+    /// Reads the tasks of `path` through `storage`.
+    pub fn with_storage(path: &Path, storage: Box<dyn Storage>) -> Result<Self> {
+        let mut store = TaskStore {
+            path: path.to_owned(),
+            storage,
+            tasks: Vec::new(),
+            saved: HashMap::new(),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// Reads the tasks again, dropping unsaved changes.
+    pub fn reload(&mut self) -> Result<()> {
+        self.tasks = self.storage.load()?;
+        self.saved.clear();
+        for task in &mut self.tasks {
+            let stored = task.serialize();
+            if task.uuid().is_nil() {
+                task.set_uuid(Uuid::new_v4());
+            }
+            self.saved.insert(task.uuid(), stored);
+        }
+        Ok(())
+    }
+
+    /// Whether any task changed since the tasks were loaded or last saved.
+    pub fn is_changed(&self) -> bool {
+        self.tasks.len() != self.saved.len() || self.tasks.iter().any(|t| self.is_unsaved(t))
+    }
+
+    fn is_unsaved(&self, task: &Task) -> bool {
+        self.saved.get(&task.uuid()) != Some(&task.serialize())
+    }
+
+    /// Stores the tasks that changed since they were loaded or last saved.
+    pub fn save(&mut self) -> Result<()> {
+        let mut changes = Changes::default();
+        for task in &self.tasks {
+            if self.is_unsaved(task) {
+                changes.written.push(task);
+            }
+        }
+        let current: HashSet<Uuid> = self.tasks.iter().map(Task::uuid).collect();
+        changes.removed = self
+            .saved
+            .keys()
+            .copied()
+            .filter(|uuid| !current.contains(uuid))
+            .collect();
+
+        self.storage.save(&self.tasks, &changes)?;
+        self.saved = self
+            .tasks
+            .iter()
+            .map(|t| (t.uuid(), t.serialize()))
+            .collect();
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn tasks_mut(&mut self) -> &mut Vec<Task> {
+        &mut self.tasks
+    }
+
+    pub fn get(&self, id: i64) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id() == id)
+    }
+
+    /// Id for a new task, one past the highest id in use.
+    pub fn next_id(&self) -> i64 {
+        next_id(&self.tasks)
+    }
+
+    /// Renumbers the tasks densely in the order of `keys`; see [`renumber`].
+    pub fn renumber(&mut self, keys: &[SortKey]) -> Vec<(i64, i64)> {
+        renumber(&mut self.tasks, keys)
+    }
+
+    /// Adds `task` and stores it without touching the other tasks.
+    pub fn add(&mut self, task: Task) -> Result<()> {
+        if self.get(task.id()).is_some() {
+            return Err(Error::Input(format!(
+                "Task id {} is already in use",
+                task.id()
+            )));
+        }
+        self.storage.add(&task)?;
+        self.saved.insert(task.uuid(), task.serialize());
+        self.tasks.push(task);
+        Ok(())
+    }
+
+    /// Applies `change` to the task with id `id`.
+    pub fn update(&mut self, id: i64, change: impl FnOnce(&mut Task)) -> Result<&Task> {
+        let task = self
+            .tasks
+            .iter_mut()
+            .find(|t| t.id() == id)
+            .ok_or(Error::TaskNotFound)?;
+        change(task);
+        Ok(task)
+    }
+
+    /// Removes the task with id `id`, keeping the order of the others.
+    pub fn remove(&mut self, id: i64) -> Result<Task> {
+        let index = self
+            .tasks
+            .iter()
+            .position(|t| t.id() == id)
+            .ok_or(Error::TaskNotFound)?;
+        Ok(self.tasks.remove(index))
+    }
+
+    /// Tasks matching `filter`, in file order.
+    pub fn query(&self, filter: &Filter) -> Vec<&Task> {
+        self.tasks.iter().filter(|t| filter.matches(t)).collect()
+    }
+}
+
+/// An exclusive lock on a task file, held until dropped, so processes
+/// reading and changing the same file take turns.
+///
+/// The lock is advisory and taken on `<file>.lock` next to the file, since
+/// some backends replace the task file itself when saving. On Unix the lock
+/// file is removed again when the lock is dropped.
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+impl FileLock {
+    /// Waits for the lock on the task file at `path`. Returns `None` if the
+    /// file's directory doesn't exist, as there is nothing to protect then.
+    pub fn acquire(path: &Path) -> Result<Option<Self>> {
+        Self::take(path, true)
+    }
+
+    /// Like [`FileLock::acquire`], but fails with [`Error::Locked`] instead
+    /// of waiting while another process holds the lock.
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>> {
+        Self::take(path, false)
+    }
+
+    fn take(path: &Path, wait: bool) -> Result<Option<Self>> {
+        let mut name = path.file_name().unwrap_or_default().to_owned();
+        name.push(".lock");
+        let lock_path = path.with_file_name(name);
+        loop {
+            let file = match OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&lock_path)
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(Error::Io(e)),
+            };
+            if wait {
+                file.lock()?;
+            } else {
+                match file.try_lock() {
+                    Ok(()) => {}
+                    Err(TryLockError::WouldBlock) => {
+                        return Err(Error::Locked(path.display().to_string()))
+                    }
+                    Err(TryLockError::Error(e)) => return Err(Error::Io(e)),
+                }
+            }
+            // The holder before us may have removed the file we waited on,
+            // and someone else may hold a new one by now.
+            if is_linked(&file, &lock_path)? {
+                return Ok(Some(FileLock {
+                    file,
+                    path: lock_path,
+                }));
+            }
+        }
+    }
+}
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Removed while still locked, so nobody can lock it in between and
+        // take it for the current one; anyone waiting on it starts over.
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Whether `file` is still the one at `path`.
+#[cfg(unix)]
+fn is_linked(file: &File, path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let locked = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok((current.dev(), current.ino()) == (locked.dev(), locked.ino())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(Error::Io(e)),
+    }
+}
+
+/// Lock files are only removed on Unix, so elsewhere the file locked is
+/// always the one at `path`.
+#[cfg(not(unix))]
+fn is_linked(_file: &File, _path: &Path) -> Result<bool> {
+    Ok(true)
+}
+
+/// Id for a new task, one past the highest id in use.
+pub fn next_id(tasks: &[Task]) -> i64 {
+    tasks.iter().map(|t| t.id()).max().unwrap_or(-1) + 1
+}
+
+/// Sorts `tasks` by `keys` and numbers them densely in that order, from the
+/// id a new file would start at. Returns the old and new id of every task
+/// whose id changed; UUIDs stay as they are.
+pub fn renumber(tasks: &mut [Task], keys: &[SortKey]) -> Vec<(i64, i64)> {
+    sort_tasks(tasks, keys, false);
+    let mut changed = Vec::new();
+    for (id, task) in (next_id(&[])..).zip(tasks.iter_mut()) {
+        if task.id() != id {
+            changed.push((task.id(), id));
+            task.set_id(id);
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::temp_path;
+
+    fn task(id: i64, name: &str) -> Task {
+        let mut task = Task::with_id(id);
+        task.name = name.into();
+        task
+    }
+
+    fn ids_and_names(tasks: &[Task]) -> Vec<(i64, &str)> {
+        tasks.iter().map(|t| (t.id(), t.name.as_str())).collect()
+    }
+
+    #[test]
+    fn locks_are_exclusive_and_clean_up_after_themselves() {
+        let path = storage::temp_path("locked");
+        let lock_path = path.with_file_name("locked.lock");
+
+        let lock = FileLock::acquire(&path).unwrap().unwrap();
+        assert!(lock_path.exists());
+        assert!(matches!(
+            FileLock::try_acquire(&path),
+            Err(Error::Locked(_))
+        ));
+        drop(lock);
+        #[cfg(unix)]
+        assert!(!lock_path.exists());
+
+        // Someone waiting while the lock file is removed locks a new one.
+        let lock = FileLock::acquire(&path).unwrap().unwrap();
+        let waiting = std::thread::spawn({
+            let path = path.clone();
+            move || FileLock::acquire(&path).unwrap().unwrap()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        drop(lock);
+        let lock = waiting.join().unwrap();
+        assert!(lock_path.exists());
+        assert!(matches!(
+            FileLock::try_acquire(&path),
+            Err(Error::Locked(_))
+        ));
+        drop(lock);
+
+        let missing = path.with_file_name("missing").join("tasks");
+        assert!(FileLock::acquire(&missing).unwrap().is_none());
+    }
+
+    #[test]
+    fn renumbers_densely_in_key_order() {
+        let mut tasks = vec![task(4, "c"), task(0, "b"), task(9, "a")];
+        let uuids: Vec<_> = tasks.iter().map(Task::uuid).collect();
+
+        let changed = renumber(&mut tasks, &[SortKey::Name]);
+        assert_eq!(ids_and_names(&tasks), [(0, "a"), (1, "b"), (2, "c")]);
+        assert_eq!(changed, [(9, 0), (0, 1), (4, 2)]);
+        assert_eq!(
+            tasks.iter().map(Task::uuid).collect::<Vec<_>>(),
+            [uuids[2], uuids[1], uuids[0]]
+        );
+    }
+
+    #[test]
+    fn renumbering_dense_ids_changes_nothing() {
+        let mut tasks = vec![task(0, "a"), task(1, "b")];
+        assert!(renumber(&mut tasks, &[SortKey::Id]).is_empty());
+    }
+
+    #[test]
+    fn renumbered_stores_save_and_read_back() {
+        for name in ["renumber", "renumber.db"] {
+            let path = temp_path(name);
+            let mut store = TaskStore::load(&path, Backend::Binary).unwrap();
+            for (id, name) in [(2, "b"), (5, "a"), (7, "c")] {
+                let mut task = task(id, name);
+                task.log_progress(60);
+                store.add(task).unwrap();
+            }
+
+            store.renumber(&[SortKey::Name]);
+            store.save().unwrap();
+
+            // SQLite keeps the rows in the order they were added.
+            let mut reloaded = TaskStore::load(&path, Backend::Binary).unwrap();
+            reloaded.tasks_mut().sort_by_key(Task::id);
+            assert_eq!(
+                ids_and_names(reloaded.tasks()),
+                [(0, "a"), (1, "b"), (2, "c")],
+                "{name}"
+            );
+            assert!(reloaded.tasks().iter().all(|t| t.log.len() == 1), "{name}");
+            assert_eq!(reloaded.next_id(), 3);
+        }
+    }
+
+    #[test]
+    fn legacy_tasks_get_a_uuid_that_the_next_save_keeps() {
+        let path = temp_path("legacy-uuid");
+        let mut store = TaskStore::load(&path, Backend::Binary).unwrap();
+        let mut legacy = Task::stored(0);
+        legacy.name = "old".into();
+        store.add(legacy).unwrap();
+
+        let mut store = TaskStore::load(&path, Backend::Binary).unwrap();
+        let uuid = store.tasks()[0].uuid();
+        assert!(!uuid.is_nil());
+        assert!(store.is_changed());
+        store.save().unwrap();
+
+        let store = TaskStore::load(&path, Backend::Binary).unwrap();
+        assert_eq!(store.tasks()[0].uuid(), uuid);
+        assert!(!store.is_changed());
+    }
+}
@@ -0,0 +1,745 @@
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+use chrono::{Local, TimeZone};
+use colored::*;
+
+use crate::{
+    format::{card, format_duration, highlight, progress_bar, strip_colors, urgency_colour},
+    uuid::Uuid,
+};
+
+pub(crate) fn read<T: std::io::Read, V, E, F>(
+    stream: &mut T,
+    convert: F,
+    size: usize,
+) -> Result<V, Box<dyn Error>>
+where
+    F: Fn(&[u8]) -> Result<V, E>,
+    E: std::error::Error + 'static,
+{
+    // Read through `take` rather than into a buffer of `size` bytes, so a
+    // corrupt length can't allocate more than the input holds.
+    let mut buf = Vec::new();
+    stream.take(size as u64).read_to_end(&mut buf)?;
+    if buf.len() < size {
+        return Err(Box::new(std::io::Error::from(
+            std::io::ErrorKind::UnexpectedEof,
+        )));
+    }
+
+    convert(&buf).map_err(|e| Box::new(e) as Box<dyn Error>)
+}
+
+pub(crate) fn read_i64<T: std::io::Read>(stream: &mut T) -> Result<i64, Box<dyn Error>> {
+    read(
+        stream,
+        |b| Ok::<i64, CorruptError>(i64::from_be_bytes(b.try_into().map_err(|_| CorruptError)?)),
+        size_of::<i64>(),
+    )
+}
+pub(crate) fn read_usize<T: std::io::Read>(stream: &mut T) -> Result<usize, Box<dyn Error>> {
+    read(
+        stream,
+        |b| {
+            Ok::<usize, CorruptError>(usize::from_be_bytes(
+                b.try_into().map_err(|_| CorruptError)?,
+            ))
+        },
+        size_of::<usize>(),
+    )
+}
+pub(crate) fn read_str<T: std::io::Read>(
+    stream: &mut T,
+    size: usize,
+) -> Result<String, Box<dyn Error>> {
+    read(
+        stream,
+        |b| std::str::from_utf8(b).map(|x| x.to_owned()),
+        size,
+    )
+}
+
+pub(crate) fn read_u8<T: std::io::Read>(stream: &mut T) -> Result<u8, Box<dyn Error>> {
+    read(stream, |b| Ok::<u8, CorruptError>(b[0]), 1)
+}
+pub(crate) fn read_bytes<T: std::io::Read>(
+    stream: &mut T,
+    size: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    read(stream, |b| Ok::<Vec<u8>, CorruptError>(b.to_vec()), size)
+}
+
+/// Written at the start of task files using the extensible record format.
+/// Files without it hold legacy records, which have no extension block.
+pub const FILE_HEADER: &[u8; 4] = b"TDL\x02";
+
+/// Tags of the optional fields stored in a record's extension block.
+/// Unknown tags are skipped when reading, so new fields can be added
+/// without breaking older files.
+const TAG_CREATED: u8 = 1;
+const TAG_LOG: u8 = 2;
+const TAG_COMPLETED: u8 = 3;
+const TAG_PRIORITY: u8 = 4;
+const TAG_OVERDUE_HANDLED: u8 = 5;
+const TAG_UUID: u8 = 6;
+const TAG_CANCELLED: u8 = 7;
+
+#[derive(Debug, Clone)]
+struct CorruptError;
+impl fmt::Display for CorruptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Corrupt file")
+    }
+}
+impl Error for CorruptError {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+impl Priority {
+    pub const NAMES: [&'static str; 4] = ["low", "normal", "high", "urgent"];
+
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Priority::Low,
+            2 => Priority::High,
+            3 => Priority::Urgent,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// The next priority up, staying at `Urgent`.
+    pub fn escalated(self) -> Self {
+        match self {
+            Priority::Low => Priority::Normal,
+            Priority::Normal => Priority::High,
+            Priority::High | Priority::Urgent => Priority::Urgent,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Priority::NAMES[self as usize]
+    }
+
+    pub fn colored(self) -> String {
+        match self {
+            Priority::Low => self.name().truecolor(128, 128, 128),
+            Priority::Normal => self.name().normal(),
+            Priority::High => self.name().yellow(),
+            Priority::Urgent => self.name().bright_red().bold(),
+        }
+        .to_string()
+    }
+}
+impl std::str::FromStr for Priority {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            "urgent" => Ok(Priority::Urgent),
+            other => Err(crate::Error::Input(format!(
+                "Unknown priority \"{other}\" (expected one of: {})",
+                Priority::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Progress recorded against a task at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    pub timestamp: i64,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Task {
+    /// Display number, what commands and the user refer to the task by.
+    id: i64,
+    uuid: Uuid,
+    pub progress: i64,
+    pub deadline: i64,
+    pub estimated_time: i64,
+    pub name: String,
+    pub description: String,
+    /// Creation time, 0 for tasks from files that didn't record it.
+    pub created: i64,
+    pub log: Vec<LogEntry>,
+    /// Time the task was completed, 0 while it is still open.
+    pub completed: i64,
+    /// Time the task was cancelled, 0 unless it was. Cancelled tasks aren't
+    /// open any more, but weren't completed either.
+    pub cancelled: i64,
+    pub priority: Priority,
+    /// Deadline the overdue policy last acted on, so each missed deadline is
+    /// only handled once.
+    pub overdue_handled: i64,
+}
+impl Default for Task {
+    fn default() -> Self {
+        Task::new()
+    }
+}
+impl Task {
+    pub fn new() -> Self {
+        Task {
+            uuid: Uuid::new_v4(),
+            ..Task::stored(0)
+        }
+    }
+
+    /// A task as read from a record, before its UUID is read. Records
+    /// without one leave it nil for [`crate::TaskStore`] to assign.
+    pub(crate) fn stored(id: i64) -> Self {
+        Task {
+            id,
+            uuid: Uuid::NIL,
+            progress: 0,
+            deadline: 0,
+            estimated_time: 0,
+            name: String::new(),
+            description: String::new(),
+            created: 0,
+            log: Vec::new(),
+            completed: 0,
+            cancelled: 0,
+            priority: Priority::Normal,
+            overdue_handled: 0,
+        }
+    }
+
+    pub fn with_id(id: i64) -> Self {
+        let mut task = Task::new();
+        task.id = id;
+        task
+    }
+
+    pub fn with_details(
+        id: i64,
+        progress: i64,
+        due: i64,
+        estimated_time: i64,
+        name: String,
+        description: String,
+    ) -> Self {
+        Task {
+            id,
+            uuid: Uuid::new_v4(),
+            progress,
+            deadline: due,
+            name,
+            description,
+            estimated_time,
+            created: 0,
+            log: Vec::new(),
+            completed: 0,
+            cancelled: 0,
+            priority: Priority::Normal,
+            overdue_handled: 0,
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Gives the task another number, as when it moves to another file; the
+    /// UUID stays the same.
+    pub fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    pub(crate) fn set_uuid(&mut self, uuid: Uuid) {
+        self.uuid = uuid;
+    }
+
+    pub fn from<T: std::io::Read>(stream: &mut T) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut task = Task::stored(read_i64(stream)?);
+        task.progress = read_i64(stream)?;
+        task.deadline = read_i64(stream)?;
+        task.estimated_time = read_i64(stream)?;
+
+        let name_len = read_usize(stream)?;
+        task.name = read_str(stream, name_len)?;
+        let desc_len = read_usize(stream)?;
+        task.description = read_str(stream, desc_len)?;
+        Ok(task)
+    }
+
+    /// Reads a record in the extensible format: the legacy fields followed by
+    /// the extension block.
+    pub fn from_extended<T: std::io::Read>(
+        stream: &mut T,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut task = Task::from(stream)?;
+
+        let count = read_usize(stream)?;
+        for _ in 0..count {
+            let tag = read_u8(stream)?;
+            let len = read_usize(stream)?;
+            let mut payload = &read_bytes(stream, len)?[..];
+
+            match tag {
+                TAG_CREATED => task.created = read_i64(&mut payload)?,
+                TAG_COMPLETED => task.completed = read_i64(&mut payload)?,
+                TAG_CANCELLED => task.cancelled = read_i64(&mut payload)?,
+                TAG_PRIORITY => task.priority = Priority::from_u8(read_u8(&mut payload)?),
+                TAG_OVERDUE_HANDLED => task.overdue_handled = read_i64(&mut payload)?,
+                TAG_UUID => {
+                    task.uuid = Uuid::from_bytes(read_bytes(&mut payload, 16)?.try_into().unwrap())
+                }
+                TAG_LOG => {
+                    let entries = read_usize(&mut payload)?;
+                    for _ in 0..entries {
+                        task.log.push(LogEntry {
+                            timestamp: read_i64(&mut payload)?,
+                            amount: read_i64(&mut payload)?,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(task)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.progress.to_be_bytes());
+        bytes.extend_from_slice(&self.deadline.to_be_bytes());
+        bytes.extend_from_slice(&self.estimated_time.to_be_bytes());
+
+        let name_bytes = self.name.as_bytes();
+        bytes.extend_from_slice(&name_bytes.len().to_be_bytes());
+        bytes.extend_from_slice(name_bytes);
+
+        let desc_bytes = self.description.as_bytes();
+        bytes.extend_from_slice(&desc_bytes.len().to_be_bytes());
+        bytes.extend_from_slice(desc_bytes);
+
+        let mut extensions: Vec<(u8, Vec<u8>)> = vec![(TAG_UUID, self.uuid.as_bytes().to_vec())];
+        if self.created != 0 {
+            extensions.push((TAG_CREATED, self.created.to_be_bytes().to_vec()));
+        }
+        if !self.log.is_empty() {
+            let mut payload = Vec::from(self.log.len().to_be_bytes());
+            for entry in &self.log {
+                payload.extend_from_slice(&entry.timestamp.to_be_bytes());
+                payload.extend_from_slice(&entry.amount.to_be_bytes());
+            }
+            extensions.push((TAG_LOG, payload));
+        }
+        if self.completed != 0 {
+            extensions.push((TAG_COMPLETED, self.completed.to_be_bytes().to_vec()));
+        }
+        if self.cancelled != 0 {
+            extensions.push((TAG_CANCELLED, self.cancelled.to_be_bytes().to_vec()));
+        }
+        if self.priority != Priority::Normal {
+            extensions.push((TAG_PRIORITY, vec![self.priority as u8]));
+        }
+        if self.overdue_handled != 0 {
+            extensions.push((
+                TAG_OVERDUE_HANDLED,
+                self.overdue_handled.to_be_bytes().to_vec(),
+            ));
+        }
+
+        bytes.extend_from_slice(&extensions.len().to_be_bytes());
+        for (tag, payload) in extensions {
+            bytes.push(tag);
+            bytes.extend_from_slice(&payload.len().to_be_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        bytes
+    }
+
+    /// Adds `amount` to the progress and records it in the log. The task is
+    /// marked completed once the progress reaches the estimate.
+    pub fn log_progress(&mut self, amount: i64) {
+        let now = Local::now().timestamp();
+        self.progress += amount;
+        self.log.push(LogEntry {
+            timestamp: now,
+            amount,
+        });
+        if self.completed == 0 && self.progress >= self.estimated_time {
+            self.completed = now;
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed != 0
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled != 0
+    }
+
+    /// Neither completed nor cancelled.
+    pub fn is_open(&self) -> bool {
+        !self.is_completed() && !self.is_cancelled()
+    }
+
+    /// Fails unless the task is open, for changes only open tasks take.
+    pub fn ensure_open(&self) -> crate::Result<()> {
+        if self.is_completed() {
+            return Err(crate::Error::Input("Task is already completed".into()));
+        }
+        if self.is_cancelled() {
+            return Err(crate::Error::Input("Task is cancelled".into()));
+        }
+        Ok(())
+    }
+
+    /// Open and past its deadline.
+    pub fn is_overdue(&self) -> bool {
+        self.is_open() && self.get_time_left() < 0
+    }
+
+    pub fn get_completion(&self) -> f32 {
+        if self.estimated_time == 0 {
+            return 1.0;
+        }
+        (self.progress as f32) / (self.estimated_time as f32)
+    }
+
+    pub fn get_time_left(&self) -> i64 {
+        self.deadline - Local::now().timestamp()
+    }
+
+    /// Estimated work still to be done.
+    pub fn get_remaining(&self) -> i64 {
+        self.estimated_time - self.progress.min(self.estimated_time)
+    }
+
+    /// Time left once the remaining work is done; the lower, the more urgent.
+    pub fn get_slack(&self) -> i64 {
+        self.get_time_left() - self.get_remaining()
+    }
+
+    pub fn format_due(&self) -> String {
+        format_timestamp(self.deadline)
+    }
+
+    /// Hours of work per day needed to finish the remaining estimate by the
+    /// deadline, or `None` once the deadline has passed.
+    pub fn get_required_pace(&self) -> Option<f32> {
+        let tl = self.get_time_left();
+        if tl <= 0 {
+            return None;
+        }
+        Some((self.get_remaining() as f32 / 3600.0) / (tl as f32 / 86400.0))
+    }
+
+    /// Fraction of the time between creation and deadline that has passed, if
+    /// the creation time is known.
+    pub fn get_time_elapsed(&self) -> Option<f32> {
+        if self.created == 0 {
+            return None;
+        }
+        let total = self.deadline - self.created;
+        if total <= 0 {
+            return Some(1.0);
+        }
+        Some((Local::now().timestamp() - self.created) as f32 / total as f32)
+    }
+
+    fn render(&self) -> String {
+        self.render_highlighted(&[], &[])
+    }
+
+    /// The task card with the characters at the given positions of the name
+    /// and description highlighted.
+    pub fn render_highlighted(&self, name: &[usize], description: &[usize]) -> String {
+        let tl = self.get_time_left();
+        let s = if self.is_completed() {
+            format!("completed {}", format_timestamp(self.completed))
+                .green()
+                .to_string()
+        } else if self.is_cancelled() {
+            format!("cancelled {}", format_timestamp(self.cancelled))
+                .truecolor(128, 128, 128)
+                .to_string()
+        } else if self.is_overdue() {
+            format!(
+                "overdue by {}",
+                strip_colors(&format_duration(-tl)).trim_end()
+            )
+            .bright_red()
+            .bold()
+            .to_string()
+        } else {
+            urgency_colour(&format_duration(tl), tl)
+        };
+
+        let mut strings: Vec<(String, String)> = vec![
+            (
+                "Name:".truecolor(128, 128, 128).bold().to_string(),
+                highlight(&self.name, name, |s| s.bold()),
+            ),
+            (
+                "Description:".truecolor(128, 128, 128).bold().to_string(),
+                highlight(&self.description, description, |s| s.italic()),
+            ),
+            (
+                "Deadline:".truecolor(128, 128, 128).bold().to_string(),
+                self.format_due().truecolor(255, 140, 0).to_string(),
+            ),
+            ("Time left:".truecolor(128, 128, 128).bold().to_string(), s),
+            (
+                "Time to complete:"
+                    .truecolor(128, 128, 128)
+                    .bold()
+                    .to_string(),
+                format_duration(self.get_remaining()),
+            ),
+            (
+                "Progress:".truecolor(128, 128, 128).bold().to_string(),
+                progress_bar(self.get_completion()).to_string(),
+            ),
+            (
+                "Id:".truecolor(128, 128, 128).bold().to_string(),
+                self.id.to_string().cyan().to_string(),
+            ),
+        ];
+        if self.priority != Priority::Normal {
+            strings.insert(
+                2,
+                (
+                    "Priority:".truecolor(128, 128, 128).bold().to_string(),
+                    self.priority.colored(),
+                ),
+            );
+        }
+
+        card(strings)
+    }
+
+    /// Card with the values derived from the task's dates and its progress log.
+    pub fn render_details(&self) -> String {
+        let label = |s: &str| s.truecolor(128, 128, 128).bold().to_string();
+
+        let pace = match self.get_required_pace() {
+            Some(pace) => format!(
+                "{}/day",
+                strip_colors(&format_duration((pace * 3600.0).round() as i64))
+            )
+            .cyan()
+            .to_string(),
+            None if self.get_remaining() == 0 => "done".green().to_string(),
+            None => "overdue".bright_red().to_string(),
+        };
+        let elapsed = match self.get_time_elapsed() {
+            Some(elapsed) => progress_bar(elapsed),
+            None => "unknown".italic().to_string(),
+        };
+        let created = match self.created {
+            0 => "unknown".italic().to_string(),
+            created => format_timestamp(created).truecolor(255, 140, 0).to_string(),
+        };
+
+        let mut strings = vec![
+            (
+                label("UUID:"),
+                self.uuid.to_string().truecolor(128, 128, 128).to_string(),
+            ),
+            (label("Created:"), created),
+            (label("Required pace:"), pace),
+            (label("Time elapsed:"), elapsed),
+            (label("Completed:"), progress_bar(self.get_completion())),
+        ];
+        if self.log.is_empty() {
+            strings.push((label("Log:"), "no progress logged".italic().to_string()));
+        }
+        for entry in &self.log {
+            strings.push((
+                label(&format_timestamp(entry.timestamp)),
+                format!("+{}", strip_colors(&format_duration(entry.amount)))
+                    .green()
+                    .to_string(),
+            ));
+        }
+
+        card(strings)
+    }
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .unwrap()
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> Task {
+        let mut task = Task::with_id(3);
+        task.progress = 1800;
+        task.deadline = 1_900_000_000;
+        task.estimated_time = 7200;
+        task.name = "write report".into();
+        task.description = "quarterly numbers".into();
+        task
+    }
+
+    /// `task` as a legacy record, which ends where the extension block starts.
+    fn legacy(task: &Task) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for field in [task.id, task.progress, task.deadline, task.estimated_time] {
+            bytes.extend_from_slice(&field.to_be_bytes());
+        }
+        for text in [&task.name, &task.description] {
+            bytes.extend_from_slice(&text.len().to_be_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        bytes
+    }
+
+    /// The record for `task` with `extensions` as its extension block.
+    fn with_extensions(task: &Task, extensions: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = legacy(task);
+        bytes.extend_from_slice(&extensions.len().to_be_bytes());
+        for (tag, payload) in extensions {
+            bytes.push(*tag);
+            bytes.extend_from_slice(&payload.len().to_be_bytes());
+            bytes.extend_from_slice(payload);
+        }
+        bytes
+    }
+
+    #[test]
+    fn every_tag_survives_a_round_trip() {
+        let mut original = task();
+        original.created = 1_800_000_000;
+        original.log = vec![
+            LogEntry {
+                timestamp: 1_800_000_100,
+                amount: 600,
+            },
+            LogEntry {
+                timestamp: 1_800_000_200,
+                amount: 1200,
+            },
+        ];
+        original.completed = 1_800_000_300;
+        original.cancelled = 1_800_000_400;
+        original.priority = Priority::Urgent;
+        original.overdue_handled = 1_900_000_000;
+
+        let bytes = original.serialize();
+        let task = Task::from_extended(&mut &bytes[..]).unwrap();
+        assert_eq!(task.id(), 3);
+        assert_eq!(task.uuid(), original.uuid());
+        assert_eq!(task.progress, 1800);
+        assert_eq!(task.deadline, 1_900_000_000);
+        assert_eq!(task.estimated_time, 7200);
+        assert_eq!(task.name, "write report");
+        assert_eq!(task.description, "quarterly numbers");
+        assert_eq!(task.created, original.created);
+        assert_eq!(task.log, original.log);
+        assert_eq!(task.completed, original.completed);
+        assert_eq!(task.cancelled, original.cancelled);
+        assert_eq!(task.priority, Priority::Urgent);
+        assert_eq!(task.overdue_handled, original.overdue_handled);
+        assert_eq!(task.serialize(), bytes);
+    }
+
+    #[test]
+    fn reads_legacy_records_without_an_extension_block() {
+        let bytes = legacy(&task());
+        let mut stream = &bytes[..];
+        let task = Task::from(&mut stream).unwrap();
+
+        assert!(stream.is_empty());
+        assert_eq!(task.name, "write report");
+        assert_eq!(task.created, 0);
+        assert!(task.log.is_empty());
+    }
+
+    #[test]
+    fn skips_unknown_tags() {
+        let bytes = with_extensions(
+            &task(),
+            &[
+                (200, b"from a newer version".to_vec()),
+                (TAG_CREATED, 1_800_000_000i64.to_be_bytes().to_vec()),
+            ],
+        );
+        let mut stream = &bytes[..];
+        let task = Task::from_extended(&mut stream).unwrap();
+
+        assert!(stream.is_empty());
+        assert_eq!(task.created, 1_800_000_000);
+        assert_eq!(task.name, "write report");
+    }
+
+    #[test]
+    fn truncated_records_are_an_error() {
+        let mut original = task();
+        original.created = 1_800_000_000;
+        let bytes = original.serialize();
+
+        for len in [0, 10, bytes.len() - 20, bytes.len() - 1] {
+            assert!(Task::from_extended(&mut &bytes[..len]).is_err(), "{len}");
+        }
+    }
+
+    #[test]
+    fn oversized_lengths_are_an_error() {
+        let original = task();
+        let huge = (usize::MAX >> 1).to_be_bytes();
+
+        // The name's length follows the four numbers.
+        let mut bytes = original.serialize();
+        bytes[32..40].copy_from_slice(&huge);
+        assert!(Task::from_extended(&mut &bytes[..]).is_err());
+
+        let mut bytes = with_extensions(&original, &[(TAG_CREATED, vec![0; 8])]);
+        let payload_length = bytes.len() - 16;
+        bytes[payload_length..payload_length + 8].copy_from_slice(&huge);
+        assert!(Task::from_extended(&mut &bytes[..]).is_err());
+
+        let mut count = huge.to_vec();
+        count.extend_from_slice(&[0; 16]);
+        let bytes = with_extensions(&original, &[(TAG_LOG, count)]);
+        assert!(Task::from_extended(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn progress_up_to_the_estimate_completes_the_task() {
+        let mut task = task();
+        task.log_progress(3600);
+        assert!(!task.is_completed());
+
+        task.log_progress(1800);
+        assert!(task.is_completed());
+        assert_eq!(task.log.len(), 2);
+        assert_eq!(task.completed, task.log[1].timestamp);
+    }
+}
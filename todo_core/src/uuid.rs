@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use crate::Error;
+
+/// A task's permanent identity, which unlike its display number survives
+/// renumbering and never collides between task files.
+///
+/// Every task gets a random (version 4) UUID: new tasks when they are
+/// created, tasks from files written before UUIDs existed when their file is
+/// first read, after which it is saved with them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid([u8; 16]);
+impl Uuid {
+    pub const NIL: Uuid = Uuid([0; 16]);
+
+    pub fn new_v4() -> Self {
+        let mut bytes = [0; 16];
+        getrandom::getrandom(&mut bytes).expect("the system random number generator failed");
+        Uuid::with_version(bytes, 4)
+    }
+
+    fn with_version(mut bytes: [u8; 16], version: u8) -> Self {
+        bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Uuid(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    pub fn is_nil(&self) -> bool {
+        *self == Uuid::NIL
+    }
+
+    /// The first eight hex digits, enough to tell tasks apart at a glance.
+    pub fn short(&self) -> String {
+        self.to_string()[..8].to_owned()
+    }
+
+    /// Whether `prefix` is the start of this UUID, ignoring case and hyphens.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        let prefix: String = prefix
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        !prefix.is_empty() && self.simple().starts_with(&prefix)
+    }
+
+    /// The hex digits without hyphens.
+    fn simple(&self) -> String {
+        self.0.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = self.simple();
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+impl FromStr for Uuid {
+    type Err = Error;
+
+    /// Parses the hyphenated form or 32 plain hex digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: Vec<char> = s.chars().filter(|c| *c != '-').collect();
+        let invalid = || Error::Parse(format!("Invalid UUID \"{s}\""));
+        if hex.len() != 32 {
+            return Err(invalid());
+        }
+        let mut bytes = [0; 16];
+        for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+            let pair: String = pair.iter().collect();
+            *byte = u8::from_str_radix(&pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(Uuid(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_uuids_are_version_4() {
+        let uuid = Uuid::new_v4();
+        let text = uuid.to_string();
+        assert_eq!(text.len(), 36);
+        assert_eq!(&text[14..15], "4");
+        assert!("89ab".contains(&text[19..20]));
+        assert_ne!(uuid, Uuid::new_v4());
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        let uuid = Uuid::new_v4();
+        assert_eq!(uuid.to_string().parse::<Uuid>().unwrap(), uuid);
+        assert_eq!(uuid.simple().parse::<Uuid>().unwrap(), uuid);
+        assert!("1f3a".parse::<Uuid>().is_err());
+        assert!("zz3a5c7e-0000-4000-8000-000000000000"
+            .parse::<Uuid>()
+            .is_err());
+    }
+
+    #[test]
+    fn prefixes_ignore_case_and_hyphens() {
+        let uuid: Uuid = "1f3a5c7e-0000-4000-8000-000000000000".parse().unwrap();
+        assert!(uuid.starts_with("1F3A"));
+        assert!(uuid.starts_with("1f3a5c7e0000"));
+        assert!(uuid.starts_with("1f3a5c7e-0000-4"));
+        assert!(!uuid.starts_with("1f3b"));
+        assert!(!uuid.starts_with(""));
+        assert_eq!(uuid.short(), "1f3a5c7e");
+    }
+}